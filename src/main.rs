@@ -1,15 +1,25 @@
 use std::error::Error;
 use anyhow::{Result};
-use tokio::net::TcpListener;
+use tokio::net::{lookup_host, TcpSocket};
+use crate::server::arg_handler::ArgHandler;
 use crate::server::client_handler::handle_clients;
-use crate::server::common_variables::SERVER_IP_AND_PORT;
+use crate::server::memory::CountingAllocator;
 
 mod server;
 
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let listener = TcpListener::bind(SERVER_IP_AND_PORT).await?;
-    println!("Server listening on {}", SERVER_IP_AND_PORT);
-    handle_clients(listener).await?;
+    let args = ArgHandler::retrieve_args()?;
+    // Bind manually rather than with `TcpListener::bind`, so the accept backlog can be configured.
+    let address = lookup_host(args.server_address()).await?.next().ok_or("bind address did not resolve")?;
+    let socket = if address.is_ipv4() { TcpSocket::new_v4()? } else { TcpSocket::new_v6()? };
+    socket.set_reuseaddr(true)?;
+    socket.bind(address)?;
+    let listener = socket.listen(args.tcp_backlog)?;
+    println!("Server listening on {}", args.server_address());
+    handle_clients(listener, args).await?;
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,215 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::server::arg_handler::ArgsCli;
+use crate::server::common_variables::{ACTIVE_EXPIRE_ACCEPTABLE_STALE_PERCENT, ACTIVE_EXPIRE_CYCLE_BUDGET_MS, ACTIVE_EXPIRE_CYCLE_MS, ACTIVE_EXPIRE_KEYS_PER_LOOP, ACTIVE_EXPIRE_MAX_VISITS_PER_LOOP, Db, Keyspace};
+use crate::server::server_state::SharedState;
+
+/// Periodically removes expired keys from the database.
+///
+/// Keys are otherwise only removed lazily when they are read. Each cycle checks the `active_expire`
+/// flag, so the sweeper can be paused at runtime with `DEBUG SET-ACTIVE-EXPIRE 0`.
+///
+/// Like Redis's `activeExpireCycle`, a cycle never scans the whole keyspace: it works in batches of
+/// `ACTIVE_EXPIRE_KEYS_PER_LOOP` keys with an expiration (see `expire_batch`), taking the lock for one
+/// batch at a time and resuming where the previous batch stopped. It stops once a batch finds few
+/// expired keys (at most `ACTIVE_EXPIRE_ACCEPTABLE_STALE_PERCENT`), the whole table has been walked, or
+/// `ACTIVE_EXPIRE_CYCLE_BUDGET_MS` have passed.
+///
+/// With `active-expire-jitter` set, each key is removed only once it has been expired for its own
+/// offset within that window, so a large batch of keys sharing one deadline is spread over several
+/// cycles. Expired keys still count as missing for every command in the meantime.
+///
+/// # Arguments
+///
+/// * `db` - The shared database instance.
+/// * `args_cli` - The command-line arguments, holding `active-expire-jitter`.
+/// * `state` - The shared server state.
+///
+/// # Examples
+///
+/// ```
+/// tokio::spawn(active_expire_cycle(db.clone(), args_cli.clone(), state.clone()));
+/// ```
+pub async fn active_expire_cycle(db: Db, args_cli: ArgsCli, state: SharedState) {
+    let mut interval = tokio::time::interval(Duration::from_millis(ACTIVE_EXPIRE_CYCLE_MS));
+    let budget = Duration::from_millis(ACTIVE_EXPIRE_CYCLE_BUDGET_MS);
+    let jitter = args_cli.active_expire_jitter;
+    let mut cursor = 0;  // Position in the keyspace where the next batch starts.
+
+    loop {
+        interval.tick().await;
+        if !state.active_expire.load(Ordering::Relaxed) {
+            continue;
+        }
+
+        let started = Instant::now();
+        loop {
+            let batch = expire_batch(&mut db.lock().unwrap(), &mut cursor, jitter);
+            let mostly_live = batch.checked > 0 && batch.removed * 100 <= batch.checked * ACTIVE_EXPIRE_ACCEPTABLE_STALE_PERCENT;
+            if mostly_live || batch.wrapped || started.elapsed() >= budget {
+                break;
+            }
+        }
+    }
+}
+
+/// `ExpireBatch` reports what one batch of the active expire cycle did.
+#[derive(Debug, PartialEq, Eq)]
+struct ExpireBatch {
+    checked: usize,  // Keys with an expiration that were checked.
+    removed: usize,  // Expired keys that were removed.
+    wrapped: bool,   // Whether the batch reached the end of the table, so the next one starts over.
+}
+
+/// Removes the expired keys among the next `ACTIVE_EXPIRE_KEYS_PER_LOOP` keys with an expiration.
+///
+/// The batch starts at position `cursor` of the keyspace, which is looked up directly, and also stops
+/// after walking `ACTIVE_EXPIRE_MAX_VISITS_PER_LOOP` keys, so a batch touches a bounded number of keys
+/// however large the keyspace is. A removed key is replaced by the last one, which is checked next, so
+/// the keys behind the cursor never move past it; a key moved before the cursor by a DEL in between
+/// batches is simply found in the next pass.
+///
+/// # Arguments
+///
+/// * `db` - The locked keyspace.
+/// * `cursor` - Where the batch starts; advanced to where the next batch starts.
+/// * `jitter` - The `active-expire-jitter` window in milliseconds.
+///
+/// # Returns
+///
+/// Returns how many keys were checked and removed, and whether the end of the table was reached.
+fn expire_batch(db: &mut Keyspace, cursor: &mut usize, jitter: u64) -> ExpireBatch {
+    let mut visited = 0;
+    let mut checked = 0;
+    let mut removed = 0;
+    while checked < ACTIVE_EXPIRE_KEYS_PER_LOOP && visited < ACTIVE_EXPIRE_MAX_VISITS_PER_LOOP {
+        let Some((key, redis_item)) = db.get_index(*cursor) else {
+            break;
+        };
+        visited += 1;
+        if redis_item.get_expiration().is_some() {
+            checked += 1;
+            if redis_item.is_expired_for(expire_offset(key, jitter)) {
+                db.swap_remove_index(*cursor);  // The last key takes its place, so the cursor stays.
+                removed += 1;
+                continue;
+            }
+        }
+        *cursor += 1;
+    }
+
+    let wrapped = *cursor >= db.len();
+    if wrapped {
+        *cursor = 0;
+    }
+    ExpireBatch { checked, removed, wrapped }
+}
+
+/// Returns how long after its expiration a key is left to the sweeper, derived from a hash of the key.
+///
+/// The offset only depends on the key, so it stays the same from one cycle to the next.
+///
+/// # Arguments
+///
+/// * `key` - The key.
+/// * `jitter` - The `active-expire-jitter` window in milliseconds.
+///
+/// # Returns
+///
+/// Returns an offset between 0 and `jitter` milliseconds.
+fn expire_offset(key: &[u8], jitter: u64) -> Duration {
+    if jitter == 0 {
+        return Duration::ZERO;
+    }
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    Duration::from_millis(hasher.finish() % (jitter + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::server::command_handler::handle_command;
+    use crate::server::command_handler::tests::setup;
+    use crate::server::redis_item::RedisItem;
+    use crate::server::resp_response::RespResponse;
+    use crate::server::server_state::ServerState;
+
+    /// Builds a keyspace of `expired` expired keys, then `persistent` keys without an expiration.
+    fn keyspace(expired: usize, persistent: usize) -> Keyspace {
+        let past = SystemTime::now() - Duration::from_secs(1);
+        let expired_items = (0..expired).map(|index| (format!("expired:{}", index).into_bytes(), RedisItem::new_with_expiration(b"v".to_vec(), past)));
+        let persistent_items = (0..persistent).map(|index| (format!("persistent:{}", index).into_bytes(), RedisItem::new(b"v".to_vec())));
+        expired_items.chain(persistent_items).collect()
+    }
+
+    #[test]
+    fn a_batch_checks_a_bounded_number_of_keys() {
+        let mut db = keyspace(100, 0);
+        let mut cursor = 0;
+        let batch = expire_batch(&mut db, &mut cursor, 0);
+
+        assert_eq!(batch, ExpireBatch { checked: ACTIVE_EXPIRE_KEYS_PER_LOOP, removed: ACTIVE_EXPIRE_KEYS_PER_LOOP, wrapped: false });
+        assert_eq!(db.len(), 100 - ACTIVE_EXPIRE_KEYS_PER_LOOP);
+    }
+
+    #[test]
+    fn a_batch_walks_a_bounded_number_of_keys_without_expiration() {
+        let mut db = keyspace(0, ACTIVE_EXPIRE_MAX_VISITS_PER_LOOP * 2);
+        let mut cursor = 0;
+
+        assert_eq!(expire_batch(&mut db, &mut cursor, 0), ExpireBatch { checked: 0, removed: 0, wrapped: false });
+        assert_eq!(cursor, ACTIVE_EXPIRE_MAX_VISITS_PER_LOOP);
+        assert!(expire_batch(&mut db, &mut cursor, 0).wrapped);
+        assert_eq!(cursor, 0);
+    }
+
+    #[test]
+    fn a_batch_resumes_at_its_cursor() {
+        let mut db = keyspace(0, 100_000);
+        db.extend(keyspace(5, 0));
+        let mut cursor = 100_000;
+
+        assert_eq!(expire_batch(&mut db, &mut cursor, 0), ExpireBatch { checked: 5, removed: 5, wrapped: true });
+        assert_eq!((db.len(), cursor), (100_000, 0));
+    }
+
+    #[test]
+    fn one_pass_of_batches_removes_every_expired_key() {
+        let mut db = keyspace(150, 1000);
+        let mut cursor = 0;
+        while !expire_batch(&mut db, &mut cursor, 0).wrapped {}
+
+        assert_eq!(db.len(), 1000);
+        assert!(db.values().all(|redis_item| redis_item.get_expiration().is_none()));
+    }
+
+    #[tokio::test]
+    async fn a_paused_sweeper_leaves_expired_keys_to_lazy_removal() {
+        let (db, args_cli) = setup(&[]);
+        let state = ServerState::new_shared();
+        let run = |parts: &[&str]| {
+            let args: Vec<RespResponse> = parts.iter().map(|part| RespResponse::BulkString(part.as_bytes().to_vec())).collect();
+            handle_command(parts[0], &args, &db, &args_cli, &state, None, false).unwrap().serialize()
+        };
+        tokio::spawn(active_expire_cycle(db.clone(), args_cli.clone(), state.clone()));
+        let expiring_soon = || RedisItem::new_with_expiration(b"v".to_vec(), SystemTime::now() + Duration::from_millis(20));
+
+        assert_eq!(run(&["DEBUG", "SET-ACTIVE-EXPIRE", "0"]), b"+OK\r\n");
+        db.lock().unwrap().insert(b"k".to_vec(), expiring_soon());
+        tokio::time::sleep(Duration::from_millis(ACTIVE_EXPIRE_CYCLE_MS * 3)).await;
+        assert_eq!(db.lock().unwrap().len(), 1);  // Still stored, though already expired.
+        assert_eq!(run(&["GET", "k"]), b"$-1\r\n");
+        assert!(db.lock().unwrap().is_empty());  // Removed by the read.
+
+        assert_eq!(run(&["DEBUG", "SET-ACTIVE-EXPIRE", "1"]), b"+OK\r\n");
+        db.lock().unwrap().insert(b"k".to_vec(), expiring_soon());
+        tokio::time::sleep(Duration::from_millis(ACTIVE_EXPIRE_CYCLE_MS * 3)).await;
+        assert!(db.lock().unwrap().is_empty());  // Removed by the sweeper.
+    }
+}
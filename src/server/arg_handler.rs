@@ -1,5 +1,12 @@
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use clap::Parser;
+use anyhow::Context;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
+
+use crate::server::common_variables::{CONFIG_CONFIG, DEFAULT_BIND, DEFAULT_DB_FILENAME, DEFAULT_DIR, DEFAULT_MAXCLIENTS, DEFAULT_PORT, DEFAULT_PROTO_MAX_BULK_LEN, DEFAULT_PROTO_MAX_INLINE_LEN, DEFAULT_SAVE_POINTS, DEFAULT_SLOWLOG_LOG_SLOWER_THAN, DEFAULT_SLOWLOG_MAX_LEN, DEFAULT_TCP_BACKLOG, DEFAULT_TCP_KEEPALIVE, SAVE_CONFIG};
+use crate::server::config_file::{read_config_file, ConfigDirective};
+use crate::server::rdb_parser::rdb_path;
 
 /// `ArgsCli` is an alias for an `Arc`-wrapped `ArgHandler`, which holds the command-line arguments.
 pub type ArgsCli = Arc<ArgHandler>;
@@ -11,6 +18,14 @@ pub type ArgsCli = Arc<ArgHandler>;
 #[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
 pub struct ArgHandler {
+    /// Interface the server listens on.
+    #[arg(long, default_value = DEFAULT_BIND)]
+    pub bind: String,
+
+    /// TCP port the server listens on.
+    #[arg(long, default_value_t = DEFAULT_PORT)]
+    pub port: u16,
+
     /// Directory path provided by the user as a command-line argument.
     #[arg(long)]
     pub dir: Option<String>,
@@ -18,22 +33,135 @@ pub struct ArgHandler {
     /// Database filename provided by the user as a command-line argument.
     #[arg(long)]
     pub dbfilename: Option<String>,
+
+    /// Fail at startup if the RDB file exists but cannot be read, instead of starting with an empty database.
+    #[arg(long)]
+    pub rdb_strict: bool,
+
+    /// Return the keys of KEYS sorted lexicographically instead of in keyspace order, so tests can
+    /// assert on the reply. Meant for testing only; sorting makes KEYS slower on large databases.
+    #[arg(long)]
+    pub deterministic_order: bool,
+
+    /// Memory limit in bytes above which keys are evicted according to `maxmemory-policy` (0 means no limit).
+    #[arg(long, default_value_t = 0)]
+    pub maxmemory: usize,
+
+    /// Eviction policy applied once `maxmemory` is reached.
+    #[arg(long, value_enum, default_value_t = MaxMemoryPolicy::Noeviction)]
+    pub maxmemory_policy: MaxMemoryPolicy,
+
+    /// Save point as "<seconds> <changes>": snapshot the database in the background once at least
+    /// `changes` writes happened within `seconds`. May be repeated; defaults to "3600 1", "300 100" and "60 10000".
+    /// An empty value (`--save ""`) removes the save points given before it, so on its own it disables automatic saving.
+    #[arg(long, value_parser = parse_save_point)]
+    pub save: Vec<Option<(u64, u64)>>,
+
+    /// Window in milliseconds over which the background sweeper spreads the removal of keys sharing the same
+    /// deadline; each key is removed up to this long after it expired. 0 removes keys in the first sweep after expiry.
+    #[arg(long, default_value_t = 0)]
+    pub active_expire_jitter: u64,
+
+    /// Largest bulk string length (in bytes) a client may send; larger requests close the connection with a protocol error.
+    #[arg(long, default_value_t = DEFAULT_PROTO_MAX_BULK_LEN)]
+    pub proto_max_bulk_len: usize,
+
+    /// Longest line (inline command or RESP header) a client may send without its CRLF; longer lines close the connection with a protocol error.
+    #[arg(long, default_value_t = DEFAULT_PROTO_MAX_INLINE_LEN)]
+    pub proto_max_inline_len: usize,
+
+    /// Maximum number of simultaneously connected clients; further connections are refused with an error.
+    #[arg(long, default_value_t = DEFAULT_MAXCLIENTS)]
+    pub maxclients: u64,
+
+    /// Length of the queue of connections accepted by the kernel but not yet by the server.
+    #[arg(long, default_value_t = DEFAULT_TCP_BACKLOG)]
+    pub tcp_backlog: u32,
+
+    /// Idle time in seconds before TCP keepalive probes are sent to a silent client; 0 disables keepalive.
+    #[arg(long, default_value_t = DEFAULT_TCP_KEEPALIVE)]
+    pub tcp_keepalive: u64,
+
+    /// Execution time in microseconds above which a command is recorded in the slow log; negative disables it.
+    #[arg(long, default_value_t = DEFAULT_SLOWLOG_LOG_SLOWER_THAN, allow_negative_numbers = true)]
+    pub slowlog_log_slower_than: i64,
+
+    /// Maximum number of entries kept in the slow log.
+    #[arg(long, default_value_t = DEFAULT_SLOWLOG_MAX_LEN)]
+    pub slowlog_max_len: usize,
+
+    /// Latency in milliseconds at or above which an operation is recorded by the latency monitor; 0 disables it.
+    #[arg(long, default_value_t = 0)]
+    pub latency_monitor_threshold: u64,
+
+    /// Path of a redis.conf-style configuration file. Its directives override the defaults, explicit
+    /// command-line flags override the file, and `CONFIG REWRITE` writes the current configuration back to it.
+    #[arg(long)]
+    pub config: Option<String>,
+}
+
+/// `MaxMemoryPolicy` selects how keys are evicted once the `maxmemory` limit is reached.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaxMemoryPolicy {
+    /// Never evict keys.
+    Noeviction,
+    /// Evict the least frequently used keys among all keys.
+    AllkeysLfu,
 }
 
 impl ArgHandler {
     /// Parses the command-line arguments and returns them wrapped in an `Arc`.
     ///
+    /// If `--config` is given, the directives of that file are applied first, and any flag also given on
+    /// the command line takes precedence over the file.
+    ///
     /// # Returns
     ///
-    /// Returns an `ArgsCli` type, which is an `Arc` containing the parsed `ArgHandler`.
+    /// Returns an `ArgsCli` type, which is an `Arc` containing the parsed `ArgHandler`, or an error if the
+    /// config file could not be read or contains an invalid directive.
     ///
     /// # Examples
     ///
     /// ```
-    /// let args = ArgHandler::retrieve_args();
+    /// let args = ArgHandler::retrieve_args()?;
+    /// ```
+    pub fn retrieve_args() -> Result<ArgsCli, anyhow::Error> {
+        let cli_args: Vec<String> = std::env::args().collect();
+        let cli_matches = ArgHandler::command().get_matches_from(&cli_args);
+        let args = ArgHandler::from_arg_matches(&cli_matches)?;
+
+        let Some(config_path) = &args.config else {
+            return Ok(Arc::new(args));
+        };
+        let directives = read_config_file(Path::new(config_path))?;
+
+        // The file's directives go first, so flags repeated on the command line override them.
+        let mut merged_args = vec![cli_args[0].clone()];
+        for directive in &directives {
+            merged_args.extend(directive_to_args(directive, &cli_matches)
+                .with_context(|| format!("Invalid config file {}", config_path))?);
+        }
+        merged_args.extend(cli_args.into_iter().skip(1));
+
+        let matches = ArgHandler::command().try_get_matches_from(merged_args)
+            .map_err(|e| anyhow::anyhow!("Invalid value in config file {}: {}", config_path, e))?;
+        Ok(Arc::new(ArgHandler::from_arg_matches(&matches)?))
+    }
+
+    /// Returns the address the server listens on.
+    ///
+    /// # Returns
+    ///
+    /// Returns the `bind:port` address as a `String`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let args = ArgHandler::retrieve_args()?;
+    /// let listener = TcpListener::bind(args.server_address()).await?;
     /// ```
-    pub fn retrieve_args() -> ArgsCli {
-        Arc::new(ArgHandler::parse())
+    pub fn server_address(&self) -> String {
+        format!("{}:{}", self.bind, self.port)
     }
 
     /// Checks if both `dir` and `dbfilename` are provided by the user.
@@ -53,4 +181,168 @@ impl ArgHandler {
     pub fn can_be_parsed(&self) -> bool {
         self.dir.is_some() && self.dbfilename.is_some()
     }
+
+    /// Returns the configured save points, falling back to the defaults only when `save` was never given.
+    ///
+    /// As in Redis, an empty `save` clears the save points listed before it.
+    ///
+    /// # Returns
+    ///
+    /// Returns the save points as `(seconds, changes)` pairs; empty if automatic saving is disabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let args = ArgHandler::retrieve_args();
+    /// for (seconds, changes) in args.save_points() {
+    ///     println!("save after {seconds}s if {changes} keys changed");
+    /// }
+    /// ```
+    pub fn save_points(&self) -> Vec<(u64, u64)> {
+        if self.save.is_empty() {
+            return DEFAULT_SAVE_POINTS.to_vec();
+        }
+        let cleared = self.save.iter().rposition(Option::is_none).map_or(0, |index| index + 1);
+        self.save[cleared..].iter().flatten().copied().collect()
+    }
+
+    /// Returns the path snapshots are written to, using the current directory and `dump.rdb` when `dir`
+    /// or `dbfilename` are not provided.
+    ///
+    /// # Returns
+    ///
+    /// Returns the full path of the RDB file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let args = ArgHandler::retrieve_args();
+    /// let path = args.rdb_file_path();
+    /// ```
+    pub fn rdb_file_path(&self) -> PathBuf {
+        rdb_path(
+            self.dir.as_deref().unwrap_or(DEFAULT_DIR),
+            self.dbfilename.as_deref().unwrap_or(DEFAULT_DB_FILENAME),
+        )
+    }
+}
+
+/// Parses a save point given as "<seconds> <changes>", or an empty value that clears the save points.
+///
+/// # Arguments
+///
+/// * `value` - The raw argument value.
+///
+/// # Returns
+///
+/// Returns the `(seconds, changes)` pair, `None` for an empty value, or an error message if the value is malformed.
+fn parse_save_point(value: &str) -> Result<Option<(u64, u64)>, String> {
+    let mut parts = value.split_whitespace();
+    match (parts.next(), parts.next(), parts.next()) {
+        (None, _, _) => Ok(None),
+        (Some(seconds), Some(changes), None) => {
+            let seconds = seconds.parse().map_err(|_| format!("invalid seconds in save point '{value}'"))?;
+            let changes = changes.parse().map_err(|_| format!("invalid changes in save point '{value}'"))?;
+            Ok(Some((seconds, changes)))
+        }
+        _ => Err(format!("save point must be \"<seconds> <changes>\", got '{value}'")),
+    }
+}
+
+/// Converts a config file directive into the equivalent command-line flags.
+///
+/// Directives for flags already given on the command line are skipped, so the command line wins.
+/// `save` may list several `<seconds> <changes>` pairs on one line, or `""` to disable automatic saving. Boolean flags take `yes` or `no`.
+/// Directives this server does not support are reported and ignored.
+///
+/// # Arguments
+///
+/// * `directive` - The directive read from the config file.
+/// * `cli_matches` - The matches of the actual command line.
+///
+/// # Returns
+///
+/// Returns the command-line arguments for the directive, or an error naming the line if its arguments are invalid.
+fn directive_to_args(directive: &ConfigDirective, cli_matches: &ArgMatches) -> Result<Vec<String>, anyhow::Error> {
+    let command = ArgHandler::command();
+    let Some(arg) = command.get_arguments().find(|arg| arg.get_long() == Some(directive.name.as_str())) else {
+        eprintln!("Warning: ignoring unsupported config directive '{}' at line {}", directive.name, directive.line);
+        return Ok(Vec::new());
+    };
+    if directive.name == CONFIG_CONFIG || cli_matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+        return Ok(Vec::new());
+    }
+
+    let flag = format!("--{}", directive.name);
+    let invalid = || anyhow::anyhow!("Bad directive or wrong number of arguments at line {}: '{}'", directive.line, directive.name);
+    let values: Vec<&str> = directive.values.iter().map(String::as_str).collect();
+
+    if directive.name == SAVE_CONFIG {
+        if values == [""] {
+            return Ok(vec![flag, String::new()]);  // `save ""` disables automatic saving.
+        }
+        if !values.len().is_multiple_of(2) {
+            return Err(invalid());
+        }
+        return Ok(values.chunks(2).flat_map(|pair| [flag.clone(), pair.join(" ")]).collect());
+    }
+    if !arg.get_action().takes_values() {
+        return match values.as_slice() {
+            ["yes"] => Ok(vec![flag]),
+            ["no"] => Ok(Vec::new()),
+            _ => Err(invalid()),
+        };
+    }
+    match values.as_slice() {
+        [value] => Ok(vec![flag, value.to_string()]),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a command line, without the program name.
+    fn parse(args: &[&str]) -> ArgHandler {
+        ArgHandler::try_parse_from(std::iter::once("redis-rust").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn save_points_default_only_when_save_is_not_given() {
+        assert_eq!(parse(&[]).save_points(), DEFAULT_SAVE_POINTS.to_vec());
+        assert_eq!(parse(&["--save", "900 1"]).save_points(), vec![(900, 1)]);
+    }
+
+    #[test]
+    fn empty_save_disables_automatic_saving() {
+        assert!(parse(&["--save", ""]).save_points().is_empty());
+        assert_eq!(parse(&["--save", "900 1", "--save", "", "--save", "60 5"]).save_points(), vec![(60, 5)]);
+    }
+
+    #[test]
+    fn empty_save_directive_becomes_an_empty_flag() {
+        let directive = ConfigDirective { name: SAVE_CONFIG.to_string(), values: vec![String::new()], line: 1 };
+        let matches = ArgHandler::command().get_matches_from(["redis-rust"]);
+        assert_eq!(directive_to_args(&directive, &matches).unwrap(), vec!["--save".to_string(), String::new()]);
+    }
+
+    #[test]
+    fn directives_become_command_line_arguments() {
+        let matches = ArgHandler::command().get_matches_from(["redis-rust", "--port", "7000"]);
+        let to_args = |name: &str, values: &[&str]| {
+            let directive = ConfigDirective { name: name.to_string(), values: values.iter().map(|value| value.to_string()).collect(), line: 1 };
+            directive_to_args(&directive, &matches).map_err(|error| error.to_string())
+        };
+
+        assert_eq!(to_args("save", &["3600", "1", "300", "100"]).unwrap(), ["--save", "3600 1", "--save", "300 100"]);
+        assert!(to_args("save", &["3600"]).is_err());
+        assert_eq!(to_args("dir", &["/my data"]).unwrap(), ["--dir", "/my data"]);
+        assert!(to_args("dir", &["a", "b"]).is_err());
+        assert_eq!(to_args("rdb-strict", &["yes"]).unwrap(), ["--rdb-strict"]);
+        assert!(to_args("rdb-strict", &["no"]).unwrap().is_empty());
+        assert!(to_args("rdb-strict", &["maybe"]).is_err());
+        assert!(to_args("appendonly", &["no"]).unwrap().is_empty());  // Unsupported directives are ignored.
+        assert!(to_args("port", &["6000"]).unwrap().is_empty());  // The command line wins.
+    }
 }
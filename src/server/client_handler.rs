@@ -1,23 +1,32 @@
-use std::collections::HashMap;
 use std::error::Error;
+use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+use socket2::{SockRef, TcpKeepalive};
 use tokio::io;
+use tokio::io::AsyncWriteExt;
 use tokio::net::{TcpListener, TcpStream};
 
-use crate::server::arg_handler::{ArgHandler, ArgsCli};
+use crate::server::active_expire::active_expire_cycle;
+use crate::server::arg_handler::ArgsCli;
 use crate::server::command_handler::CommandHandler;
-use crate::server::common_variables::Db;
+use crate::server::common_variables::{Db, Keyspace, MAX_CLIENTS_ERR_STR};
 use crate::server::rdb_parser::RdbParser;
+use crate::server::resp_response::RespResponse;
+use crate::server::server_state::{ServerState, SharedState};
+use crate::server::snapshot::auto_save_cycle;
 
 /// Handles incoming client connections on the provided `TcpListener`.
 ///
 /// This function listens for incoming connections and spawns a new task to handle each client.
+/// Once `maxclients` clients are connected, new connections are answered with an error and closed.
 /// It also initializes the database, either by loading data from an RDB file or creating a new, empty database.
 ///
 /// # Arguments
 ///
 /// * `listener` - A `TcpListener` that listens for incoming client connections.
+/// * `retrieved_args` - The parsed command-line arguments.
 ///
 /// # Returns
 ///
@@ -26,51 +35,109 @@ use crate::server::rdb_parser::RdbParser;
 /// # Examples
 ///
 /// ```
-/// let listener = TcpListener::bind("127.0.0.1:6379").await?;
-/// handle_clients(listener).await?;
+/// let args = ArgHandler::retrieve_args()?;
+/// let listener = TcpListener::bind(args.server_address()).await?;
+/// handle_clients(listener, args).await?;
 /// ```
-pub async fn handle_clients(listener: TcpListener) -> Result<(), Box<dyn Error>> {
-    // Retrieve command-line arguments.
-    let retrieved_args = ArgHandler::retrieve_args();
-    let db: Db;
-
+pub async fn handle_clients(listener: TcpListener, retrieved_args: ArgsCli) -> Result<(), Box<dyn Error>> {
     // Check if the necessary arguments are provided and populate the database if possible.
-    if retrieved_args.can_be_parsed() {
+    let db: Db = if retrieved_args.can_be_parsed() {
         let rdb = RdbParser::new(retrieved_args.clone());
-        db = rdb.populate_database()?
+        rdb.populate_database()?
     } else {
         // If arguments are not provided, initialize an empty in-memory database.
-        db = Arc::new(Mutex::new(HashMap::new()))
-    }
+        Arc::new(Mutex::new(Keyspace::new()))
+    };
+    let state = ServerState::new_shared();
+
+    // Start the background task that removes expired keys.
+    tokio::spawn(active_expire_cycle(db.clone(), retrieved_args.clone(), state.clone()));
+
+    // Start the background task that snapshots the database when a save point is reached.
+    tokio::spawn(auto_save_cycle(db.clone(), retrieved_args.clone(), state.clone()));
 
     loop {
         // Accept a new client connection.
         let (socket, addr) = listener.accept().await?;
+
+        // Refuse the connection if the client limit is reached, without blocking the accept loop.
+        let Some(slot) = ClientSlot::reserve(&state, retrieved_args.maxclients) else {
+            tokio::spawn(refuse_client(socket));
+            continue;
+        };
         println!("New client: {addr:?}");
 
-        // Clone the database and command-line arguments to be used in the client handler.
+        // Clone the database, command-line arguments and server state to be used in the client handler.
         let db = db.clone();
         let cli_args = retrieved_args.clone();
+        let state = state.clone();
 
-        // Spawn a new task to handle the client asynchronously.
+        // Spawn a new task to handle the client asynchronously, holding its slot until it returns.
         tokio::spawn(async move {
-            if let Err(e) = process_client(socket, db, cli_args).await {
+            if let Err(e) = process_client(socket, db, cli_args, state).await {
                 eprintln!("Error processing client: {e}");
             }
+            drop(slot);
         });
     }
 }
 
+/// `ClientSlot` is one of the `maxclients` connection slots, counted in `connected_clients` until it is dropped.
+struct ClientSlot {
+    state: SharedState,
+}
+
+impl ClientSlot {
+    /// Reserves a slot for a new connection.
+    ///
+    /// The check and the increment are a single atomic update, so concurrent reservations can never
+    /// take the count past `maxclients`.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The shared server state, holding the count of connected clients.
+    /// * `maxclients` - The maximum number of simultaneously connected clients.
+    ///
+    /// # Returns
+    ///
+    /// Returns the slot, or `None` if every slot is taken.
+    fn reserve(state: &SharedState, maxclients: u64) -> Option<ClientSlot> {
+        state.connected_clients
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |connected| (connected < maxclients).then_some(connected + 1))
+            .ok()
+            .map(|_| ClientSlot { state: state.clone() })
+    }
+}
+
+impl Drop for ClientSlot {
+    fn drop(&mut self) {
+        self.state.connected_clients.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Tells a client that the server has no room for it and closes the connection, like Redis does once
+/// `maxclients` clients are connected.
+///
+/// # Arguments
+///
+/// * `socket` - The refused connection.
+async fn refuse_client(mut socket: TcpStream) {
+    let _ = socket.write_all(&RespResponse::Error(MAX_CLIENTS_ERR_STR.to_string()).serialize()).await;
+    let _ = socket.shutdown().await;  // The client may already be gone; there is nothing left to tell it.
+}
+
 /// Processes an individual client's commands.
 ///
-/// This function splits the TCP stream into a reader and a writer, then creates a `CommandHandler`
-/// to process the client's commands asynchronously.
+/// This function disables Nagle's algorithm so small replies are sent immediately, enables TCP keepalive
+/// when `tcp-keepalive` is set, then splits the TCP stream into a reader and a writer and creates a
+/// `CommandHandler` to process the client's commands asynchronously.
 ///
 /// # Arguments
 ///
 /// * `stream` - The `TcpStream` representing the client's connection.
 /// * `db` - The shared database instance.
 /// * `cli_args` - The command-line arguments.
+/// * `state` - The shared server state.
 ///
 /// # Returns
 ///
@@ -80,15 +147,61 @@ pub async fn handle_clients(listener: TcpListener) -> Result<(), Box<dyn Error>>
 ///
 /// ```
 /// let stream = TcpStream::connect("127.0.0.1:6379").await?;
-/// process_client(stream, db, cli_args).await?;
+/// process_client(stream, db, cli_args, state).await?;
 /// ```
-pub async fn process_client(stream: TcpStream, db: Db, cli_args: ArgsCli) -> Result<(), anyhow::Error> {
+pub async fn process_client(stream: TcpStream, db: Db, cli_args: ArgsCli, state: SharedState) -> Result<(), anyhow::Error> {
+    // Send replies without waiting to coalesce them, and detect dead peers if keepalive is configured.
+    stream.set_nodelay(true)?;
+    if cli_args.tcp_keepalive > 0 {
+        let keepalive = TcpKeepalive::new().with_time(Duration::from_secs(cli_args.tcp_keepalive));
+        SockRef::from(&stream).set_tcp_keepalive(&keepalive)?;
+    }
+
     // Split the TCP stream into a reader and writer for asynchronous I/O.
+    let addr = stream.peer_addr()?;
     let (reader, writer) = io::split(stream);
 
     // Create a new CommandHandler to manage the client's commands.
-    let mut handler = CommandHandler::new(reader, writer, db, cli_args);
+    let mut handler = CommandHandler::new(reader, writer, db, cli_args, state, addr);
 
     // Run the CommandHandler to process the client's commands.
     handler.run().await
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+    use crate::server::arg_handler::ArgHandler;
+
+    #[tokio::test]
+    async fn connections_past_maxclients_are_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let args_cli = Arc::new(ArgHandler::parse_from(["redis-rust", "--maxclients", "2"]));
+
+        let clients = async {
+            let mut admitted = Vec::new();
+            for _ in 0..2 {
+                let mut client = TcpStream::connect(addr).await.unwrap();
+                client.write_all(b"PING\r\n").await.unwrap();
+                let mut reply = [0; 7];
+                client.read_exact(&mut reply).await.unwrap();
+                assert_eq!(&reply, b"+PONG\r\n");
+                admitted.push(client);
+            }
+
+            let mut refused = TcpStream::connect(addr).await.unwrap();
+            let mut reply = Vec::new();
+            refused.read_to_end(&mut reply).await.unwrap();  // Ends once the server closes the connection.
+            assert_eq!(reply, RespResponse::Error(MAX_CLIENTS_ERR_STR.to_string()).serialize());
+        };
+
+        tokio::select! {
+            result = handle_clients(listener, args_cli) => panic!("the server stopped: {:?}", result.err()),
+            _ = clients => {}
+        }
+    }
+}
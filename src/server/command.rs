@@ -1,22 +1,69 @@
+use std::path::Path;
 use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use anyhow::Result;
 
-use crate::server::arg_handler::ArgsCli;
-use crate::server::common_variables::{Db, DIR_ARG_COMMAND, DB_FILENAME_ARG_COMMAND, GET_COMMAND, OK_STR, PONG_STR, PX_ARG_COMMAND};
+use crate::server::arg_handler::{ArgsCli, MaxMemoryPolicy};
+use crate::server::common_variables::{ABSTTL_ARG_COMMAND, AND_ARG_COMMAND, ASYNC_ARG_COMMAND, BGSAVE_IN_PROGRESS_ERR_STR, BGSAVE_STARTED_STR, BITOP_NOT_ERR_STR, BIT_ARG_ERR_STR, BIT_OFFSET_ERR_STR, BIT_VALUE_ERR_STR, BUSYKEY_ERR_STR, CHANGE_REPL_ID_ARG_COMMAND, CLUSTER_COMMAND, CLUSTER_HELP, CLUSTER_INFO_STR, COMMAND_COMMAND, COMMAND_HELP, CONFIG_COMMAND, CONFIG_HELP, COUNT_ARG_COMMAND, Db, DBSIZE_EXPIRE_CLEANUP_LIMIT, DB_FILENAME_ARG_COMMAND, DEBUG_COMMAND, DEBUG_HELP, DEFAULT_DB_FILENAME, DEFAULT_DIR, DIR_ARG_COMMAND, DOCS_ARG_COMMAND, ENCODING_ARG_COMMAND, ERROR_ARG_COMMAND, EXAT_ARG_COMMAND, EX_ARG_COMMAND, FREQ_ARG_COMMAND, FREQ_REQUIRES_LFU_ERR_STR, GETKEYS_ARG_COMMAND, GET_COMMAND, HELP_ARG_COMMAND, HISTORY_ARG_COMMAND, IDLETIME_ARG_COMMAND, INFO_ARG_COMMAND, INVALID_COMMAND_ARITY_ERR_STR, INVALID_COMMAND_ERR_STR, INVALID_FREQ_ERR_STR, INVALID_IDLETIME_ERR_STR, INVALID_TTL_ERR_STR, KEEPTTL_ARG_COMMAND, Keyspace, LATENCY_COMMAND, LATENCY_HELP, LATEST_ARG_COMMAND, LEN_ARG_COMMAND, LIST_ARG_COMMAND, LRU_CLOCK_MAX, MAX_BIT_OFFSET, MAX_QUOTED_ARG_LEN, MSET_COMMAND, MYID_ARG_COMMAND, NODE_ID_LEN, NOT_ARG_COMMAND, NOT_FLOAT_ERR_STR, NOT_INTEGER_ERR_STR, NO_CONFIG_FILE_ERR_STR, NO_KEY_ARGUMENTS_ERR_STR, NO_SUCH_KEY_ERR_STR, NX_ARG_COMMAND, OBJECT_ARG_COMMAND, OBJECT_COMMAND, OBJECT_HELP, OFFSET_RANGE_ERR_STR, OK_STR, OR_ARG_COMMAND, OVERFLOW_ERR_STR, PERSIST_ARG_COMMAND, PONG_STR, PXAT_ARG_COMMAND, PX_ARG_COMMAND, REPLACE_ARG_COMMAND, RESET_COMMAND, REWRITE_ARG_COMMAND, SET_ACTIVE_EXPIRE_ARG_COMMAND, SLEEP_ARG_COMMAND, SLEEP_BEFORE_REPLY_ARG_COMMAND, SLOWLOG_COMMAND, SLOWLOG_DEFAULT_GET_COUNT, SLOWLOG_HELP, STRINGMATCH_LEN_ARG_COMMAND, STRING_TOO_LONG_ERR_STR, SYNC_ARG_COMMAND, SYNTAX_ERR_STR, UNSUPPORTED_COMMAND_ERR_STR, VERSION_ARG_COMMAND, XOR_ARG_COMMAND, XX_ARG_COMMAND};
 use crate::server::redis_item::RedisItem;
 use crate::server::resp_response::RespResponse;
+use crate::server::info::build_info;
+use crate::server::glob::glob_match;
+use crate::server::server_state::{random_hex_id, SharedState};
+use crate::server::command_table::{lookup_command, CommandSpec, COMMAND_TABLE};
+use crate::server::config_file::{config_entries, rewrite_config};
+use crate::server::rdb_parser::decode_dump_payload;
+use crate::server::rdb_writer::{encode_dump_payload, serialized_length};
+use crate::server::snapshot::{bgsave, run_in_background, save, unix_time_secs};
 
 /// Enum representing different types of commands that can be executed by the server.
 pub enum Command<'a> {
     Ping,                                        // Handles the "PING" command.
     Echo(&'a [RespResponse]),                    // Handles the "ECHO" command with arguments.
-    Set(&'a [RespResponse], &'a Db),             // Handles the "SET" command with arguments and a reference to the database.
-    Get(&'a [RespResponse], &'a Db),             // Handles the "GET" command with arguments and a reference to the database.
+    Set(&'a [RespResponse], &'a Db, &'a SharedState), // Handles the "SET" command with arguments, the database and the server state.
+    Get(&'a [RespResponse], &'a Db, &'a SharedState, bool), // Handles the "GET" command with arguments, the database, the server state and whether to leave access metadata untouched.
     ConfigGet(&'a [RespResponse], &'a ArgsCli),  // Handles the "CONFIG GET" command with arguments and a reference to the CLI arguments.
-    Keys(&'a [RespResponse], &'a Db),            // Handles the "KEYS" command with arguments and a reference to the database.
-    Unknown,                                     // Represents an unknown command.
+    Keys(&'a [RespResponse], &'a Db, &'a ArgsCli), // Handles the "KEYS" command with arguments, the database and the CLI arguments.
+    GetEx(&'a [RespResponse], &'a Db, &'a SharedState, bool), // Handles the "GETEX" command with arguments, the database, the server state and whether to leave access metadata untouched.
+    GetDel(&'a [RespResponse], &'a Db, &'a SharedState), // Handles the "GETDEL" command with arguments, the database and the server state.
+    Append(&'a [RespResponse], &'a Db, &'a SharedState), // Handles the "APPEND" command with arguments, the database and the server state.
+    Incr(&'a [RespResponse], &'a Db, &'a SharedState), // Handles the "INCR" command with arguments, the database and the server state.
+    Decr(&'a [RespResponse], &'a Db, &'a SharedState), // Handles the "DECR" command with arguments, the database and the server state.
+    IncrBy(&'a [RespResponse], &'a Db, &'a SharedState), // Handles the "INCRBY" command with arguments, the database and the server state.
+    DecrBy(&'a [RespResponse], &'a Db, &'a SharedState), // Handles the "DECRBY" command with arguments, the database and the server state.
+    Object(&'a [RespResponse], &'a Db, &'a ArgsCli), // Handles the "OBJECT" command with arguments, the database and the CLI arguments.
+    Debug(&'a [RespResponse], &'a Db, &'a SharedState), // Handles the "DEBUG" command with arguments, the database and the server state.
+    ExpireTime(&'a [RespResponse], &'a Db),      // Handles the "EXPIRETIME" command with arguments and a reference to the database.
+    PExpireTime(&'a [RespResponse], &'a Db),     // Handles the "PEXPIRETIME" command with arguments and a reference to the database.
+    Lolwut(&'a [RespResponse]),                  // Handles the "LOLWUT" command with arguments.
+    SetBit(&'a [RespResponse], &'a Db, &'a SharedState), // Handles the "SETBIT" command with arguments, the database and the server state.
+    SetRange(&'a [RespResponse], &'a Db, &'a ArgsCli, &'a SharedState), // Handles the "SETRANGE" command with arguments, the database, the CLI arguments and the server state.
+    GetBit(&'a [RespResponse], &'a Db, &'a SharedState, bool), // Handles the "GETBIT" command with arguments, the database, the server state and whether to leave access metadata untouched.
+    BitCount(&'a [RespResponse], &'a Db, &'a SharedState, bool), // Handles the "BITCOUNT" command with arguments, the database, the server state and whether to leave access metadata untouched.
+    BitPos(&'a [RespResponse], &'a Db, &'a SharedState, bool), // Handles the "BITPOS" command with arguments, the database, the server state and whether to leave access metadata untouched.
+    BitOp(&'a [RespResponse], &'a Db, &'a SharedState), // Handles the "BITOP" command with arguments, the database and the server state.
+    Save(&'a Db, &'a ArgsCli, &'a SharedState),  // Handles the "SAVE" command with the database, the CLI arguments and the server state.
+    BgSave(&'a Db, &'a ArgsCli, &'a SharedState), // Handles the "BGSAVE" command with the database, the CLI arguments and the server state.
+    FlushAll(&'a [RespResponse], &'a Db, &'a SharedState), // Handles the "FLUSHALL" command with arguments, the database and the server state.
+    DbSize(&'a Db),                              // Handles the "DBSIZE" command with a reference to the database.
+    ClusterNoOp,                                 // Handles the "READONLY", "READWRITE" and "ASKING" commands, which are no-ops without cluster mode.
+    Cluster(&'a [RespResponse], &'a SharedState), // Handles the "CLUSTER" command with arguments and a reference to the server state.
+    Time,                                        // Handles the "TIME" command.
+    MGet(&'a [RespResponse], &'a Db, &'a SharedState, bool), // Handles the "MGET" command with arguments, the database, the server state and whether to leave access metadata untouched.
+    MSet(&'a [RespResponse], &'a Db, &'a SharedState), // Handles the "MSET" command with arguments, the database and the server state.
+    Del(&'a [RespResponse], &'a Db, &'a SharedState), // Handles the "DEL" command with arguments, the database and the server state.
+    Exists(&'a [RespResponse], &'a Db),          // Handles the "EXISTS" command with arguments and a reference to the database.
+    Introspect(&'a [RespResponse]),              // Handles the "COMMAND" command with arguments.
+    Info(&'a [RespResponse], &'a Db, &'a SharedState), // Handles the "INFO" command with arguments, the database and the server state.
+    Dump(&'a [RespResponse], &'a Db),            // Handles the "DUMP" command with arguments and a reference to the database.
+    Restore(&'a [RespResponse], &'a Db, &'a ArgsCli, &'a SharedState), // Handles the "RESTORE" command with arguments, the database, the CLI arguments and the server state.
+    SlowLog(&'a [RespResponse], &'a SharedState), // Handles the "SLOWLOG" command with arguments and a reference to the server state.
+    Latency(&'a [RespResponse], &'a SharedState), // Handles the "LATENCY" command with arguments and a reference to the server state.
+    LastSave(&'a SharedState),                   // Handles the "LASTSAVE" command with a reference to the server state.
+    Unsupported,                                 // Represents a known Redis command this server deliberately does not implement (scripting).
+    Unknown(&'a str),                            // Represents an unknown command, with the name the client sent.
 }
 
 impl<'a> Command<'a> {
@@ -29,15 +76,127 @@ impl<'a> Command<'a> {
         match self {
             Command::Ping => handle_ping_command(),                       // Execute the PING command.
             Command::Echo(args) => handle_echo_command(args),             // Execute the ECHO command.
-            Command::Set(args, db) => handle_set_command(args, db),       // Execute the SET command.
-            Command::Get(args, db) => handle_get_command(args, db),       // Execute the GET command.
+            Command::Set(args, db, state) => handle_set_command(args, db, state), // Execute the SET command.
+            Command::Get(args, db, state, no_touch) => handle_get_command(args, db, state, *no_touch), // Execute the GET command.
             Command::ConfigGet(args, args_cli) => handle_config(args, args_cli), // Execute the CONFIG GET command.
-            Command::Keys(args, db) => handle_keys(args, db),             // Execute the KEYS command.
-            _ => Ok(RespResponse::SimpleString("-ERR unknown command".to_string())), // Handle unknown commands.
+            Command::Keys(args, db, args_cli) => handle_keys(args, db, args_cli), // Execute the KEYS command.
+            Command::GetEx(args, db, state, no_touch) => handle_getex_command(args, db, state, *no_touch), // Execute the GETEX command.
+            Command::GetDel(args, db, state) => handle_getdel_command(args, db, state), // Execute the GETDEL command.
+            Command::Append(args, db, state) => handle_append_command(args, db, state), // Execute the APPEND command.
+            Command::Incr(args, db, state) => handle_incr_by(args, db, state, Some(1)), // Execute the INCR command.
+            Command::Decr(args, db, state) => handle_incr_by(args, db, state, Some(-1)), // Execute the DECR command.
+            Command::IncrBy(args, db, state) => handle_incr_by(args, db, state, None), // Execute the INCRBY command.
+            Command::DecrBy(args, db, state) => handle_decr_by(args, db, state), // Execute the DECRBY command.
+            Command::Object(args, db, args_cli) => handle_object(args, db, args_cli), // Execute the OBJECT command.
+            Command::Debug(args, db, state) => handle_debug(args, db, state), // Execute the DEBUG command.
+            Command::ExpireTime(args, db) => handle_expiretime(args, db, false), // Execute the EXPIRETIME command.
+            Command::PExpireTime(args, db) => handle_expiretime(args, db, true), // Execute the PEXPIRETIME command.
+            Command::Lolwut(args) => handle_lolwut(args),                 // Execute the LOLWUT command.
+            Command::SetBit(args, db, state) => handle_setbit(args, db, state),         // Execute the SETBIT command.
+            Command::SetRange(args, db, args_cli, state) => handle_setrange(args, db, args_cli, state), // Execute the SETRANGE command.
+            Command::GetBit(args, db, state, no_touch) => handle_getbit(args, db, state, *no_touch), // Execute the GETBIT command.
+            Command::BitCount(args, db, state, no_touch) => handle_bitcount(args, db, state, *no_touch), // Execute the BITCOUNT command.
+            Command::BitPos(args, db, state, no_touch) => handle_bitpos(args, db, state, *no_touch), // Execute the BITPOS command.
+            Command::BitOp(args, db, state) => handle_bitop(args, db, state),           // Execute the BITOP command.
+            Command::Save(db, args_cli, state) => handle_save(db, args_cli, state),     // Execute the SAVE command.
+            Command::BgSave(db, args_cli, state) => handle_bgsave(db, args_cli, state), // Execute the BGSAVE command.
+            Command::FlushAll(args, db, state) => handle_flushall(args, db, state),     // Execute the FLUSHALL command.
+            Command::DbSize(db) => handle_dbsize(db),                     // Execute the DBSIZE command.
+            Command::ClusterNoOp => Ok(RespResponse::SimpleString(OK_STR.to_string())), // Acknowledge READONLY, READWRITE and ASKING.
+            Command::Cluster(args, state) => handle_cluster(args, state), // Execute the CLUSTER command.
+            Command::Time => handle_time(),                               // Execute the TIME command.
+            Command::MGet(args, db, state, no_touch) => handle_mget(args, db, state, *no_touch), // Execute the MGET command.
+            Command::MSet(args, db, state) => handle_mset(args, db, state),             // Execute the MSET command.
+            Command::Del(args, db, state) => handle_del(args, db, state),               // Execute the DEL command.
+            Command::Exists(args, db) => handle_exists(args, db),         // Execute the EXISTS command.
+            Command::Introspect(args) => handle_command_introspection(args), // Execute the COMMAND command.
+            Command::Info(args, db, state) => handle_info(args, db, state), // Execute the INFO command.
+            Command::Dump(args, db) => handle_dump(args, db),             // Execute the DUMP command.
+            Command::Restore(args, db, args_cli, state) => handle_restore(args, db, args_cli, state), // Execute the RESTORE command.
+            Command::SlowLog(args, state) => handle_slowlog(args, state), // Execute the SLOWLOG command.
+            Command::Latency(args, state) => handle_latency(args, state), // Execute the LATENCY command.
+            Command::LastSave(state) => handle_lastsave(state),          // Execute the LASTSAVE command.
+            Command::Unsupported => Ok(RespResponse::Error(UNSUPPORTED_COMMAND_ERR_STR.to_string())), // Reject scripting commands.
+            Command::Unknown(name) => Ok(RespResponse::Error(format!("ERR unknown command '{}'", quoted_arg(name)))), // Handle unknown commands.
         }
     }
 }
 
+/// Checks that a command received at least `min` arguments, counting the command name itself.
+///
+/// # Arguments
+///
+/// * `cmd` - The command name (or `command|subcommand`), used in the error message.
+/// * `args` - A slice of `RespResponse` arguments, starting with the command name.
+/// * `min` - The minimum number of arguments.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if there are enough arguments, or the "wrong number of arguments" error `RespResponse` otherwise.
+pub fn check_arity(cmd: &str, args: &[RespResponse], min: usize) -> Result<(), RespResponse> {
+    if args.len() < min {
+        return Err(wrong_arity(cmd));
+    }
+    Ok(())
+}
+
+/// Builds the error returned when a command receives the wrong number of arguments.
+///
+/// # Arguments
+///
+/// * `cmd` - The command name (or `command|subcommand`).
+///
+/// # Returns
+///
+/// Returns the "wrong number of arguments" error `RespResponse`.
+pub fn wrong_arity(cmd: &str) -> RespResponse {
+    RespResponse::Error(format!("ERR wrong number of arguments for '{}' command", cmd.to_ascii_lowercase()))
+}
+
+/// Builds the `command|subcommand` name used in arity errors of container commands.
+///
+/// # Arguments
+///
+/// * `command` - The container command name (e.g. "OBJECT").
+/// * `subcommand` - The subcommand as sent by the client.
+///
+/// # Returns
+///
+/// Returns the lowercase full name (e.g. "object|freq").
+pub fn subcommand_name(command: &str, subcommand: &str) -> String {
+    format!("{}|{}", command, subcommand).to_ascii_lowercase()
+}
+
+/// Looks up a key, treating an item that expired but was not removed yet as absent.
+///
+/// Every read and introspection command goes through this (or `lookup_live_mut`), so a stale value is never reported.
+///
+/// # Arguments
+///
+/// * `map` - The locked database.
+/// * `key` - The key to look up.
+///
+/// # Returns
+///
+/// Returns the item stored at the key, or `None` if the key does not exist or is expired.
+fn lookup_live<'a>(map: &'a Keyspace, key: &[u8]) -> Option<&'a RedisItem> {
+    map.get(key).filter(|redis_item| !redis_item.is_expired())
+}
+
+/// Looks up a key for modification, treating an item that expired but was not removed yet as absent.
+///
+/// # Arguments
+///
+/// * `map` - The locked database.
+/// * `key` - The key to look up.
+///
+/// # Returns
+///
+/// Returns the item stored at the key, or `None` if the key does not exist or is expired.
+fn lookup_live_mut<'a>(map: &'a mut Keyspace, key: &[u8]) -> Option<&'a mut RedisItem> {
+    map.get_mut(key).filter(|redis_item| !redis_item.is_expired())
+}
+
 /// Handles the "PING" command.
 ///
 /// # Returns
@@ -55,61 +214,158 @@ fn handle_ping_command() -> Result<RespResponse, anyhow::Error> {
 ///
 /// # Returns
 ///
-/// Returns a `RespResponse` containing the second argument, or an empty string if none is provided.
+/// Returns a `RespResponse` containing the second argument, or an error if none is provided.
 fn handle_echo_command(args: &[RespResponse]) -> Result<RespResponse, anyhow::Error> {
-    Ok(args.get(1).cloned().unwrap_or(RespResponse::SimpleString("".to_string())))
+    Ok(args[1].clone())
+}
+
+/// `SetExpiration` is the expiration the "SET" command gives the new value.
+enum SetExpiration {
+    Persistent,        // No expiration option: the key becomes persistent.
+    At(SystemTime),    // `EX`, `PX`, `EXAT` or `PXAT`: the key expires at the given time.
+    Keep,              // `KEEPTTL`: the key keeps the expiration of the value it replaces.
+}
+
+/// `SetCondition` restricts when the "SET" command writes the new value.
+#[derive(PartialEq, Eq)]
+enum SetCondition {
+    Always,     // No condition: the value is always written.
+    IfMissing,  // `NX`: only write if the key does not exist.
+    IfPresent,  // `XX`: only write if the key already exists.
+}
+
+/// `SetOptions` holds the options given to the "SET" command after the key and value.
+struct SetOptions {
+    expiration: SetExpiration,  // The expiration of the new value.
+    condition: SetCondition,    // When the new value is written.
+    get: bool,                  // `GET`: reply with the previous value instead of `OK`.
 }
 
-/// Parses the expiration time from the command arguments, if provided.
+/// Parses the options of the "SET" command.
+///
+/// At most one of `EX`, `PX`, `EXAT`, `PXAT` and `KEEPTTL` may be given, and `NX` and `XX` exclude each other.
 ///
 /// # Arguments
 ///
-/// * `args` - A slice of `RespResponse` arguments.
+/// * `options` - The arguments following the key and value.
 ///
 /// # Returns
 ///
-/// Returns an `Option<SystemTime>` representing the expiration time, or `None` if no expiration is provided.
-fn parse_expiration(args: &[RespResponse]) -> Option<SystemTime> {
-    if args.len() >= 4 {
-        let expiration_type = args.get(3).unwrap().get_value().to_ascii_uppercase();
-        if expiration_type.eq(PX_ARG_COMMAND) {
-            if let Ok(expire_millis) = args.get(4).unwrap().get_value().parse::<u64>() {
-                return Some(SystemTime::now() + Duration::from_millis(expire_millis));
+/// Returns the parsed `SetOptions`, or an error `RespResponse` for unknown, repeated or conflicting
+/// options and invalid expire times.
+fn parse_set_options(options: &[RespResponse]) -> Result<SetOptions, RespResponse> {
+    let mut set_options = SetOptions { expiration: SetExpiration::Persistent, condition: SetCondition::Always, get: false };
+    let mut pos = 0;
+
+    while pos < options.len() {
+        let option = options[pos].get_value().to_ascii_uppercase();
+        let expiration_set = !matches!(set_options.expiration, SetExpiration::Persistent);
+        match option.as_str() {
+            KEEPTTL_ARG_COMMAND if !expiration_set => set_options.expiration = SetExpiration::Keep,
+            EX_ARG_COMMAND | PX_ARG_COMMAND | EXAT_ARG_COMMAND | PXAT_ARG_COMMAND if !expiration_set && pos + 1 < options.len() => {
+                pos += 1;
+                set_options.expiration = SetExpiration::At(parse_expiration_option(&option, &options[pos].get_value(), "set")?);
             }
+            NX_ARG_COMMAND if set_options.condition != SetCondition::IfPresent => set_options.condition = SetCondition::IfMissing,
+            XX_ARG_COMMAND if set_options.condition != SetCondition::IfMissing => set_options.condition = SetCondition::IfPresent,
+            GET_COMMAND => set_options.get = true,
+            _ => return Err(RespResponse::Error(SYNTAX_ERR_STR.to_string())),
         }
+        pos += 1;
+    }
+    Ok(set_options)
+}
+
+/// Converts an expiration option and its argument into an absolute expiration time.
+///
+/// `EX` and `PX` are relative to the current time (in seconds and milliseconds), while `EXAT` and `PXAT`
+/// are absolute unix timestamps (in seconds and milliseconds).
+///
+/// # Arguments
+///
+/// * `option` - The uppercased option name (`EX`, `PX`, `EXAT` or `PXAT`).
+/// * `value` - The option's argument.
+/// * `command_name` - The lowercase command name, used in the error message.
+///
+/// # Returns
+///
+/// Returns the expiration as a `SystemTime`, or an error `RespResponse` if the option is unknown or the value is invalid.
+fn parse_expiration_option(option: &str, value: &str, command_name: &str) -> Result<SystemTime, RespResponse> {
+    let amount = value.parse::<i64>()
+        .map_err(|_| RespResponse::Error(NOT_INTEGER_ERR_STR.to_string()))?;
+    let invalid_expire = || RespResponse::Error(format!("ERR invalid expire time in '{}' command", command_name));
+    if amount <= 0 {
+        return Err(invalid_expire());
     }
-    None
+
+    let amount = amount as u64;
+    let expiration = match option {
+        EX_ARG_COMMAND => SystemTime::now().checked_add(Duration::from_secs(amount)),
+        PX_ARG_COMMAND => SystemTime::now().checked_add(Duration::from_millis(amount)),
+        EXAT_ARG_COMMAND => UNIX_EPOCH.checked_add(Duration::from_secs(amount)),
+        PXAT_ARG_COMMAND => UNIX_EPOCH.checked_add(Duration::from_millis(amount)),
+        _ => return Err(RespResponse::Error(SYNTAX_ERR_STR.to_string())),
+    };
+    expiration.ok_or_else(invalid_expire)
 }
 
 /// Handles the "SET" command, which sets a key-value pair in the database.
 ///
+/// The new value replaces any existing one along with its expiration, unless `KEEPTTL` is given. `NX` and
+/// `XX` only write the value if the key is missing or present, and `GET` replies with the previous value.
+///
 /// # Arguments
 ///
 /// * `args` - A slice of `RespResponse` arguments.
 /// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose dirty counter counts the changed keys.
 ///
 /// # Returns
 ///
-/// Returns a `RespResponse` indicating success.
-fn handle_set_command(args: &[RespResponse], db: &Db) -> Result<RespResponse, anyhow::Error> {
-    let set_key: String = args.get(1).unwrap().get_value();   // Retrieve the key to set.
-    let set_value: String = args.get(2).unwrap().get_value(); // Retrieve the value to set.
+/// Returns `OK` (or with `GET`, the previous value or null), null if `NX`/`XX` prevented the write, or an
+/// error for invalid options.
+fn handle_set_command(args: &[RespResponse], db: &Db, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    let set_key: Vec<u8> = args.get(1).unwrap().get_bytes();   // Retrieve the key to set.
+    let set_value: Vec<u8> = args.get(2).unwrap().get_bytes(); // Retrieve the value to set.
 
-    let expiration = parse_expiration(args);  // Parse any expiration time provided.
+    let set_options = match parse_set_options(&args[3..]) {
+        Ok(set_options) => set_options,
+        Err(error) => return Ok(error),
+    };
+
+    // Read the previous value, check the condition and write under one lock, so the reply matches what was replaced.
+    let mut db = db.lock().unwrap();
+    let previous = lookup_live(&db, &set_key);
+    let previous_reply = set_options.get.then(|| match previous {
+        Some(redis_item) => RespResponse::SharedBulkString(redis_item.get_shared_data()),
+        None => RespResponse::NullBulkString,
+    });
+    let allowed = match set_options.condition {
+        SetCondition::Always => true,
+        SetCondition::IfMissing => previous.is_none(),
+        SetCondition::IfPresent => previous.is_some(),
+    };
+    if !allowed {
+        return Ok(previous_reply.unwrap_or(RespResponse::NullBulkString));
+    }
+
+    let expiration = match set_options.expiration {
+        SetExpiration::Persistent => None,
+        SetExpiration::At(expiration) => Some(expiration),
+        SetExpiration::Keep => previous.and_then(RedisItem::get_expiration),
+    };
 
     // Create a `RedisItem` with or without expiration.
-    let redis_item = if let Some(exp) = expiration {
-        RedisItem::new_with_expiration(set_value, exp)
-    } else {
-        RedisItem::new(set_value)
+    let redis_item = match expiration {
+        Some(expiration) => RedisItem::new_with_expiration(set_value, expiration),
+        None => RedisItem::new(set_value),
     };
 
     // Insert the key-value pair into the database.
-    let mut db = db.lock().unwrap();
     db.insert(set_key, redis_item);
+    state.mark_dirty(1);
 
-    // Return a success response.
-    Ok(RespResponse::SimpleString(OK_STR.to_string()))
+    Ok(previous_reply.unwrap_or_else(|| RespResponse::SimpleString(OK_STR.to_string())))
 }
 
 /// Handles the "GET" command, which retrieves a value from the database.
@@ -118,104 +374,1978 @@ fn handle_set_command(args: &[RespResponse], db: &Db) -> Result<RespResponse, an
 ///
 /// * `args` - A slice of `RespResponse` arguments.
 /// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose keyspace hit and miss counters are updated.
+/// * `no_touch` - Whether the client asked (CLIENT NO-TOUCH) not to update the key's access metadata.
 ///
 /// # Returns
 ///
 /// Returns a `RespResponse` containing the value or indicating that the key does not exist or is expired.
-fn handle_get_command(args: &[RespResponse], db: &Db) -> Result<RespResponse, anyhow::Error> {
-    let get_key: String = args.get(1).unwrap().get_value();  // Retrieve the key to get.
-    let db = db.lock().unwrap();
+fn handle_get_command(args: &[RespResponse], db: &Db, state: &SharedState, no_touch: bool) -> Result<RespResponse, anyhow::Error> {
+    let get_key: Vec<u8> = args.get(1).unwrap().get_bytes();  // Retrieve the key to get.
+    let mut db = db.lock().unwrap();
 
-    // Check if the key exists in the database and is not expired.
-    match db.get(&get_key) {
+    // Check if the key exists in the database and is not expired; an expired key counts as a miss.
+    match db.get_mut(&get_key) {
         Some(redis_item) => {
             if redis_item.is_expired() {
+                state.record_keyspace_lookup(false);
+                db.swap_remove(&get_key);  // Lazily remove the expired item.
                 Ok(RespResponse::NullBulkString)  // Return null if the item is expired.
             } else {
-                Ok(RespResponse::BulkString(redis_item.get_data().clone()))  // Return the value if not expired.
+                state.record_keyspace_lookup(true);
+                if !no_touch {
+                    redis_item.touch();
+                }
+                Ok(RespResponse::SharedBulkString(redis_item.get_shared_data()))  // Return the value if not expired.
             }
         }
-        None => Ok(RespResponse::NullBulkString),  // Return null if the key does not exist.
+        None => {
+            state.record_keyspace_lookup(false);
+            Ok(RespResponse::NullBulkString)  // Return null if the key does not exist.
+        }
     }
 }
 
-/// Handles the "CONFIG GET" command, which retrieves configuration values.
+/// Handles the "GETEX" command, which retrieves a value and optionally updates or removes its expiration.
+///
+/// Without options it behaves exactly like GET. `EX`, `PX`, `EXAT` and `PXAT` set a new expiration,
+/// while `PERSIST` removes any existing one.
 ///
 /// # Arguments
 ///
 /// * `args` - A slice of `RespResponse` arguments.
-/// * `args_cli` - A reference to the command-line arguments.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose keyspace hit and miss counters and dirty counter are updated.
+/// * `no_touch` - Whether the client asked (CLIENT NO-TOUCH) not to update the key's access metadata.
 ///
 /// # Returns
 ///
-/// Returns a `RespResponse` containing the configuration value or null if the command is not recognized.
-fn handle_config(args: &[RespResponse], args_cli: &ArgsCli) -> Result<RespResponse, anyhow::Error> {
-    let subcommand: String = args.get(1).unwrap().get_value();  // Retrieve the subcommand (e.g., "GET").
-    let get_key: String = args.get(2).unwrap().get_value();     // Retrieve the key for the configuration value.
+/// Returns a `RespResponse` containing the value, null if the key does not exist or is expired, or an error for invalid options.
+fn handle_getex_command(args: &[RespResponse], db: &Db, state: &SharedState, no_touch: bool) -> Result<RespResponse, anyhow::Error> {
+    let get_key: Vec<u8> = args.get(1).unwrap().get_bytes();  // Retrieve the key to get.
+
+    // Parse the expiration change before touching the database, so invalid options leave the key untouched.
+    let new_expiration = match args.len() {
+        2 => None,
+        3 if args[2].get_value().eq_ignore_ascii_case(PERSIST_ARG_COMMAND) => Some(None),
+        4 => {
+            let option = args[2].get_value().to_ascii_uppercase();
+            match parse_expiration_option(&option, &args[3].get_value(), "getex") {
+                Ok(expiration) => Some(Some(expiration)),
+                Err(error) => return Ok(error),
+            }
+        }
+        _ => return Ok(RespResponse::Error(SYNTAX_ERR_STR.to_string())),
+    };
 
-    match subcommand.as_str() {
-        GET_COMMAND => handle_config_get(get_key, args_cli),  // Handle the "GET" subcommand.
-        _ => Ok(RespResponse::NullBulkString)  // Return null if the subcommand is not recognized.
+    let mut db = db.lock().unwrap();
+    match lookup_live_mut(&mut db, &get_key) {
+        Some(redis_item) => {
+            state.record_keyspace_lookup(true);
+            if !no_touch {
+                redis_item.touch();
+            }
+            match new_expiration {
+                Some(Some(expiration)) => {
+                    redis_item.set_expiration(expiration);
+                    state.mark_dirty(1);
+                }
+                Some(None) if redis_item.get_expiration().is_some() => {
+                    redis_item.persist();
+                    state.mark_dirty(1);
+                }
+                Some(None) | None => {}
+            }
+            Ok(RespResponse::SharedBulkString(redis_item.get_shared_data()))
+        }
+        None => {
+            state.record_keyspace_lookup(false);
+            Ok(RespResponse::NullBulkString)  // Return null if the key does not exist or is expired.
+        }
     }
 }
 
-/// Retrieves specific configuration values based on the provided key.
+/// Handles the "GETDEL" command, which retrieves a value and deletes its key.
+///
+/// The key is removed and its value read under a single database lock, so neither other clients nor
+/// the active expire cycle can change the key in between.
 ///
 /// # Arguments
 ///
-/// * `get_key` - The key for the configuration value to retrieve.
-/// * `args_cli` - A reference to the command-line arguments.
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose keyspace hit and miss counters and dirty counter are updated.
 ///
 /// # Returns
 ///
-/// Returns a `RespResponse` containing the configuration value or null if the key is not recognized.
-fn handle_config_get(get_key: String, args_cli: &ArgsCli) -> Result<RespResponse, anyhow::Error> {
-    let result = match get_key.as_str() {
-        DIR_ARG_COMMAND => {
-            let arg_name = RespResponse::BulkString(DIR_ARG_COMMAND.to_string());
-            let arg_value = RespResponse::BulkString(args_cli.dir.clone().unwrap());
-            vec![arg_name, arg_value]
+/// Returns a `RespResponse` containing the value, or null if the key does not exist or is expired.
+fn handle_getdel_command(args: &[RespResponse], db: &Db, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    let get_key: Vec<u8> = args.get(1).unwrap().get_bytes();  // Retrieve the key to get and delete.
+    let mut db = db.lock().unwrap();
+
+    // An expired item is removed as well, but reported as missing.
+    match db.swap_remove(&get_key).filter(|redis_item| !redis_item.is_expired()) {
+        Some(redis_item) => {
+            state.record_keyspace_lookup(true);
+            state.mark_dirty(1);
+            Ok(RespResponse::SharedBulkString(redis_item.get_shared_data()))
+        }
+        None => {
+            state.record_keyspace_lookup(false);
+            Ok(RespResponse::NullBulkString)
+        }
+    }
+}
+
+/// Handles the "APPEND" command, which appends a value to the string stored at a key.
+///
+/// If the key does not exist (or is expired) it is created with the given value. An existing key
+/// keeps its expiration.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose dirty counter counts the changed keys.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` containing the length of the string after the append.
+fn handle_append_command(args: &[RespResponse], db: &Db, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    let append_key: Vec<u8> = args.get(1).unwrap().get_bytes();   // Retrieve the key to append to.
+    let append_value: Vec<u8> = args.get(2).unwrap().get_bytes(); // Retrieve the value to append.
+
+    let mut db = db.lock().unwrap();
+    state.mark_dirty(1);
+    match lookup_live_mut(&mut db, &append_key) {
+        Some(redis_item) => {
+            redis_item.touch();
+            let data = redis_item.get_data_mut();
+            data.extend_from_slice(&append_value);
+            Ok(RespResponse::Integer(data.len() as i64))
         }
-        DB_FILENAME_ARG_COMMAND => {
-            let arg_name = RespResponse::BulkString(DB_FILENAME_ARG_COMMAND.to_string());
-            let arg_value = RespResponse::BulkString(args_cli.dbfilename.clone().unwrap());
-            vec![arg_name, arg_value]
+        None => {
+            let new_len = append_value.len();
+            db.insert(append_key, RedisItem::new(append_value));
+            Ok(RespResponse::Integer(new_len as i64))
         }
-        _ => vec![]  // Return an empty vector if the key is not recognized.
+    }
+}
+
+/// Handles the "SETRANGE" command, which overwrites part of the string stored at a key, starting at an offset.
+///
+/// The string is padded with zero bytes if the offset lies past its end, and a missing (or expired) key
+/// is created as an empty string first. An existing key keeps its expiration. An empty value changes
+/// nothing, so it does not create the key either. As in Redis, the result may not be longer than
+/// `proto-max-bulk-len`.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `args_cli` - A reference to the command-line arguments, holding `proto-max-bulk-len`.
+/// * `state` - A reference to the shared server state, whose dirty counter counts the changed keys.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` containing the length of the string after the change, or an error if the
+/// offset is negative or the string would grow too long.
+fn handle_setrange(args: &[RespResponse], db: &Db, args_cli: &ArgsCli, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    let key: Vec<u8> = args.get(1).unwrap().get_bytes();
+    let Ok(offset) = args.get(2).unwrap().get_value().parse::<i64>() else {
+        return Ok(RespResponse::Error(NOT_INTEGER_ERR_STR.to_string()));
     };
+    let Ok(offset) = usize::try_from(offset) else {
+        return Ok(RespResponse::Error(OFFSET_RANGE_ERR_STR.to_string()));
+    };
+    let value: Vec<u8> = args.get(3).unwrap().get_bytes();
 
-    // Return the configuration values as an array or null if not found.
-    if result.is_empty() {
-        Ok(RespResponse::NullBulkString)
-    } else {
-        Ok(RespResponse::RespArray(Arc::new(result)))
+    let mut db = db.lock().unwrap();
+    let current_len = lookup_live(&db, &key).map_or(0, |redis_item| redis_item.get_data().len());
+    if value.is_empty() {
+        return Ok(RespResponse::Integer(current_len as i64));  // Nothing to write, like Redis.
+    }
+    if offset.saturating_add(value.len()) > args_cli.proto_max_bulk_len {
+        return Ok(RespResponse::Error(STRING_TOO_LONG_ERR_STR.to_string()));
+    }
+
+    if lookup_live(&db, &key).is_none() {
+        db.insert(key.clone(), RedisItem::new(Vec::new()));
+    }
+    let redis_item = db.get_mut(&key).unwrap();
+    redis_item.touch();
+
+    let data = redis_item.get_data_mut();
+    if data.len() < offset + value.len() {
+        data.resize(offset + value.len(), 0);
     }
+    data[offset..offset + value.len()].copy_from_slice(&value);
+    state.mark_dirty(1);
+    Ok(RespResponse::Integer(data.len() as i64))
 }
 
-/// Handles the "KEYS" command, which retrieves keys matching a pattern.
+/// Handles the "INCR", "DECR" and "INCRBY" commands, which add an integer to the value stored at a key.
+///
+/// A missing (or expired) key is treated as `0`. An existing key keeps its expiration.
 ///
 /// # Arguments
 ///
 /// * `args` - A slice of `RespResponse` arguments.
 /// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose dirty counter counts the changed keys.
+/// * `delta` - The fixed amount to add, or `None` to read it from the third argument.
 ///
 /// # Returns
 ///
-/// Returns a `RespResponse` containing an array of matching keys or null if no matches are found.
-fn handle_keys(args: &[RespResponse], db: &Db) -> Result<RespResponse, anyhow::Error> {
-    let get_key_pattern: String = args.get(1).unwrap().get_value();
-    let db = db.lock().unwrap();
-    let mut response_array = vec![];
+/// Returns a `RespResponse` containing the new value, or an error if a value is not an integer or the result overflows.
+fn handle_incr_by(args: &[RespResponse], db: &Db, state: &SharedState, delta: Option<i64>) -> Result<RespResponse, anyhow::Error> {
+    let delta = match delta {
+        Some(delta) => delta,
+        None => match args.get(2).unwrap().get_value().parse::<i64>() {
+            Ok(delta) => delta,
+            Err(_) => return Ok(RespResponse::Error(NOT_INTEGER_ERR_STR.to_string())),
+        },
+    };
+    Ok(incr_by(args.get(1).unwrap().get_bytes(), db, state, delta))
+}
+
+/// Handles the "DECRBY" command, which subtracts an integer from the value stored at a key.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose dirty counter counts the changed keys.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` containing the new value, or an error if a value is not an integer or the result overflows.
+fn handle_decr_by(args: &[RespResponse], db: &Db, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    match args.get(2).unwrap().get_value().parse::<i64>().ok().and_then(i64::checked_neg) {
+        Some(delta) => Ok(incr_by(args.get(1).unwrap().get_bytes(), db, state, delta)),
+        None => Ok(RespResponse::Error(NOT_INTEGER_ERR_STR.to_string())),
+    }
+}
+
+/// Adds `delta` to the integer stored at `key`, preserving the key's expiration.
+///
+/// # Arguments
+///
+/// * `key` - The key holding the integer.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose dirty counter counts the changed keys.
+/// * `delta` - The amount to add.
+///
+/// # Returns
+///
+/// Returns the new value as an integer `RespResponse`, or an error `RespResponse`.
+fn incr_by(key: Vec<u8>, db: &Db, state: &SharedState, delta: i64) -> RespResponse {
+    let mut db = db.lock().unwrap();
+    let live_item = lookup_live_mut(&mut db, &key);
+
+    let current = match &live_item {
+        Some(redis_item) => match std::str::from_utf8(redis_item.get_data()).ok().and_then(|data| data.parse::<i64>().ok()) {
+            Some(value) => value,
+            None => return RespResponse::Error(NOT_INTEGER_ERR_STR.to_string()),
+        },
+        None => 0,
+    };
+    let Some(new_value) = current.checked_add(delta) else {
+        return RespResponse::Error(OVERFLOW_ERR_STR.to_string());
+    };
+
+    match live_item {
+        Some(redis_item) => {
+            redis_item.touch();
+            redis_item.set_data(new_value.to_string().into_bytes());
+        }
+        None => {
+            db.insert(key, RedisItem::new(new_value.to_string().into_bytes()));
+        }
+    }
+    state.mark_dirty(1);
+    RespResponse::Integer(new_value)
+}
+
+/// Handles the "OBJECT" command, which inspects the internals of the value stored at a key.
+///
+/// Supported subcommands:
+/// * `FREQ key` - the logarithmic access-frequency counter (only available under an LFU `maxmemory-policy`).
+/// * `ENCODING key` - the internal encoding of the value (`int`, `embstr` or `raw`).
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `args_cli` - A reference to the command-line arguments.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` with the requested information, null if the key does not exist, or an error.
+fn handle_object(args: &[RespResponse], db: &Db, args_cli: &ArgsCli) -> Result<RespResponse, anyhow::Error> {
+    let subcommand: String = args.get(1).unwrap().get_value();  // Retrieve the subcommand (e.g., "FREQ").
+
+    match subcommand.to_ascii_uppercase().as_str() {
+        HELP_ARG_COMMAND => Ok(help_reply(OBJECT_COMMAND, OBJECT_HELP)),
+        FREQ_ARG_COMMAND => {
+            if let Err(error) = check_arity(&subcommand_name(OBJECT_COMMAND, &subcommand), args, 3) {
+                return Ok(error);
+            }
+            let object_key: Vec<u8> = args.get(2).unwrap().get_bytes();  // Retrieve the key to inspect.
+            if args_cli.maxmemory_policy != MaxMemoryPolicy::AllkeysLfu {
+                return Ok(RespResponse::Error("ERR An LFU maxmemory policy is not selected, access frequency not tracked. \
+                    Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust.".to_string()));
+            }
+            let db = db.lock().unwrap();
+            match lookup_live(&db, &object_key) {
+                Some(redis_item) => Ok(RespResponse::Integer(redis_item.get_frequency() as i64)),
+                None => Ok(RespResponse::NullBulkString),  // Return null if the key does not exist or is expired.
+            }
+        }
+        IDLETIME_ARG_COMMAND => {
+            if let Err(error) = check_arity(&subcommand_name(OBJECT_COMMAND, &subcommand), args, 3) {
+                return Ok(error);
+            }
+            let object_key: Vec<u8> = args.get(2).unwrap().get_bytes();  // Retrieve the key to inspect.
+            if args_cli.maxmemory_policy == MaxMemoryPolicy::AllkeysLfu {
+                return Ok(RespResponse::Error("ERR An LFU maxmemory policy is selected, idle time not tracked. \
+                    Please note that when switching between policies at runtime LRU and LFU data will take some time to adjust.".to_string()));
+            }
+            let db = db.lock().unwrap();
+            match lookup_live(&db, &object_key) {
+                Some(redis_item) => Ok(RespResponse::Integer(redis_item.get_idle_seconds() as i64)),
+                None => Ok(RespResponse::NullBulkString),  // Return null if the key does not exist or is expired.
+            }
+        }
+        ENCODING_ARG_COMMAND => {
+            if let Err(error) = check_arity(&subcommand_name(OBJECT_COMMAND, &subcommand), args, 3) {
+                return Ok(error);
+            }
+            let object_key: Vec<u8> = args.get(2).unwrap().get_bytes();  // Retrieve the key to inspect.
+            let db = db.lock().unwrap();
+            match lookup_live(&db, &object_key) {
+                Some(redis_item) => Ok(RespResponse::BulkString(redis_item.get_encoding().as_bytes().to_vec())),
+                None => Ok(RespResponse::NullBulkString),  // Return null if the key does not exist or is expired.
+            }
+        }
+        _ => Ok(unknown_subcommand(OBJECT_COMMAND, &subcommand)),
+    }
+}
+
+/// Handles the "DEBUG" command, which exposes server internals for testing.
+///
+/// Supported subcommands:
+/// * `SET-ACTIVE-EXPIRE 0|1` - disables or enables the background removal of expired keys.
+/// * `OBJECT key` - low-level information about the value stored at a key.
+/// * `CHANGE-REPL-ID` - generates a new replication id.
+/// * `SLEEP seconds` - blocks the connection for the given (possibly fractional) number of seconds; the
+///   runtime moves the other connections off the blocked worker thread in the meantime.
+/// * `SLEEP-BEFORE-REPLY milliseconds` - replies `+OK` after the given delay. The delay is awaited by the
+///   connection once the command has run and released its locks (see `reply_delay`), so nothing else waits.
+/// * `ERROR message` - replies with `-message`, to simulate server errors in client tests.
+/// * `STRINGMATCH-LEN pattern string` - whether the glob matcher used by KEYS matches the string.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` indicating success, or an error for unknown subcommands or invalid values.
+fn handle_debug(args: &[RespResponse], db: &Db, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    let subcommand: String = args.get(1).unwrap().get_value();  // Retrieve the subcommand (e.g., "SET-ACTIVE-EXPIRE").
 
-    match get_key_pattern.as_str() {
-        "*" => {
-            for (key, _) in db.iter() {
-                response_array.push(
-                        RespResponse::BulkString(key.clone()),
-               )
+    match subcommand.to_ascii_uppercase().as_str() {
+        HELP_ARG_COMMAND => Ok(help_reply(DEBUG_COMMAND, DEBUG_HELP)),
+        SET_ACTIVE_EXPIRE_ARG_COMMAND => {
+            if let Err(error) = check_arity(&subcommand_name(DEBUG_COMMAND, &subcommand), args, 3) {
+                return Ok(error);
+            }
+            match args.get(2).unwrap().get_value().as_str() {
+                "0" => state.active_expire.store(false, Ordering::Relaxed),
+                "1" => state.active_expire.store(true, Ordering::Relaxed),
+                _ => return Ok(RespResponse::Error(SYNTAX_ERR_STR.to_string())),
+            }
+            Ok(RespResponse::SimpleString(OK_STR.to_string()))
+        }
+        SLEEP_ARG_COMMAND => {
+            if let Err(error) = check_arity(&subcommand_name(DEBUG_COMMAND, &subcommand), args, 3) {
+                return Ok(error);
+            }
+            match args.get(2).unwrap().get_value().parse::<f64>() {
+                Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => sleep_blocking(Duration::from_secs_f64(seconds)),
+                _ => return Ok(RespResponse::Error(NOT_FLOAT_ERR_STR.to_string())),
             }
-            Ok(RespResponse::RespArray(Arc::new(response_array)))
+            Ok(RespResponse::SimpleString(OK_STR.to_string()))
         }
-        _ => Ok(RespResponse::NullBulkString)
+        SLEEP_BEFORE_REPLY_ARG_COMMAND => {
+            if args.len() != 3 {
+                return Ok(wrong_arity(&subcommand_name(DEBUG_COMMAND, &subcommand)));
+            }
+            match args[2].get_value().parse::<u64>() {
+                Ok(_) => Ok(RespResponse::SimpleString(OK_STR.to_string())),  // The connection delays the reply.
+                Err(_) => Ok(RespResponse::Error(NOT_INTEGER_ERR_STR.to_string())),
+            }
+        }
+        ERROR_ARG_COMMAND => {
+            if args.len() != 3 {
+                return Ok(wrong_arity(&subcommand_name(DEBUG_COMMAND, &subcommand)));
+            }
+            // Line breaks would end the error reply early, so they are replaced with spaces like Redis does.
+            let message = args[2].get_value().replace(['\r', '\n'], " ");
+            Ok(RespResponse::Error(message))
+        }
+        STRINGMATCH_LEN_ARG_COMMAND => {
+            if args.len() != 4 {
+                return Ok(wrong_arity(&subcommand_name(DEBUG_COMMAND, &subcommand)));
+            }
+            let matched = glob_match(&args[2].get_bytes(), &args[3].get_bytes(), false);
+            Ok(RespResponse::Integer(matched as i64))
+        }
+        CHANGE_REPL_ID_ARG_COMMAND => {
+            *state.replid.write().unwrap() = random_hex_id(NODE_ID_LEN);
+            Ok(RespResponse::SimpleString(OK_STR.to_string()))
+        }
+        OBJECT_ARG_COMMAND => {
+            if let Err(error) = check_arity(&subcommand_name(DEBUG_COMMAND, &subcommand), args, 3) {
+                return Ok(error);
+            }
+            let object_key: Vec<u8> = args.get(2).unwrap().get_bytes();  // Retrieve the key to inspect.
+            let db = db.lock().unwrap();
+            match lookup_live(&db, &object_key) {
+                Some(redis_item) => Ok(RespResponse::BulkString(debug_object_info(redis_item).into_bytes())),
+                None => Ok(RespResponse::Error(NO_SUCH_KEY_ERR_STR.to_string())),
+            }
+        }
+        _ => Ok(unknown_subcommand(DEBUG_COMMAND, &subcommand)),
+    }
+}
+
+/// Returns how long the reply to a command must be held back, for `DEBUG SLEEP-BEFORE-REPLY`.
+///
+/// The connection awaits the delay after the command ran, so no lock is held and no worker thread is
+/// blocked while it waits.
+///
+/// # Arguments
+///
+/// * `command` - The command name.
+/// * `args` - The arguments of the command, starting with the command name.
+///
+/// # Returns
+///
+/// Returns the delay for a valid `DEBUG SLEEP-BEFORE-REPLY milliseconds`, or `None` for any other command.
+pub fn reply_delay(command: &str, args: &[RespResponse]) -> Option<Duration> {
+    if !command.eq_ignore_ascii_case(DEBUG_COMMAND) || args.len() != 3
+        || !args[1].get_value().eq_ignore_ascii_case(SLEEP_BEFORE_REPLY_ARG_COMMAND) {
+        return None;
+    }
+    args[2].get_value().parse().ok().map(Duration::from_millis)
+}
+
+/// Blocks the current thread for `duration`, as `DEBUG SLEEP` does.
+///
+/// On a multi-threaded runtime the worker is first handed over with `block_in_place`, so the other
+/// connections scheduled on it keep being served; elsewhere (e.g. in tests) the thread simply sleeps.
+///
+/// # Arguments
+///
+/// * `duration` - How long to block.
+fn sleep_blocking(duration: Duration) {
+    let on_worker = tokio::runtime::Handle::try_current()
+        .is_ok_and(|handle| handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread);
+    if on_worker {
+        tokio::task::block_in_place(|| std::thread::sleep(duration));
+    } else {
+        std::thread::sleep(duration);
     }
-}
\ No newline at end of file
+}
+
+/// Formats the `DEBUG OBJECT` description of a value.
+///
+/// The `lru` field is the Redis 24-bit LRU clock (in seconds) at the last access.
+///
+/// # Arguments
+///
+/// * `redis_item` - The item to describe.
+///
+/// # Returns
+///
+/// Returns the description, e.g. `Value at:0x... refcount:1 encoding:embstr serializedlength:6 lru:... lru_seconds_idle:0`.
+fn debug_object_info(redis_item: &RedisItem) -> String {
+    let idle = redis_item.get_idle_seconds();
+    let lru = unix_time_secs().saturating_sub(idle) & LRU_CLOCK_MAX;
+    format!(
+        "Value at:{:p} refcount:1 encoding:{} serializedlength:{} lru:{} lru_seconds_idle:{}",
+        redis_item,
+        redis_item.get_encoding(),
+        serialized_length(redis_item.get_data()),
+        lru,
+        idle,
+    )
+}
+
+/// Handles the "EXPIRETIME" and "PEXPIRETIME" commands, which return the absolute unix expiration time of a key.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `in_millis` - Whether to report the time in milliseconds (PEXPIRETIME) instead of seconds (EXPIRETIME).
+///
+/// # Returns
+///
+/// Returns an integer `RespResponse` with the expiration timestamp, `-1` if the key has no expiration, or `-2` if the key does not exist.
+fn handle_expiretime(args: &[RespResponse], db: &Db, in_millis: bool) -> Result<RespResponse, anyhow::Error> {
+    let get_key: Vec<u8> = args.get(1).unwrap().get_bytes();  // Retrieve the key to inspect.
+    let db = db.lock().unwrap();
+
+    let expiration = match lookup_live(&db, &get_key) {
+        Some(redis_item) => redis_item.get_expiration(),
+        None => return Ok(RespResponse::Integer(-2)),  // The key does not exist or is expired.
+    };
+
+    match expiration {
+        Some(expiration) => {
+            let since_epoch = expiration.duration_since(UNIX_EPOCH).unwrap_or_default();
+            let timestamp = if in_millis { since_epoch.as_millis() } else { since_epoch.as_secs() as u128 };
+            Ok(RespResponse::Integer(timestamp as i64))
+        }
+        None => Ok(RespResponse::Integer(-1)),  // The key exists but has no expiration.
+    }
+}
+
+/// Handles the "LOLWUT" command, which returns an ASCII banner followed by the server version.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments (optionally `VERSION n`).
+///
+/// # Returns
+///
+/// Returns a `RespResponse` bulk string with the banner, or an error if the options are invalid.
+fn handle_lolwut(args: &[RespResponse]) -> Result<RespResponse, anyhow::Error> {
+    match args.len() {
+        1 => {}
+        3 if args[1].get_value().eq_ignore_ascii_case(VERSION_ARG_COMMAND) => {
+            if args[2].get_value().parse::<i64>().is_err() {
+                return Ok(RespResponse::Error(NOT_INTEGER_ERR_STR.to_string()));
+            }
+        }
+        _ => return Ok(RespResponse::Error(SYNTAX_ERR_STR.to_string())),
+    }
+
+    let banner = concat!(
+        " ____          _ _           ____            _   \n",
+        "|  _ \\ ___  __| (_)___      |  _ \\ _   _ ___| |_ \n",
+        "| |_) / _ \\/ _` | / __|_____| |_) | | | / __| __|\n",
+        "|  _ <  __/ (_| | \\__ \\_____|  _ <| |_| \\__ \\ |_ \n",
+        "|_| \\_\\___|\\__,_|_|___/     |_| \\_\\\\__,_|___/\\__|\n",
+    );
+    Ok(RespResponse::BulkString(format!("{}\nredis-rust ver. {}\n", banner, env!("CARGO_PKG_VERSION")).into_bytes()))
+}
+
+/// Parses a bit offset argument of the bit commands.
+///
+/// # Arguments
+///
+/// * `arg` - The `RespResponse` holding the offset.
+///
+/// # Returns
+///
+/// Returns the offset, or an error `RespResponse` if it is not an integer in `0..=MAX_BIT_OFFSET`.
+fn parse_bit_offset(arg: &RespResponse) -> Result<u64, RespResponse> {
+    arg.get_value().parse::<u64>().ok()
+        .filter(|offset| *offset <= MAX_BIT_OFFSET)
+        .ok_or_else(|| RespResponse::Error(BIT_OFFSET_ERR_STR.to_string()))
+}
+
+/// Handles the "SETBIT" command, which sets or clears the bit at an offset of the string stored at a key.
+///
+/// The string is grown with zero bytes as needed. A missing key is created; an existing key keeps its expiration.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose dirty counter counts the changed keys.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` containing the previous value of the bit, or an error for an invalid offset or bit.
+fn handle_setbit(args: &[RespResponse], db: &Db, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    let set_key: Vec<u8> = args.get(1).unwrap().get_bytes();  // Retrieve the key to modify.
+    let offset = match parse_bit_offset(args.get(2).unwrap()) {
+        Ok(offset) => offset,
+        Err(error) => return Ok(error),
+    };
+    let bit_on = match args.get(3).unwrap().get_value().as_str() {
+        "0" => false,
+        "1" => true,
+        _ => return Ok(RespResponse::Error(BIT_VALUE_ERR_STR.to_string())),
+    };
+
+    let mut db = db.lock().unwrap();
+    if lookup_live(&db, &set_key).is_none() {
+        db.insert(set_key.clone(), RedisItem::new(Vec::new()));
+    }
+    let redis_item = db.get_mut(&set_key).unwrap();
+    redis_item.touch();
+
+    let data = redis_item.get_data_mut();
+    let byte_index = (offset >> 3) as usize;
+    let mask = 1u8 << (7 - (offset & 7));
+    if data.len() <= byte_index {
+        data.resize(byte_index + 1, 0);
+    }
+
+    let previous = data[byte_index] & mask != 0;
+    if bit_on {
+        data[byte_index] |= mask;
+    } else {
+        data[byte_index] &= !mask;
+    }
+    state.mark_dirty(1);
+    Ok(RespResponse::Integer(previous as i64))
+}
+
+/// Handles the "GETBIT" command, which returns the bit at an offset of the string stored at a key.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose keyspace hit and miss counters are updated.
+/// * `no_touch` - Whether the client asked (CLIENT NO-TOUCH) not to update the key's access metadata.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` containing the bit value (`0` past the end of the string or for a missing key), or an error for an invalid offset.
+fn handle_getbit(args: &[RespResponse], db: &Db, state: &SharedState, no_touch: bool) -> Result<RespResponse, anyhow::Error> {
+    let get_key: Vec<u8> = args.get(1).unwrap().get_bytes();  // Retrieve the key to read.
+    let offset = match parse_bit_offset(args.get(2).unwrap()) {
+        Ok(offset) => offset,
+        Err(error) => return Ok(error),
+    };
+
+    let mut db = db.lock().unwrap();
+    let bit = match lookup_live_mut(&mut db, &get_key) {
+        Some(redis_item) => {
+            state.record_keyspace_lookup(true);
+            if !no_touch {
+                redis_item.touch();
+            }
+            redis_item.get_data()
+                .get((offset >> 3) as usize)
+                .map_or(0, |byte| (byte >> (7 - (offset & 7))) & 1)
+        }
+        None => {
+            state.record_keyspace_lookup(false);
+            0
+        }
+    };
+    Ok(RespResponse::Integer(bit as i64))
+}
+
+/// Handles the "BITCOUNT" command, which counts the set bits of the string stored at a key.
+///
+/// An optional `start end` byte range may be given; negative indexes count from the end of the string.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose keyspace hit and miss counters are updated.
+/// * `no_touch` - Whether the client asked (CLIENT NO-TOUCH) not to update the key's access metadata.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` containing the number of set bits, or an error for invalid arguments.
+fn handle_bitcount(args: &[RespResponse], db: &Db, state: &SharedState, no_touch: bool) -> Result<RespResponse, anyhow::Error> {
+    let get_key: Vec<u8> = args.get(1).unwrap().get_bytes();  // Retrieve the key to read.
+    let range = match args.len() {
+        2 => None,
+        4 => match (args[2].get_value().parse::<i64>(), args[3].get_value().parse::<i64>()) {
+            (Ok(start), Ok(end)) => Some((start, end)),
+            _ => return Ok(RespResponse::Error(NOT_INTEGER_ERR_STR.to_string())),
+        },
+        _ => return Ok(RespResponse::Error(SYNTAX_ERR_STR.to_string())),
+    };
+
+    let mut db = db.lock().unwrap();
+    let data = match lookup_live_mut(&mut db, &get_key) {
+        Some(redis_item) => {
+            state.record_keyspace_lookup(true);
+            if !no_touch {
+                redis_item.touch();
+            }
+            redis_item.get_data()
+        }
+        None => {
+            state.record_keyspace_lookup(false);
+            return Ok(RespResponse::Integer(0));
+        }
+    };
+
+    let (start, end) = range.unwrap_or((0, -1));
+    let Some((start, end)) = byte_range(start, end, data.len()) else {
+        return Ok(RespResponse::Integer(0));
+    };
+
+    let count: u32 = data[start..=end].iter().map(|byte| byte.count_ones()).sum();
+    Ok(RespResponse::Integer(count as i64))
+}
+
+/// Resolves a `start end` byte range of the bit commands against a string of length `len`.
+///
+/// Negative indexes count from the end of the string, and the range is clamped to the string.
+///
+/// # Arguments
+///
+/// * `start` - The first byte of the range.
+/// * `end` - The last byte of the range (inclusive).
+/// * `len` - The length of the string.
+///
+/// # Returns
+///
+/// Returns the inclusive range as indexes into the string, or `None` if it is empty.
+fn byte_range(start: i64, end: i64, len: usize) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let start = if start < 0 { (len + start).max(0) } else { start };
+    let end = if end < 0 { len + end } else { end.min(len - 1) };
+    if start > end || start >= len {
+        return None;
+    }
+    Some((start as usize, end as usize))
+}
+
+/// Handles the "BITPOS" command, which returns the position of the first bit set to 0 or 1 in the string stored at a key.
+///
+/// An optional `start [end]` byte range may be given; negative indexes count from the end of the string.
+/// A string is treated as padded with zero bytes on the right, so a search for 0 without an explicit end
+/// reports the first bit past the string if every bit is set.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose keyspace hit and miss counters are updated.
+/// * `no_touch` - Whether the client asked (CLIENT NO-TOUCH) not to update the key's access metadata.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` containing the bit position, `-1` if the bit was not found, or an error for invalid arguments.
+fn handle_bitpos(args: &[RespResponse], db: &Db, state: &SharedState, no_touch: bool) -> Result<RespResponse, anyhow::Error> {
+    let get_key: Vec<u8> = args.get(1).unwrap().get_bytes();  // Retrieve the key to read.
+    let bit_on = match args.get(2).unwrap().get_value().as_str() {
+        "0" => false,
+        "1" => true,
+        _ => return Ok(RespResponse::Error(BIT_ARG_ERR_STR.to_string())),
+    };
+    if args.len() > 5 {
+        return Ok(RespResponse::Error(SYNTAX_ERR_STR.to_string()));
+    }
+    let mut bounds = Vec::with_capacity(2);
+    for arg in &args[3..] {
+        match arg.get_value().parse::<i64>() {
+            Ok(bound) => bounds.push(bound),
+            Err(_) => return Ok(RespResponse::Error(NOT_INTEGER_ERR_STR.to_string())),
+        }
+    }
+    let end_given = bounds.len() == 2;
+
+    let mut db = db.lock().unwrap();
+    let data = match lookup_live_mut(&mut db, &get_key) {
+        Some(redis_item) => {
+            state.record_keyspace_lookup(true);
+            if !no_touch {
+                redis_item.touch();
+            }
+            redis_item.get_data()
+        }
+        None => {
+            state.record_keyspace_lookup(false);
+            return Ok(RespResponse::Integer(if bit_on { -1 } else { 0 }));  // A missing key is an empty string.
+        }
+    };
+
+    let start = bounds.first().copied().unwrap_or(0);
+    let end = bounds.get(1).copied().unwrap_or(-1);
+    let Some((start, end)) = byte_range(start, end, data.len()) else {
+        return Ok(RespResponse::Integer(-1));
+    };
+
+    // Skip the bytes that cannot contain the bit, then locate it within the first byte that does.
+    let skip = if bit_on { 0x00 } else { 0xff };
+    let position = data[start..=end].iter()
+        .position(|byte| *byte != skip)
+        .map(|index| {
+            let byte = if bit_on { data[start + index] } else { !data[start + index] };
+            ((start + index) * 8) as i64 + byte.leading_zeros() as i64
+        });
+
+    match position {
+        Some(position) => Ok(RespResponse::Integer(position)),
+        None if !bit_on && !end_given => Ok(RespResponse::Integer(((end + 1) * 8) as i64)),  // The first zero of the padding.
+        None => Ok(RespResponse::Integer(-1)),
+    }
+}
+
+/// Handles the "BITOP" command, which performs a bitwise operation between strings and stores the result.
+///
+/// `AND`, `OR` and `XOR` combine any number of source keys; `NOT` inverts a single one. Shorter strings and
+/// missing keys are treated as padded with zero bytes up to the length of the longest source. An empty
+/// result deletes the destination key.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose dirty counter counts the changed keys.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` containing the length of the stored string, or an error for invalid arguments.
+fn handle_bitop(args: &[RespResponse], db: &Db, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    let operation = args.get(1).unwrap().get_value().to_ascii_uppercase();  // Retrieve the operation (e.g., "XOR").
+    let dest_key: Vec<u8> = args.get(2).unwrap().get_bytes();  // Retrieve the key to store the result in.
+    let combine: Option<fn(u8, u8) -> u8> = match operation.as_str() {
+        AND_ARG_COMMAND => Some(|a, b| a & b),
+        OR_ARG_COMMAND => Some(|a, b| a | b),
+        XOR_ARG_COMMAND => Some(|a, b| a ^ b),
+        NOT_ARG_COMMAND if args.len() != 4 => return Ok(RespResponse::Error(BITOP_NOT_ERR_STR.to_string())),
+        NOT_ARG_COMMAND => None,
+        _ => return Ok(RespResponse::Error(SYNTAX_ERR_STR.to_string())),
+    };
+
+    let mut db = db.lock().unwrap();
+    let mut sources: Vec<Vec<u8>> = Vec::with_capacity(args.len() - 3);
+    for arg in &args[3..] {
+        let source_key = arg.get_bytes();
+        let source = match lookup_live_mut(&mut db, &source_key) {
+            Some(redis_item) => {
+                redis_item.touch();
+                redis_item.get_data().clone()
+            }
+            None => Vec::new(),  // A missing key is an empty string.
+        };
+        sources.push(source);
+    }
+
+    let len = sources.iter().map(Vec::len).max().unwrap_or(0);
+    if len == 0 {
+        if db.swap_remove(&dest_key).is_some_and(|redis_item| !redis_item.is_expired()) {
+            state.mark_dirty(1);
+        }
+        return Ok(RespResponse::Integer(0));
+    }
+    let byte_at = |source: &Vec<u8>, index: usize| source.get(index).copied().unwrap_or(0);
+    let result: Vec<u8> = match combine {
+        Some(combine) => (0..len)
+            .map(|index| {
+                sources[1..].iter().fold(byte_at(&sources[0], index), |acc, source| combine(acc, byte_at(source, index)))
+            })
+            .collect(),
+        None => sources[0].iter().map(|byte| !byte).collect(),  // NOT has exactly one source.
+    };
+    db.insert(dest_key, RedisItem::new(result));
+    state.mark_dirty(1);
+    Ok(RespResponse::Integer(len as i64))
+}
+
+/// Handles the "CONFIG" command, which retrieves configuration values.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `args_cli` - A reference to the command-line arguments.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` containing the configuration value, the help text, or an error if the subcommand is not recognized.
+fn handle_config(args: &[RespResponse], args_cli: &ArgsCli) -> Result<RespResponse, anyhow::Error> {
+    let subcommand: String = args.get(1).unwrap().get_value();  // Retrieve the subcommand (e.g., "GET").
+
+    match subcommand.to_ascii_uppercase().as_str() {
+        HELP_ARG_COMMAND => Ok(help_reply(CONFIG_COMMAND, CONFIG_HELP)),  // Handle the "HELP" subcommand.
+        GET_COMMAND => {
+            if let Err(error) = check_arity(&subcommand_name(CONFIG_COMMAND, &subcommand), args, 3) {
+                return Ok(error);
+            }
+            handle_config_get(&args[2..], args_cli)  // Handle the "GET" subcommand.
+        }
+        REWRITE_ARG_COMMAND => handle_config_rewrite(args_cli),  // Handle the "REWRITE" subcommand.
+        _ => Ok(unknown_subcommand(CONFIG_COMMAND, &subcommand)),  // Return an error if the subcommand is not recognized.
+    }
+}
+
+/// Writes the current configuration back to the config file given with `--config`.
+///
+/// Without `CONFIG SET`, the configuration cannot change at runtime, so this only records the
+/// command-line flags and defaults the server was started with in the file.
+///
+/// # Arguments
+///
+/// * `args_cli` - A reference to the command-line arguments.
+///
+/// # Returns
+///
+/// Returns "OK" once the file is rewritten, or an error `RespResponse` if there is no config file or it could not be written.
+fn handle_config_rewrite(args_cli: &ArgsCli) -> Result<RespResponse, anyhow::Error> {
+    let Some(config_path) = &args_cli.config else {
+        return Ok(RespResponse::Error(NO_CONFIG_FILE_ERR_STR.to_string()));
+    };
+    match rewrite_config(Path::new(config_path), args_cli) {
+        Ok(()) => Ok(RespResponse::SimpleString(OK_STR.to_string())),
+        Err(e) => Ok(RespResponse::Error(format!("ERR Rewriting config file: {:#}", e))),
+    }
+}
+
+/// Retrieves the configuration parameters whose names match any of the given glob-style patterns.
+///
+/// Names are matched case-insensitively, so `CONFIG GET *` dumps the whole runtime configuration. Each
+/// parameter is reported once, in the order `CONFIG REWRITE` writes them.
+///
+/// # Arguments
+///
+/// * `patterns` - The patterns given to `CONFIG GET`.
+/// * `args_cli` - A reference to the command-line arguments.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` map of names to values, empty if nothing matches.
+fn handle_config_get(patterns: &[RespResponse], args_cli: &ArgsCli) -> Result<RespResponse, anyhow::Error> {
+    let mut entries = config_entries(args_cli);
+    // `dir` and `dbfilename` are left out of the config file while unset, but always have an effective value.
+    if args_cli.dir.is_none() {
+        entries.push((DIR_ARG_COMMAND, DEFAULT_DIR.to_string()));
+    }
+    if args_cli.dbfilename.is_none() {
+        entries.push((DB_FILENAME_ARG_COMMAND, DEFAULT_DB_FILENAME.to_string()));
+    }
+
+    let patterns: Vec<Vec<u8>> = patterns.iter().map(RespResponse::get_bytes).collect();
+    let result = entries.into_iter()
+        .filter(|(name, _)| patterns.iter().any(|pattern| glob_match(pattern, name.as_bytes(), true)))
+        .map(|(name, value)| (RespResponse::BulkString(name.as_bytes().to_vec()), RespResponse::BulkString(value.into_bytes())))
+        .collect();
+    Ok(RespResponse::Map(Arc::new(result)))
+}
+
+/// Handles the "KEYS" command, which retrieves keys matching a glob-style pattern.
+///
+/// Like Redis, keys are returned in no particular order (the keyspace's internal order, which deletions
+/// reshuffle); with
+/// `--deterministic-order` they are sorted lexicographically instead. Keys that have expired but were
+/// not removed yet are skipped, so the reply agrees with DBSIZE.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `args_cli` - A reference to the command-line arguments.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` containing an array of the matching keys (empty if none match).
+fn handle_keys(args: &[RespResponse], db: &Db, args_cli: &ArgsCli) -> Result<RespResponse, anyhow::Error> {
+    let get_key_pattern: Vec<u8> = args.get(1).unwrap().get_bytes();
+    let db = db.lock().unwrap();
+
+    // Like Redis, a lone `*` skips the matcher, which would not match the empty key.
+    let all_keys = get_key_pattern == b"*";
+    let mut keys: Vec<&Vec<u8>> = db.iter()
+        .filter(|(_, redis_item)| !redis_item.is_expired())
+        .map(|(key, _)| key)
+        .filter(|key| all_keys || glob_match(&get_key_pattern, key, false))
+        .collect();
+    if args_cli.deterministic_order {
+        keys.sort_unstable();
+    }
+    let response_array = keys.into_iter()
+        .map(|key| RespResponse::BulkString(key.clone()))
+        .collect();
+    Ok(RespResponse::RespArray(Arc::new(response_array)))
+}
+
+/// Handles the "SAVE" command, which writes the database to the RDB file before replying.
+///
+/// # Arguments
+///
+/// * `db` - A reference to the shared database.
+/// * `args_cli` - A reference to the command-line arguments.
+/// * `state` - A reference to the shared server state.
+///
+/// # Returns
+///
+/// Returns "OK" once the snapshot is written, or an error `RespResponse` if it could not be.
+fn handle_save(db: &Db, args_cli: &ArgsCli, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    if state.bgsave_in_progress.load(Ordering::Acquire) {
+        return Ok(RespResponse::Error(BGSAVE_IN_PROGRESS_ERR_STR.to_string()));
+    }
+    match save(db, args_cli, state) {
+        Ok(()) => Ok(RespResponse::SimpleString(OK_STR.to_string())),
+        Err(e) => Ok(RespResponse::Error(format!("ERR {:#}", e))),
+    }
+}
+
+/// Handles the "BGSAVE" command, which starts writing the database to the RDB file in the background.
+///
+/// # Arguments
+///
+/// * `db` - A reference to the shared database.
+/// * `args_cli` - A reference to the command-line arguments.
+/// * `state` - A reference to the shared server state.
+///
+/// # Returns
+///
+/// Returns "Background saving started", or an error `RespResponse` if a background save is already running.
+fn handle_bgsave(db: &Db, args_cli: &ArgsCli, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    if bgsave(db, args_cli, state) {
+        Ok(RespResponse::SimpleString(BGSAVE_STARTED_STR.to_string()))
+    } else {
+        Ok(RespResponse::Error(BGSAVE_IN_PROGRESS_ERR_STR.to_string()))
+    }
+}
+
+/// Handles the "FLUSHALL" command, which removes every key from the database.
+///
+/// With `ASYNC`, the old keyspace is swapped out under the lock and dropped on a background thread,
+/// so freeing a large dataset does not block other clients. `SYNC` (the default) frees it in place.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose dirty counter counts the changed keys.
+///
+/// # Returns
+///
+/// Returns "OK", or a syntax error for an unknown flush mode.
+fn handle_flushall(args: &[RespResponse], db: &Db, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    let asynchronous = match args.get(1).map(|mode| mode.get_value().to_ascii_uppercase()) {
+        None => false,
+        Some(mode) if mode == SYNC_ARG_COMMAND && args.len() == 2 => false,
+        Some(mode) if mode == ASYNC_ARG_COMMAND && args.len() == 2 => true,
+        Some(_) => return Ok(RespResponse::Error(SYNTAX_ERR_STR.to_string())),
+    };
+
+    let mut db = db.lock().unwrap();
+    state.mark_dirty(db.len() as u64);
+    if asynchronous {
+        let old_keyspace = std::mem::take(&mut *db);
+        run_in_background(move || drop(old_keyspace));
+    } else {
+        db.clear();
+    }
+    Ok(RespResponse::SimpleString(OK_STR.to_string()))
+}
+
+/// Builds the reply of a container command's `HELP` subcommand.
+///
+/// # Arguments
+///
+/// * `command` - The container command name (e.g. "OBJECT").
+/// * `lines` - The help lines describing each subcommand.
+///
+/// # Returns
+///
+/// Returns an array of simple strings: a usage header, the given lines, and the description of `HELP` itself.
+pub fn help_reply(command: &str, lines: &[&str]) -> RespResponse {
+    let header = format!("{} <subcommand> [<arg> [value] [opt] ...]. Subcommands are:", command);
+    let help = std::iter::once(header)
+        .chain(lines.iter().map(|line| line.to_string()))
+        .chain(["HELP".to_string(), "    Print this help.".to_string()])
+        .map(RespResponse::SimpleString)
+        .collect();
+    RespResponse::RespArray(Arc::new(help))
+}
+
+/// Builds the error returned for an unknown subcommand of a container command.
+///
+/// # Arguments
+///
+/// * `command` - The container command name (e.g. "OBJECT").
+/// * `subcommand` - The subcommand as sent by the client.
+///
+/// # Returns
+///
+/// Returns an error `RespResponse` pointing the client at the command's `HELP`.
+pub fn unknown_subcommand(command: &str, subcommand: &str) -> RespResponse {
+    RespResponse::Error(format!("ERR unknown subcommand '{}'. Try {} HELP.", quoted_arg(subcommand), command))
+}
+
+/// Makes a client argument safe to quote in an error reply, like Redis's `addReplyErrorFormat`.
+///
+/// Line breaks would end the error reply early (and let the rest be read as more replies), so they are
+/// replaced with spaces, and the argument is cut to `MAX_QUOTED_ARG_LEN` characters.
+///
+/// # Arguments
+///
+/// * `arg` - The argument as sent by the client.
+///
+/// # Returns
+///
+/// Returns the text to quote.
+pub fn quoted_arg(arg: &str) -> String {
+    arg.chars()
+        .take(MAX_QUOTED_ARG_LEN)
+        .map(|c| if c == '\r' || c == '\n' { ' ' } else { c })
+        .collect()
+}
+
+/// Handles the "DBSIZE" command, which returns the number of live keys.
+///
+/// Keys that have expired but were not removed yet are not counted. Up to `DBSIZE_EXPIRE_CLEANUP_LIMIT`
+/// of them are removed on the way, so the cleanup cost of a single call stays bounded.
+///
+/// # Arguments
+///
+/// * `db` - A reference to the shared database.
+///
+/// # Returns
+///
+/// Returns an integer `RespResponse` with the number of keys.
+fn handle_dbsize(db: &Db) -> Result<RespResponse, anyhow::Error> {
+    let mut db = db.lock().unwrap();
+
+    let mut expired_count = 0;
+    let mut to_remove = Vec::new();
+    for (key, redis_item) in db.iter() {
+        if redis_item.is_expired() {
+            expired_count += 1;
+            if to_remove.len() < DBSIZE_EXPIRE_CLEANUP_LIMIT {
+                to_remove.push(key.clone());
+            }
+        }
+    }
+
+    let live = db.len() - expired_count;
+    for key in to_remove {
+        db.swap_remove(&key);
+    }
+    Ok(RespResponse::Integer(live as i64))
+}
+
+/// Handles the "CLUSTER" command for cluster-aware clients talking to this standalone server.
+///
+/// Supported subcommands:
+/// * `INFO` - reports that cluster support is disabled.
+/// * `MYID` - the id of this node.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `state` - A reference to the shared server state.
+///
+/// # Returns
+///
+/// Returns a bulk string `RespResponse` with the requested information, or an error for unknown subcommands.
+fn handle_cluster(args: &[RespResponse], state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    let subcommand: String = args.get(1).unwrap().get_value();  // Retrieve the subcommand (e.g., "INFO").
+
+    match subcommand.to_ascii_uppercase().as_str() {
+        HELP_ARG_COMMAND => Ok(help_reply(CLUSTER_COMMAND, CLUSTER_HELP)),
+        INFO_ARG_COMMAND => Ok(RespResponse::BulkString(CLUSTER_INFO_STR.as_bytes().to_vec())),
+        MYID_ARG_COMMAND => Ok(RespResponse::BulkString(state.node_id.as_bytes().to_vec())),
+        _ => Ok(unknown_subcommand(CLUSTER_COMMAND, &subcommand)),
+    }
+}
+
+/// Handles the "LASTSAVE" command, which returns the time of the last successful save.
+///
+/// # Arguments
+///
+/// * `state` - A reference to the shared server state.
+///
+/// # Returns
+///
+/// Returns an integer `RespResponse` with the Unix time (in seconds) of the last successful save.
+fn handle_lastsave(state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    Ok(RespResponse::Integer(state.last_save.load(Ordering::Relaxed) as i64))
+}
+
+/// Handles the "TIME" command, which returns the current server time.
+///
+/// # Returns
+///
+/// Returns an array `RespResponse` of two bulk strings: the unix time in seconds and the microseconds
+/// elapsed in the current second. A system clock set before the unix epoch is reported as time 0.
+fn handle_time() -> Result<RespResponse, anyhow::Error> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = RespResponse::BulkString(now.as_secs().to_string().into_bytes());
+    let micros = RespResponse::BulkString(now.subsec_micros().to_string().into_bytes());
+    Ok(RespResponse::RespArray(Arc::new(vec![seconds, micros])))
+}
+
+/// Handles the "MGET" command, which retrieves the values of several keys.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose keyspace hit and miss counters are updated.
+/// * `no_touch` - Whether the client asked (CLIENT NO-TOUCH) not to update the key's access metadata.
+///
+/// # Returns
+///
+/// Returns an array `RespResponse` with the value of each key, or null for keys that do not exist or are expired.
+fn handle_mget(args: &[RespResponse], db: &Db, state: &SharedState, no_touch: bool) -> Result<RespResponse, anyhow::Error> {
+    let mut db = db.lock().unwrap();
+
+    let values = args[1..].iter()
+        .map(|key| match lookup_live_mut(&mut db, &key.get_bytes()) {
+            Some(redis_item) => {
+                state.record_keyspace_lookup(true);
+                if !no_touch {
+                    redis_item.touch();
+                }
+                RespResponse::SharedBulkString(redis_item.get_shared_data())
+            }
+            None => {
+                state.record_keyspace_lookup(false);
+                RespResponse::NullBulkString
+            }
+        })
+        .collect();
+    Ok(RespResponse::RespArray(Arc::new(values)))
+}
+
+/// Handles the "MSET" command, which sets several key-value pairs at once.
+///
+/// Like SET, any existing expiration of the keys is discarded.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose dirty counter counts the changed keys.
+///
+/// # Returns
+///
+/// Returns "OK", or an error if the keys and values do not come in pairs.
+fn handle_mset(args: &[RespResponse], db: &Db, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    if args.len().is_multiple_of(2) {
+        return Ok(wrong_arity(MSET_COMMAND));  // Every key needs a value.
+    }
+
+    let mut db = db.lock().unwrap();
+    for pair in args[1..].chunks(2) {
+        db.insert(pair[0].get_bytes(), RedisItem::new(pair[1].get_bytes()));
+    }
+    state.mark_dirty((args.len() / 2) as u64);
+    Ok(RespResponse::SimpleString(OK_STR.to_string()))
+}
+
+/// Handles the "DEL" command, which removes keys.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state, whose dirty counter counts the changed keys.
+///
+/// # Returns
+///
+/// Returns an integer `RespResponse` with the number of keys removed; expired keys are not counted.
+fn handle_del(args: &[RespResponse], db: &Db, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    let mut db = db.lock().unwrap();
+
+    let removed = args[1..].iter()
+        .filter_map(|key| db.swap_remove(&key.get_bytes()))
+        .filter(|redis_item| !redis_item.is_expired())
+        .count();
+    state.mark_dirty(removed as u64);
+    Ok(RespResponse::Integer(removed as i64))
+}
+
+/// Handles the "EXISTS" command, which counts how many of the given keys exist.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+///
+/// # Returns
+///
+/// Returns an integer `RespResponse` with the number of existing keys; a key given several times is counted each time.
+fn handle_exists(args: &[RespResponse], db: &Db) -> Result<RespResponse, anyhow::Error> {
+    let db = db.lock().unwrap();
+
+    let existing = args[1..].iter()
+        .filter(|key| lookup_live(&db, &key.get_bytes()).is_some())
+        .count();
+    Ok(RespResponse::Integer(existing as i64))
+}
+
+/// Handles the "COMMAND" command, which describes the commands supported by the server.
+///
+/// Supported subcommands:
+/// * (none) - the description of every command.
+/// * `COUNT` - the number of supported commands.
+/// * `LIST` - the names of the supported commands.
+/// * `DOCS [command ...]` - the documentation of the given commands (all commands if none are given).
+/// * `INFO [command ...]` - the description of the given commands (all commands if none are given).
+/// * `GETKEYS command [arg ...]` - the keys the given full command would access.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` with the requested information, or an error for unknown subcommands.
+fn handle_command_introspection(args: &[RespResponse]) -> Result<RespResponse, anyhow::Error> {
+    let Some(subcommand) = args.get(1).map(RespResponse::get_value) else {
+        return Ok(RespResponse::RespArray(Arc::new(COMMAND_TABLE.iter().map(command_info).collect())));
+    };
+
+    match subcommand.to_ascii_uppercase().as_str() {
+        HELP_ARG_COMMAND => Ok(help_reply(COMMAND_COMMAND, COMMAND_HELP)),
+        COUNT_ARG_COMMAND => Ok(RespResponse::Integer(COMMAND_TABLE.len() as i64)),
+        LIST_ARG_COMMAND => {
+            let names = COMMAND_TABLE.iter().map(|spec| RespResponse::BulkString(spec.name.as_bytes().to_vec())).collect();
+            Ok(RespResponse::RespArray(Arc::new(names)))
+        }
+        DOCS_ARG_COMMAND => {
+            let specs: Vec<&CommandSpec> = if args.len() == 2 {
+                COMMAND_TABLE.iter().collect()
+            } else {
+                args[2..].iter().filter_map(|name| lookup_command(&name.get_value())).collect()  // Unknown commands are left out.
+            };
+            Ok(RespResponse::Map(Arc::new(specs.into_iter().map(command_docs).collect())))
+        }
+        INFO_ARG_COMMAND if args.len() == 2 => Ok(RespResponse::RespArray(Arc::new(COMMAND_TABLE.iter().map(command_info).collect()))),
+        INFO_ARG_COMMAND => {
+            let infos = args[2..].iter()
+                .map(|name| match lookup_command(&name.get_value()) {
+                    Some(spec) => command_info(spec),
+                    None => RespResponse::NullBulkString,  // Unknown commands are reported as null.
+                })
+                .collect();
+            Ok(RespResponse::RespArray(Arc::new(infos)))
+        }
+        GETKEYS_ARG_COMMAND => {
+            if let Err(error) = check_arity(&subcommand_name(COMMAND_COMMAND, &subcommand), args, 3) {
+                return Ok(error);
+            }
+            handle_command_getkeys(&args[2..])
+        }
+        _ => Ok(unknown_subcommand(COMMAND_COMMAND, &subcommand)),
+    }
+}
+
+/// Handles the "COMMAND GETKEYS" subcommand, which extracts the keys of a full command.
+///
+/// # Arguments
+///
+/// * `full_command` - The command name followed by its arguments.
+///
+/// # Returns
+///
+/// Returns an array `RespResponse` of the key arguments, or an error if the command is unknown, has the
+/// wrong number of arguments, or takes no keys.
+fn handle_command_getkeys(full_command: &[RespResponse]) -> Result<RespResponse, anyhow::Error> {
+    let Some(spec) = lookup_command(&full_command[0].get_value()) else {
+        return Ok(RespResponse::Error(INVALID_COMMAND_ERR_STR.to_string()));
+    };
+    if !spec.accepts_argc(full_command.len()) {
+        return Ok(RespResponse::Error(INVALID_COMMAND_ARITY_ERR_STR.to_string()));
+    }
+
+    let keys: Vec<RespResponse> = spec.key_positions(full_command.len()).into_iter()
+        .map(|position| full_command[position].clone())
+        .collect();
+    if keys.is_empty() {
+        return Ok(RespResponse::Error(NO_KEY_ARGUMENTS_ERR_STR.to_string()));
+    }
+    Ok(RespResponse::RespArray(Arc::new(keys)))
+}
+
+/// Builds the `COMMAND INFO` description of a command.
+///
+/// # Arguments
+///
+/// * `spec` - The command's entry in the command table.
+///
+/// # Returns
+///
+/// Returns an array `RespResponse` of `[name, arity, [flags], first_key, last_key, step]`.
+fn command_info(spec: &CommandSpec) -> RespResponse {
+    let flags = spec.flags.iter().map(|flag| RespResponse::SimpleString(flag.to_string())).collect();
+    RespResponse::RespArray(Arc::new(vec![
+        RespResponse::BulkString(spec.name.as_bytes().to_vec()),
+        RespResponse::Integer(spec.arity),
+        RespResponse::RespArray(Arc::new(flags)),
+        RespResponse::Integer(spec.first_key),
+        RespResponse::Integer(spec.last_key),
+        RespResponse::Integer(spec.step),
+    ]))
+}
+
+/// Describes a command the way `COMMAND DOCS` reports it.
+///
+/// # Arguments
+///
+/// * `spec` - The command to describe.
+///
+/// # Returns
+///
+/// Returns the command name and a map of its documentation (`summary` and `arity`).
+fn command_docs(spec: &CommandSpec) -> (RespResponse, RespResponse) {
+    let docs = vec![
+        (RespResponse::BulkString(b"summary".to_vec()), RespResponse::BulkString(spec.summary.as_bytes().to_vec())),
+        (RespResponse::BulkString(b"arity".to_vec()), RespResponse::Integer(spec.arity)),
+    ];
+    (RespResponse::BulkString(spec.name.as_bytes().to_vec()), RespResponse::Map(Arc::new(docs)))
+}
+
+/// Handles the "INFO" command, which reports information and statistics about the server.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments (optional section names).
+/// * `db` - A reference to the shared database.
+/// * `state` - A reference to the shared server state.
+///
+/// # Returns
+///
+/// Returns a bulk string `RespResponse` with the requested sections.
+fn handle_info(args: &[RespResponse], db: &Db, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    let sections: Vec<String> = args[1..].iter()
+        .map(|section| section.get_value().to_ascii_lowercase())
+        .collect();
+    Ok(RespResponse::BulkString(build_info(&sections, db, state).into_bytes()))
+}
+
+/// Handles the "DUMP" command, which serializes the value stored at a key in the RDB format.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+///
+/// # Returns
+///
+/// Returns the serialized value as a bulk string, or a null bulk string if the key does not exist.
+fn handle_dump(args: &[RespResponse], db: &Db) -> Result<RespResponse, anyhow::Error> {
+    let key = args[1].get_bytes();
+    let mut db = db.lock().unwrap();
+
+    match db.get(&key) {
+        Some(redis_item) if redis_item.is_expired() => {
+            db.swap_remove(&key);  // Lazily remove the expired item.
+            Ok(RespResponse::NullBulkString)
+        }
+        Some(redis_item) => Ok(RespResponse::BulkString(encode_dump_payload(redis_item))),
+        None => Ok(RespResponse::NullBulkString),
+    }
+}
+
+/// Handles the "RESTORE" command, which creates a key from a value serialized by DUMP.
+///
+/// The TTL is in milliseconds (0 means no expiration), or an absolute Unix time in milliseconds with
+/// `ABSTTL`. An existing key is only overwritten with `REPLACE`. `IDLETIME seconds` or `FREQ frequency`
+/// (but not both) carry the key's eviction state over from the source server; FREQ requires an LFU
+/// policy, while IDLETIME is ignored under one, like Redis.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `db` - A reference to the shared database.
+/// * `args_cli` - A reference to the command-line arguments.
+/// * `state` - A reference to the shared server state, whose dirty counter counts the changed keys.
+///
+/// # Returns
+///
+/// Returns "OK", or an error if the key exists, the TTL, idle time or frequency is invalid or the payload is corrupt.
+fn handle_restore(args: &[RespResponse], db: &Db, args_cli: &ArgsCli, state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    let key = args[1].get_bytes();
+    let Ok(ttl) = args[2].get_value().parse::<i64>() else {
+        return Ok(RespResponse::Error(NOT_INTEGER_ERR_STR.to_string()));
+    };
+    if ttl < 0 {
+        return Ok(RespResponse::Error(INVALID_TTL_ERR_STR.to_string()));
+    }
+
+    let mut replace = false;
+    let mut absolute_ttl = false;
+    let mut idle_seconds: Option<u64> = None;
+    let mut frequency: Option<u8> = None;
+    let mut options = args[4..].iter();
+    while let Some(option) = options.next() {
+        match option.get_value().to_ascii_uppercase().as_str() {
+            REPLACE_ARG_COMMAND => replace = true,
+            ABSTTL_ARG_COMMAND => absolute_ttl = true,
+            IDLETIME_ARG_COMMAND if frequency.is_none() => {
+                let Some(value) = options.next() else {
+                    return Ok(RespResponse::Error(SYNTAX_ERR_STR.to_string()));
+                };
+                let Ok(idle) = value.get_value().parse::<i64>() else {
+                    return Ok(RespResponse::Error(NOT_INTEGER_ERR_STR.to_string()));
+                };
+                if idle < 0 {
+                    return Ok(RespResponse::Error(INVALID_IDLETIME_ERR_STR.to_string()));
+                }
+                idle_seconds = Some(idle as u64);
+            }
+            FREQ_ARG_COMMAND if idle_seconds.is_none() => {
+                let Some(value) = options.next() else {
+                    return Ok(RespResponse::Error(SYNTAX_ERR_STR.to_string()));
+                };
+                let Ok(freq) = value.get_value().parse::<i64>() else {
+                    return Ok(RespResponse::Error(NOT_INTEGER_ERR_STR.to_string()));
+                };
+                let Ok(freq) = u8::try_from(freq) else {
+                    return Ok(RespResponse::Error(INVALID_FREQ_ERR_STR.to_string()));
+                };
+                frequency = Some(freq);
+            }
+            _ => return Ok(RespResponse::Error(SYNTAX_ERR_STR.to_string())),  // Unknown option, or both IDLETIME and FREQ.
+        }
+    }
+    if frequency.is_some() && args_cli.maxmemory_policy != MaxMemoryPolicy::AllkeysLfu {
+        return Ok(RespResponse::Error(FREQ_REQUIRES_LFU_ERR_STR.to_string()));
+    }
+
+    let value = match decode_dump_payload(&args[3].get_bytes()) {
+        Ok(value) => value,
+        Err(error) => return Ok(RespResponse::Error(error.to_string())),
+    };
+
+    let mut db = db.lock().unwrap();
+    if !replace && lookup_live(&db, &key).is_some() {
+        return Ok(RespResponse::Error(BUSYKEY_ERR_STR.to_string()));
+    }
+
+    let mut redis_item = match ttl {
+        0 => RedisItem::new(value),
+        ttl if absolute_ttl => RedisItem::new_with_expiration(value, UNIX_EPOCH + Duration::from_millis(ttl as u64)),
+        ttl => RedisItem::new_with_expiration(value, SystemTime::now() + Duration::from_millis(ttl as u64)),
+    };
+    if let Some(frequency) = frequency {
+        redis_item.set_frequency(frequency);
+    }
+    if let Some(idle_seconds) = idle_seconds.filter(|_| args_cli.maxmemory_policy != MaxMemoryPolicy::AllkeysLfu) {
+        redis_item.set_idle_seconds(idle_seconds);
+    }
+    if redis_item.is_expired() {
+        db.swap_remove(&key);  // An absolute TTL in the past restores nothing, like Redis.
+    } else {
+        db.insert(key, redis_item);
+    }
+    state.mark_dirty(1);
+    Ok(RespResponse::SimpleString(OK_STR.to_string()))
+}
+
+/// Handles the "SLOWLOG" command, which reads and resets the log of slow commands.
+///
+/// Supported subcommands:
+/// * `GET [count]` - the most recent entries, newest first (10 by default, all with a negative count).
+/// * `LEN` - the number of entries.
+/// * `RESET` - removes every entry.
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `state` - A reference to the shared server state.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` with the requested information, or an error for unknown subcommands.
+fn handle_slowlog(args: &[RespResponse], state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    let subcommand: String = args.get(1).unwrap().get_value();  // Retrieve the subcommand (e.g., "GET").
+
+    match subcommand.to_ascii_uppercase().as_str() {
+        HELP_ARG_COMMAND => Ok(help_reply(SLOWLOG_COMMAND, SLOWLOG_HELP)),
+        GET_COMMAND => {
+            let count = match args.get(2).map(|count| count.get_value().parse::<i64>()) {
+                None => Some(SLOWLOG_DEFAULT_GET_COUNT),
+                Some(Ok(count)) if count < 0 => None,
+                Some(Ok(count)) => Some(count as usize),
+                Some(Err(_)) => return Ok(RespResponse::Error(NOT_INTEGER_ERR_STR.to_string())),
+            };
+            Ok(state.slowlog.lock().unwrap().get(count))
+        }
+        LEN_ARG_COMMAND => Ok(RespResponse::Integer(state.slowlog.lock().unwrap().len() as i64)),
+        RESET_COMMAND => {
+            state.slowlog.lock().unwrap().reset();
+            Ok(RespResponse::SimpleString(OK_STR.to_string()))
+        }
+        _ => Ok(unknown_subcommand(SLOWLOG_COMMAND, &subcommand)),
+    }
+}
+
+/// Handles the "LATENCY" command, which reads and resets the latency monitor.
+///
+/// Supported subcommands:
+/// * `LATEST` - the latest sample of every event.
+/// * `HISTORY event` - every sample of one event.
+/// * `RESET [event ...]` - removes the samples of the given events (all events if none are given).
+///
+/// # Arguments
+///
+/// * `args` - A slice of `RespResponse` arguments.
+/// * `state` - A reference to the shared server state.
+///
+/// # Returns
+///
+/// Returns a `RespResponse` with the requested information, or an error for unknown subcommands.
+fn handle_latency(args: &[RespResponse], state: &SharedState) -> Result<RespResponse, anyhow::Error> {
+    let subcommand: String = args.get(1).unwrap().get_value();  // Retrieve the subcommand (e.g., "LATEST").
+
+    match subcommand.to_ascii_uppercase().as_str() {
+        HELP_ARG_COMMAND => Ok(help_reply(LATENCY_COMMAND, LATENCY_HELP)),
+        LATEST_ARG_COMMAND => Ok(state.latency.lock().unwrap().latest()),
+        HISTORY_ARG_COMMAND => {
+            if let Err(error) = check_arity(&subcommand_name(LATENCY_COMMAND, &subcommand), args, 3) {
+                return Ok(error);
+            }
+            Ok(state.latency.lock().unwrap().history(&args[2].get_value()))
+        }
+        RESET_COMMAND => {
+            let events: Vec<String> = args[2..].iter().map(RespResponse::get_value).collect();
+            Ok(RespResponse::Integer(state.latency.lock().unwrap().reset(&events) as i64))
+        }
+        _ => Ok(unknown_subcommand(LATENCY_COMMAND, &subcommand)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::server::arg_handler::ArgHandler;
+    use crate::server::command_handler::execute_command;
+    use crate::server::command_handler::tests::setup;
+    use crate::server::server_state::ServerState;
+
+    /// Builds the arguments of a command, starting with its name.
+    fn args(parts: &[&str]) -> Vec<RespResponse> {
+        parts.iter().map(|part| RespResponse::BulkString(part.as_bytes().to_vec())).collect()
+    }
+
+    /// Runs inline commands against `db` and returns the replies as text.
+    fn run(db: &Db, args_cli: &ArgsCli, input: &str) -> String {
+        String::from_utf8_lossy(&execute_command(db, args_cli, input.as_bytes())).into_owned()
+    }
+
+    /// Returns the expiration of `key` in unix milliseconds, as PEXPIRETIME reports it.
+    fn pexpiretime(db: &Db, args_cli: &ArgsCli, key: &str) -> i64 {
+        let reply = run(db, args_cli, &format!("PEXPIRETIME {}", key));
+        reply.trim_start_matches(':').trim_end().parse().unwrap()
+    }
+
+    /// Returns the current unix time in milliseconds.
+    fn now_millis() -> i64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64
+    }
+
+    #[test]
+    fn reply_delay_is_only_set_by_sleep_before_reply() {
+        assert_eq!(reply_delay("debug", &args(&["debug", "sleep-before-reply", "250"])), Some(Duration::from_millis(250)));
+        assert_eq!(reply_delay("DEBUG", &args(&["DEBUG", "SLEEP-BEFORE-REPLY", "x"])), None);
+        assert_eq!(reply_delay("DEBUG", &args(&["DEBUG", "SLEEP", "1"])), None);
+        assert_eq!(reply_delay("GET", &args(&["GET", "SLEEP-BEFORE-REPLY", "1"])), None);
+    }
+
+    #[test]
+    fn writes_count_only_the_keys_they_change() {
+        let db: Db = Arc::new(Mutex::new(Keyspace::new()));
+        let state = ServerState::new_shared();
+        let dirty = || state.dirty.load(Ordering::Relaxed);
+
+        handle_set_command(&args(&["SET", "k", "v"]), &db, &state).unwrap();
+        assert_eq!(dirty(), 1);
+
+        // No-op writes leave the counter alone.
+        handle_set_command(&args(&["SET", "k", "v", "NX"]), &db, &state).unwrap();
+        handle_del(&args(&["DEL", "missing"]), &db, &state).unwrap();
+        handle_getdel_command(&args(&["GETDEL", "missing"]), &db, &state).unwrap();
+        handle_getex_command(&args(&["GETEX", "k"]), &db, &state, false).unwrap();
+        handle_getex_command(&args(&["GETEX", "k", "PERSIST"]), &db, &state, false).unwrap();
+        assert_eq!(dirty(), 1);
+
+        // Multi-key writes count every key.
+        handle_mset(&args(&["MSET", "a", "1", "b", "2", "c", "3"]), &db, &state).unwrap();
+        assert_eq!(dirty(), 4);
+        handle_del(&args(&["DEL", "a", "b", "missing"]), &db, &state).unwrap();
+        assert_eq!(dirty(), 6);
+    }
+
+    #[test]
+    fn keys_skips_expired_keys() {
+        let past = SystemTime::now() - Duration::from_secs(1);
+        let keyspace = Keyspace::from([
+            (b"live".to_vec(), RedisItem::new(b"v".to_vec())),
+            (b"expired".to_vec(), RedisItem::new_with_expiration(b"v".to_vec(), past)),
+        ]);
+        let db: Db = Arc::new(Mutex::new(keyspace));
+        let args_cli: ArgsCli = Arc::new(ArgHandler::parse_from(["redis-rust"]));
+
+        let reply = handle_keys(&args(&["KEYS", "*"]), &db, &args_cli).unwrap();
+        assert_eq!(reply.serialize(), b"*1\r\n$4\r\nlive\r\n");
+        assert_eq!(handle_dbsize(&db).unwrap().serialize(), b":1\r\n");
+    }
+
+    #[test]
+    fn dbsize_counts_only_live_keys_under_concurrent_writes() {
+        let db: Db = Arc::new(Mutex::new(Keyspace::new()));
+        let past = SystemTime::now() - Duration::from_secs(1);
+        let dbsize = |db: &Db| match handle_dbsize(db).unwrap() {
+            RespResponse::Integer(count) => count,
+            other => panic!("unexpected DBSIZE reply {:?}", other),
+        };
+
+        let writers: Vec<_> = (0..4).map(|writer| {
+            let db = Arc::clone(&db);
+            std::thread::spawn(move || {
+                for index in 0..250 {
+                    let mut db = db.lock().unwrap();
+                    db.insert(format!("live:{}:{}", writer, index).into_bytes(), RedisItem::new(b"v".to_vec()));
+                    db.insert(format!("expired:{}:{}", writer, index).into_bytes(), RedisItem::new_with_expiration(b"v".to_vec(), past));
+                }
+            })
+        }).collect();
+        let mut last = 0;
+        while !writers.iter().all(|writer| writer.is_finished()) {
+            let count = dbsize(&db);
+            assert!(count >= last && count <= 1000, "DBSIZE {} after {}", count, last);  // Expired keys are never counted.
+            last = count;
+        }
+        writers.into_iter().for_each(|writer| writer.join().unwrap());
+        assert_eq!(dbsize(&db), 1000);
+
+        // Each call removes a bounded number of the expired keys it finds.
+        db.lock().unwrap().retain(|key, _| key.starts_with(b"live:"));
+        db.lock().unwrap().extend((0..DBSIZE_EXPIRE_CLEANUP_LIMIT * 2).map(|index| (format!("expired:{}", index).into_bytes(), RedisItem::new_with_expiration(b"v".to_vec(), past))));
+        assert_eq!(dbsize(&db), 1000);
+        assert_eq!(db.lock().unwrap().len(), 1000 + DBSIZE_EXPIRE_CLEANUP_LIMIT);
+    }
+
+    #[test]
+    fn time_replies_with_seconds_and_microseconds() {
+        let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let RespResponse::RespArray(reply) = handle_time().unwrap() else { panic!("TIME must reply with an array") };
+        let parts: Vec<u64> = reply.iter().map(|part| match part {
+            RespResponse::BulkString(digits) => String::from_utf8_lossy(digits).parse().unwrap(),
+            other => panic!("expected a bulk string, got {:?}", other),
+        }).collect();
+
+        assert_eq!(parts.len(), 2);
+        assert!(parts[0] >= before && parts[0] <= before + 1);
+        assert!(parts[1] < 1_000_000);
+    }
+
+    #[test]
+    fn commands_with_too_few_arguments_get_the_arity_error() {
+        let (db, args_cli) = setup(&[]);
+        let arity_error = |cmd: &str| format!("-ERR wrong number of arguments for '{}' command\r\n", cmd);
+
+        for spec in COMMAND_TABLE.iter().filter(|spec| spec.arity.abs() > 1) {
+            assert_eq!(run(&db, &args_cli, spec.name), arity_error(spec.name));
+        }
+        assert_eq!(run(&db, &args_cli, "MSET a 1 b"), arity_error("mset"));
+        assert_eq!(run(&db, &args_cli, "OBJECT FREQ"), arity_error("object|freq"));
+        assert_eq!(run(&db, &args_cli, "LATENCY HISTORY"), arity_error("latency|history"));
+    }
+
+    #[test]
+    fn command_getkeys_follows_the_key_specs() {
+        let (db, args_cli) = setup(&[]);
+
+        assert_eq!(run(&db, &args_cli, "COMMAND GETKEYS GET foo"), "*1\r\n$3\r\nfoo\r\n");
+        assert_eq!(run(&db, &args_cli, "COMMAND GETKEYS MSET a 1 b 2"), "*2\r\n$1\r\na\r\n$1\r\nb\r\n");
+        assert_eq!(run(&db, &args_cli, "COMMAND GETKEYS BITOP AND dest a b"), "*3\r\n$4\r\ndest\r\n$1\r\na\r\n$1\r\nb\r\n");
+
+        assert_eq!(run(&db, &args_cli, "COMMAND GETKEYS PING"), format!("-{}\r\n", NO_KEY_ARGUMENTS_ERR_STR));
+        assert_eq!(run(&db, &args_cli, "COMMAND GETKEYS GET"), format!("-{}\r\n", INVALID_COMMAND_ARITY_ERR_STR));
+        assert_eq!(run(&db, &args_cli, "COMMAND GETKEYS NOPE x"), format!("-{}\r\n", INVALID_COMMAND_ERR_STR));
+    }
+
+    #[test]
+    fn set_applies_expiration_options() {
+        let (db, args_cli) = setup(&[]);
+
+        run(&db, &args_cli, "SET k v EX 100");
+        assert!((pexpiretime(&db, &args_cli, "k") - now_millis() - 100_000).abs() < 1000);
+        run(&db, &args_cli, "SET k v PX 1500");
+        assert!((pexpiretime(&db, &args_cli, "k") - now_millis() - 1500).abs() < 1000);
+        run(&db, &args_cli, "SET k v EXAT 4000000000");
+        assert_eq!(pexpiretime(&db, &args_cli, "k"), 4_000_000_000_000);
+        run(&db, &args_cli, "SET k w PXAT 4000000000123 GET");
+        assert_eq!(pexpiretime(&db, &args_cli, "k"), 4_000_000_000_123);
+        assert_eq!(run(&db, &args_cli, "SET k x KEEPTTL GET"), "$1\r\nw\r\n");
+        assert_eq!(pexpiretime(&db, &args_cli, "k"), 4_000_000_000_123);
+        run(&db, &args_cli, "SET k v");
+        assert_eq!(pexpiretime(&db, &args_cli, "k"), -1);
+
+        assert_eq!(run(&db, &args_cli, "SET k v EX 0"), "-ERR invalid expire time in 'set' command\r\n");
+        assert_eq!(run(&db, &args_cli, "SET k v EX abc"), format!("-{}\r\n", NOT_INTEGER_ERR_STR));
+        assert_eq!(run(&db, &args_cli, "SET k v EX 10 KEEPTTL"), format!("-{}\r\n", SYNTAX_ERR_STR));
+        assert_eq!(run(&db, &args_cli, "SET k v NX XX"), format!("-{}\r\n", SYNTAX_ERR_STR));
+        assert_eq!(run(&db, &args_cli, "SET k v PX"), format!("-{}\r\n", SYNTAX_ERR_STR));
+        assert_eq!(pexpiretime(&db, &args_cli, "k"), -1);
+
+        assert_eq!(run(&db, &args_cli, "SET k w NX"), "$-1\r\n");
+        assert_eq!(run(&db, &args_cli, "SET k w XX GET"), "$1\r\nv\r\n");
+        assert_eq!(run(&db, &args_cli, "SET missing w XX"), "$-1\r\n");
+    }
+
+    #[test]
+    fn getex_applies_expiration_options() {
+        let (db, args_cli) = setup(&[]);
+        run(&db, &args_cli, "SET k v");
+
+        assert_eq!(run(&db, &args_cli, "GETEX k EX 100"), "$1\r\nv\r\n");
+        assert!((pexpiretime(&db, &args_cli, "k") - now_millis() - 100_000).abs() < 1000);
+        run(&db, &args_cli, "GETEX k PXAT 4000000000123");
+        assert_eq!(pexpiretime(&db, &args_cli, "k"), 4_000_000_000_123);
+        run(&db, &args_cli, "GETEX k");
+        assert_eq!(pexpiretime(&db, &args_cli, "k"), 4_000_000_000_123);
+        run(&db, &args_cli, "GETEX k PERSIST");
+        assert_eq!(pexpiretime(&db, &args_cli, "k"), -1);
+
+        assert_eq!(run(&db, &args_cli, "GETEX k EX -1"), "-ERR invalid expire time in 'getex' command\r\n");
+        assert_eq!(run(&db, &args_cli, "GETEX k PERSIST EX 10"), format!("-{}\r\n", SYNTAX_ERR_STR));
+        assert_eq!(run(&db, &args_cli, "GETEX missing EX 10"), "$-1\r\n");
+        assert_eq!(run(&db, &args_cli, "GETEX k PX 1\r\nDEBUG SLEEP 0.01\r\nGET k"), "$1\r\nv\r\n+OK\r\n$-1\r\n");
+    }
+
+    #[test]
+    fn incr_refuses_to_overflow() {
+        let (db, args_cli) = setup(&[]);
+        let overflow = format!("-{}\r\n", OVERFLOW_ERR_STR);
+
+        assert_eq!(run(&db, &args_cli, "INCR n\r\nINCRBY n 9223372036854775806"), ":1\r\n:9223372036854775807\r\n");
+        assert_eq!(run(&db, &args_cli, "INCR n"), overflow);
+        assert_eq!(run(&db, &args_cli, "GET n"), "$19\r\n9223372036854775807\r\n");
+        assert_eq!(run(&db, &args_cli, "SET n -9223372036854775807\r\nDECR n"), "+OK\r\n:-9223372036854775808\r\n");
+        assert_eq!(run(&db, &args_cli, "DECR n"), overflow);
+        assert_eq!(run(&db, &args_cli, "DECRBY n 1"), overflow);
+
+        assert_eq!(run(&db, &args_cli, "INCRBY n 1x"), format!("-{}\r\n", NOT_INTEGER_ERR_STR));
+        assert_eq!(run(&db, &args_cli, "SET s 12a\r\nINCR s"), format!("+OK\r\n-{}\r\n", NOT_INTEGER_ERR_STR));
+    }
+
+    #[test]
+    fn expiretime_reports_the_absolute_deadline() {
+        let (db, args_cli) = setup(&[]);
+        run(&db, &args_cli, "SET volatile v PXAT 4000000000123\r\nSET persistent v");
+
+        assert_eq!(run(&db, &args_cli, "PEXPIRETIME volatile\r\nEXPIRETIME volatile"), ":4000000000123\r\n:4000000000\r\n");
+        assert_eq!(run(&db, &args_cli, "PEXPIRETIME persistent\r\nEXPIRETIME persistent"), ":-1\r\n:-1\r\n");
+        assert_eq!(run(&db, &args_cli, "PEXPIRETIME missing\r\nEXPIRETIME missing"), ":-2\r\n:-2\r\n");
+
+        run(&db, &args_cli, "SET volatile v EX 100");
+        assert!((pexpiretime(&db, &args_cli, "volatile") - now_millis() - 100_000).abs() < 1000);
+    }
+
+    #[test]
+    fn bit_commands_address_bits_from_the_most_significant() {
+        let (db, args_cli) = setup(&[]);
+
+        assert_eq!(run(&db, &args_cli, "SETBIT k 7 1\r\nSETBIT k 7 1\r\nGETBIT k 7\r\nGETBIT k 100"), ":0\r\n:1\r\n:1\r\n:0\r\n");
+        assert_eq!(run(&db, &args_cli, "SETBIT k 9 1\r\nGET k"), ":0\r\n$2\r\n\u{1}@\r\n");
+        assert_eq!(run(&db, &args_cli, "BITCOUNT k\r\nBITCOUNT k 1 -1\r\nBITCOUNT k 5 10"), ":2\r\n:1\r\n:0\r\n");
+
+        assert_eq!(run(&db, &args_cli, "SETBIT k -1 1"), format!("-{}\r\n", BIT_OFFSET_ERR_STR));
+        assert_eq!(run(&db, &args_cli, "SETBIT k 1 2"), format!("-{}\r\n", BIT_VALUE_ERR_STR));
+    }
+
+    #[test]
+    fn flushall_empties_the_keyspace() {
+        let (db, args_cli) = setup(&[]);
+
+        run(&db, &args_cli, "SET a 1\r\nSET b 2 EX 100");
+        assert_eq!(run(&db, &args_cli, "FLUSHALL\r\nDBSIZE"), "+OK\r\n:0\r\n");
+        run(&db, &args_cli, "SET a 1\r\nSET b 2 EX 100");
+        assert_eq!(run(&db, &args_cli, "FLUSHALL ASYNC\r\nDBSIZE\r\nGET a"), "+OK\r\n:0\r\n$-1\r\n");
+        assert_eq!(run(&db, &args_cli, "FLUSHALL sync\r\nDBSIZE"), "+OK\r\n:0\r\n");
+
+        assert_eq!(run(&db, &args_cli, "FLUSHALL LATER"), format!("-{}\r\n", SYNTAX_ERR_STR));
+    }
+
+    #[test]
+    fn bitpos_finds_the_first_matching_bit() {
+        let (db, args_cli) = setup(&[]);
+        run(&db, &args_cli, "SETBIT k 7 1\r\nSETBIT k 9 1");
+
+        assert_eq!(run(&db, &args_cli, "BITPOS k 1\r\nBITPOS k 0\r\nBITPOS k 1 1"), ":7\r\n:0\r\n:9\r\n");
+        assert_eq!(run(&db, &args_cli, "BITPOS missing 0\r\nBITPOS missing 1"), ":0\r\n:-1\r\n");
+    }
+
+    #[test]
+    fn bitop_combines_strings_padded_with_zero_bytes() {
+        let (db, args_cli) = setup(&[]);
+        run(&db, &args_cli, "SET a \"\\xf0\\x0f\"\r\nSET b \"\\xff\"");
+
+        assert_eq!(execute_command(&db, &args_cli, b"BITOP AND d a b\r\nGET d"), b":2\r\n$2\r\n\xf0\x00\r\n");
+        assert_eq!(execute_command(&db, &args_cli, b"BITOP OR d a b missing\r\nGET d"), b":2\r\n$2\r\n\xff\x0f\r\n");
+        assert_eq!(execute_command(&db, &args_cli, b"BITOP XOR d a b\r\nGET d"), b":2\r\n$2\r\n\x0f\x0f\r\n");
+        assert_eq!(execute_command(&db, &args_cli, b"BITOP NOT d a\r\nGET d"), b":2\r\n$2\r\n\x0f\xf0\r\n");
+        assert_eq!(execute_command(&db, &args_cli, b"BITOP AND d missing\r\nEXISTS d"), b":0\r\n:0\r\n");
+
+        assert_eq!(run(&db, &args_cli, "BITOP NOT d a b"), format!("-{}\r\n", BITOP_NOT_ERR_STR));
+        assert_eq!(run(&db, &args_cli, "BITOP NAND d a b"), format!("-{}\r\n", SYNTAX_ERR_STR));
+    }
+
+    #[test]
+    fn in_place_edits_keep_the_expiration() {
+        let (db, args_cli) = setup(&[]);
+
+        run(&db, &args_cli, "SET k hello PXAT 4000000000123\r\nAPPEND k !");
+        assert_eq!(pexpiretime(&db, &args_cli, "k"), 4_000_000_000_123);
+        run(&db, &args_cli, "SETRANGE k 0 j");
+        assert_eq!(pexpiretime(&db, &args_cli, "k"), 4_000_000_000_123);
+        assert_eq!(run(&db, &args_cli, "GET k"), "$6\r\njello!\r\n");
+
+        run(&db, &args_cli, "SET n 10 PXAT 4000000000123\r\nINCR n\r\nDECRBY n 3");
+        assert_eq!(pexpiretime(&db, &args_cli, "n"), 4_000_000_000_123);
+        assert_eq!(run(&db, &args_cli, "GET n"), "$1\r\n8\r\n");
+    }
+
+    #[test]
+    fn setrange_overwrites_and_pads_with_zero_bytes() {
+        let (db, args_cli) = setup(&["--proto-max-bulk-len", "100"]);
+
+        assert_eq!(run(&db, &args_cli, "SET k \"Hello World\"\r\nSETRANGE k 6 Redis\r\nGET k"), "+OK\r\n:11\r\n$11\r\nHello Redis\r\n");
+        assert_eq!(run(&db, &args_cli, "SETRANGE new 3 ab\r\nGET new"), ":5\r\n$5\r\n\0\0\0ab\r\n");
+        assert_eq!(run(&db, &args_cli, "SETRANGE k 100 \"\"\r\nSETRANGE missing 5 \"\"\r\nEXISTS missing"), ":11\r\n:0\r\n:0\r\n");
+
+        assert_eq!(run(&db, &args_cli, "SETRANGE k -1 x"), format!("-{}\r\n", OFFSET_RANGE_ERR_STR));
+        assert_eq!(run(&db, &args_cli, "SETRANGE k 99 xy"), format!("-{}\r\n", STRING_TOO_LONG_ERR_STR));
+        assert_eq!(run(&db, &args_cli, "SETRANGE k x y"), format!("-{}\r\n", NOT_INTEGER_ERR_STR));
+    }
+
+    #[test]
+    fn setbit_grows_the_string_up_to_512mb() {
+        let (db, args_cli) = setup(&[]);
+
+        assert_eq!(run(&db, &args_cli, "SETBIT k 8191 1\r\nBITCOUNT k\r\nGETBIT k 8191"), ":0\r\n:1\r\n:1\r\n");
+        assert!(run(&db, &args_cli, "GET k").starts_with("$1024\r\n"));
+        assert_eq!(run(&db, &args_cli, "SETBIT k 100 1\r\nBITCOUNT k\r\nBITCOUNT k 0 12"), ":0\r\n:2\r\n:1\r\n");
+        assert!(run(&db, &args_cli, "GET k").starts_with("$1024\r\n"));
+
+        let past_limit = MAX_BIT_OFFSET + 1;
+        assert_eq!(run(&db, &args_cli, &format!("SETBIT big {} 1", past_limit)), format!("-{}\r\n", BIT_OFFSET_ERR_STR));
+        assert_eq!(run(&db, &args_cli, &format!("GETBIT big {}", past_limit)), format!("-{}\r\n", BIT_OFFSET_ERR_STR));
+        assert_eq!(run(&db, &args_cli, "SETBIT big 34359738367 1\r\nEXISTS big"), format!("-{}\r\n:0\r\n", BIT_OFFSET_ERR_STR));
+    }
+
+    #[test]
+    fn unknown_commands_are_named_in_the_error() {
+        let (db, args_cli) = setup(&[]);
+        assert_eq!(run(&db, &args_cli, "FOO bar"), "-ERR unknown command 'FOO'\r\n");
+        assert_eq!(run(&db, &args_cli, "*1\r\n$12\r\nFOO\r\n+OK\r\n:1\r\n"), "-ERR unknown command 'FOO  +OK  :1'\r\n");
+        assert_eq!(run(&db, &args_cli, &format!("{} bar", "x".repeat(200))), format!("-ERR unknown command '{}'\r\n", "x".repeat(MAX_QUOTED_ARG_LEN)));
+        assert_eq!(run(&db, &args_cli, "*2\r\n$6\r\nOBJECT\r\n$4\r\nA\r\nB\r\n"), "-ERR unknown subcommand 'A  B'. Try OBJECT HELP.\r\n");
+        for scripting in ["EVAL \"return 1\" 0", "EVALSHA abc 0", "FCALL f 0", "FUNCTION LIST", "SCRIPT LOAD \"return 1\""] {
+            assert_eq!(run(&db, &args_cli, scripting), format!("-{}\r\n", UNSUPPORTED_COMMAND_ERR_STR));
+        }
+    }
+}
@@ -1,13 +1,23 @@
+use std::io;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Instant;
 
 use anyhow::Result;
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, ReadHalf, WriteHalf};
 use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
 
 use crate::server::arg_handler::ArgsCli;
-use crate::server::command::Command;
-use crate::server::common_variables::{CONFIG_COMMAND, Db, ECHO_COMMAND, GET_COMMAND, KEYS_COMMAND, PING_COMMAND, SET_COMMAND};
-use crate::server::resp_response::{parse_message, RespResponse};
+use crate::server::command::{help_reply, quoted_arg, reply_delay, subcommand_name, unknown_subcommand, wrong_arity, Command};
+use crate::server::command_table::{lookup_command, CommandSpec};
+use crate::server::common_variables::{APPEND_COMMAND, ASKING_COMMAND, ASTERISK_, AUTH_ARG_COMMAND, BGSAVE_COMMAND, BITCOUNT_COMMAND, BITOP_COMMAND, BITPOS_COMMAND, CLIENT_COMMAND, CLIENT_HELP, CLIENT_NAME_ERR_STR, CLIENT_READ_BUFFER_LEN, CLUSTER_COMMAND, COMMAND_COMMAND, CONFIG_COMMAND, CONTAINER_COMMANDS, CRLF, Db, DBSIZE_COMMAND, DEBUG_COMMAND, DECRBY_COMMAND, DECR_COMMAND, DEFAULT_USER, DEL_COMMAND, DUMP_COMMAND, ECHO_COMMAND, EVALSHA_COMMAND, EVALSHA_RO_COMMAND, EVAL_COMMAND, EVAL_RO_COMMAND, EXISTS_COMMAND, EXPIRETIME_COMMAND, FCALL_COMMAND, FCALL_RO_COMMAND, FLUSHALL_COMMAND, FUNCTION_COMMAND, GETBIT_COMMAND, GETDEL_COMMAND, GETEX_COMMAND, GETNAME_ARG_COMMAND, GET_COMMAND, HELLO_COMMAND, HELP_ARG_COMMAND, ID_ARG_COMMAND, INCRBY_COMMAND, INCR_COMMAND, INFO_ARG_COMMAND, INFO_COMMAND, KEYS_COMMAND, LASTSAVE_COMMAND, LATENCY_COMMAND, LATENCY_EVENT_COMMAND, LOLWUT_COMMAND, MGET_COMMAND, MONITOR_COMMAND, MONITOR_MODE_ERR_STR, MSET_COMMAND, NOPROTO_ERR_STR, NO_EVICT_ARG_COMMAND, NO_TOUCH_ARG_COMMAND, OBJECT_COMMAND, OFF_ARG_COMMAND, OK_STR, ON_ARG_COMMAND, OOM_ERR_STR, PEXPIRETIME_COMMAND, PING_COMMAND, PROTOCOL_VERSION_ERR_STR, QUIT_COMMAND, READONLY_COMMAND, READWRITE_COMMAND, RESET_COMMAND, RESET_STR, RESP2_PROTOCOL, RESP3_PROTOCOL, RESTORE_COMMAND, SAVE_COMMAND, SCRIPT_COMMAND, SETBIT_COMMAND, SETNAME_ARG_COMMAND, SETRANGE_COMMAND, SET_COMMAND, SLOWLOG_COMMAND, SYNTAX_ERR_STR, TIME_COMMAND, WRONGPASS_ERR_STR};
+use crate::server::memory::evict_if_needed;
+use crate::server::monitor::publish_command;
+use crate::server::resp_response::{ParseError, RequestParser, RespResponse};
+use crate::server::server_state::{ServerState, SharedState};
 
 /// `CommandHandler` is responsible for processing client commands received over a TCP connection.
 pub struct CommandHandler {
@@ -15,6 +25,26 @@ pub struct CommandHandler {
     writer: WriteHalf<TcpStream>,            // Writer for sending responses back to the client.
     db: Db,                                  // Reference to the shared database.
     args_cli: ArgsCli,                       // Command-line arguments passed to the server.
+    state: SharedState,                      // Shared runtime state of the server.
+    conn: ConnState,                         // State of the connection that CLIENT and HELLO inspect and change.
+    monitor: Option<broadcast::Receiver<String>>, // Feed of executed commands while the client is in MONITOR mode.
+    closing: bool,                           // Set by QUIT; the connection closes once the reply is sent.
+    reply_buffer: Vec<u8>,                   // Reused buffer each reply is serialized into before being written.
+}
+
+/// `ConnState` is the state of a single client connection, independent of its socket.
+///
+/// It is what the connection-level commands `CLIENT` and `HELLO` read and change, so `CommandHandler`
+/// and `execute_command` handle them the same way.
+pub struct ConnState {
+    id: u64,                     // Unique id of the connection, reported by CLIENT ID and HELLO.
+    addr: Option<SocketAddr>,    // Address of the connected client, if there is a socket.
+    protocol: i64,               // RESP version replies are sent in; RESP2 until HELLO negotiates another.
+    name: Vec<u8>,               // Name set by CLIENT SETNAME or HELLO SETNAME; empty if none.
+    no_touch: bool,              // Set by CLIENT NO-TOUCH; reads leave the keys' access metadata untouched.
+    created: Instant,            // When the connection was accepted, reported as `age` by CLIENT INFO.
+    last_interaction: Instant,   // When the last command was received, reported as `idle` by CLIENT INFO.
+    last_command: String,        // Full name of the last command received (e.g. "client|info").
 }
 
 impl CommandHandler {
@@ -26,38 +56,102 @@ impl CommandHandler {
     /// * `writer` - The writing half of the TCP stream.
     /// * `db` - Shared database instance.
     /// * `args_cli` - Command-line arguments for the server.
-    pub fn new(reader: ReadHalf<TcpStream>, writer: WriteHalf<TcpStream>, db: Db, args_cli: ArgsCli) -> Self {
+    /// * `state` - Shared runtime state of the server.
+    /// * `addr` - Address of the connected client.
+    pub fn new(reader: ReadHalf<TcpStream>, writer: WriteHalf<TcpStream>, db: Db, args_cli: ArgsCli, state: SharedState, addr: SocketAddr) -> Self {
+        let conn = ConnState::new(&state, Some(addr));
         CommandHandler {
             reader: BufReader::new(reader),  // Wrap the reader in a `BufReader` for efficient reading.
             writer,
             db,
             args_cli,
+            state,
+            conn,
+            monitor: None,
+            closing: false,
+            reply_buffer: Vec::new(),
         }
     }
 
     /// Runs the command handler, continuously reading commands from the client and processing them.
     ///
+    /// Incoming bytes are accumulated until they form complete commands, so a command may span several
+    /// reads and a single read may carry several (pipelined) commands. A protocol violation is answered
+    /// with an error and closes the connection. While the client is in MONITOR mode, every command
+    /// published by other clients is forwarded to it as it arrives.
+    ///
+    /// A client that goes away while a reply is being read or written (a reset connection or a broken
+    /// pipe) is treated like a regular disconnect rather than a failure. Everything the connection holds,
+    /// such as its MONITOR subscription, is released when the handler is dropped.
+    ///
     /// # Returns
     ///
-    /// Returns `Ok(())` when the client disconnects or an error occurs.
+    /// Returns `Ok(())` when the client disconnects, or an error if the connection failed for another reason.
     pub async fn run(&mut self) -> Result<(), anyhow::Error> {
-        let mut buffer = [0; 1024];  // Buffer for storing incoming data.
+        match self.serve().await {
+            Err(e) if is_disconnect(&e) => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Reads and answers the client's commands until the connection ends; the loop behind `run`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` when the client disconnects or an error occurs.
+    async fn serve(&mut self) -> Result<(), anyhow::Error> {
+        let mut pending: Vec<u8> = Vec::new();  // Received bytes that have not been processed yet.
+        let mut parser = RequestParser::default();  // Keeps the progress of a request spanning several reads.
 
         loop {
-            // Read data from the client into the buffer.
-            let bytes_read = self.reader.read(&mut buffer).await?;
+            // Read data from the client into the buffer, forwarding monitored commands in the meantime.
+            pending.reserve(CLIENT_READ_BUFFER_LEN);
+            let bytes_read = match &mut self.monitor {
+                Some(receiver) => tokio::select! {
+                    read = self.reader.read_buf(&mut pending) => read?,
+                    line = receiver.recv() => {
+                        match line {
+                            Ok(line) => {
+                                self.print_to_client(RespResponse::SimpleString(line)).await?;
+                                self.writer.flush().await?;
+                            }
+                            Err(RecvError::Lagged(_)) => {}  // Lines dropped for a slow monitor are skipped.
+                            Err(RecvError::Closed) => return Ok(()),
+                        }
+                        continue;
+                    }
+                },
+                None => self.reader.read_buf(&mut pending).await?,
+            };
 
             // If no data was read, the client has disconnected.
             if bytes_read == 0 {
                 return Ok(());
             }
 
-            // Convert the received data to a UTF-8 string, handling any errors.
-            let client_command = std::str::from_utf8(&buffer[..bytes_read])
-                .map_err(|_| anyhow::anyhow!("Invalid UTF-8 in command"))?;
-
-            // Process the client's command.
-            self.process_client_command(client_command).await?;
+            // Process every complete command, keeping a trailing partial command for the next read.
+            let mut processed = 0;
+            loop {
+                let message = match parser.parse(&pending[processed..], self.args_cli.proto_max_bulk_len, self.args_cli.proto_max_inline_len) {
+                    Ok((message, consumed)) => {
+                        processed += consumed;
+                        message
+                    }
+                    Err(e) if matches!(e.downcast_ref::<ParseError>(), Some(ParseError::Incomplete)) => break,
+                    Err(e) => return self.close_on_protocol_error(e).await,
+                };
+                if is_empty_request(&message) {
+                    continue;  // Blank inline lines and empty arrays get no reply.
+                }
+                if let Err(e) = self.process_client_command(message).await {
+                    return self.close_on_protocol_error(e).await;
+                }
+                if self.closing {
+                    self.writer.flush().await?;
+                    return Ok(());
+                }
+            }
+            pending.drain(..processed);
 
             // Flush the writer to ensure the response is sent to the client.
             self.writer.flush().await?;
@@ -66,43 +160,120 @@ impl CommandHandler {
 
     /// Processes a single client command.
     ///
+    /// A command that cannot be extracted or fails to execute is answered with `-ERR <message>` and the
+    /// connection stays open for the next command; only protocol errors and I/O failures are returned.
+    ///
     /// # Arguments
     ///
-    /// * `client_command` - The command received from the client as a string slice.
+    /// * `message` - The parsed RESP message received from the client.
     ///
     /// # Returns
     ///
-    /// Returns `Ok(())` if the command was successfully processed, or an error if it failed.
-    async fn process_client_command(&mut self, client_command: &str) -> Result<(), anyhow::Error> {
-        // Parse the command and its arguments from the client's input.
-        let (command, args) = CommandHandler::get_command_with_args(client_command).unwrap();
+    /// Returns `Ok(())` if a reply was sent, or an error if the connection should be closed.
+    async fn process_client_command(&mut self, message: RespResponse) -> Result<(), anyhow::Error> {
+        // Extract the command and its arguments from the client's message, then handle it.
+        let mut delay = None;
+        let result = CommandHandler::get_command_with_args(message).and_then(|(command, args)| {
+            self.conn.record_command(&command, &args);
+            match self.handle_connection_command(&command, &args) {
+                Some(response) => Ok(response),
+                None => {
+                    if let Some(addr) = &self.conn.addr {
+                        publish_command(&self.state, addr, &command, &args);
+                    }
+                    delay = reply_delay(&command, &args);
+                    handle_command(&command, &args, &self.db, &self.args_cli, &self.state, self.conn.addr.as_ref(), self.conn.no_touch)
+                }
+            }
+        });
 
-        // Handle the command and generate a response.
-        let response = self.handle_command(&command, &args).unwrap();
+        // Turn recoverable failures into an error reply instead of ending the connection.
+        let response = match result {
+            Ok(response) => response,
+            Err(e) if matches!(e.downcast_ref::<ParseError>(), Some(ParseError::Protocol(_))) => return Err(e),
+            Err(e) => RespResponse::Error(format!("ERR {e}")),
+        };
 
-        // Send the response back to the client.
+        // Hold the reply back if DEBUG SLEEP-BEFORE-REPLY asked for it, then send it back to the client.
+        if let Some(delay) = delay {
+            tokio::time::sleep(delay).await;
+        }
         self.print_to_client(response).await
     }
 
-    /// Parses a command and its arguments from the client's input.
+    /// Handles the commands that change the state of the connection itself rather than the server.
+    ///
+    /// * `MONITOR` - starts streaming every command executed by other clients.
+    /// * `RESET` - leaves MONITOR mode and resets the connection's state (see `ConnState::handle_connection_command`).
+    /// * `QUIT` - closes the connection once the reply is sent.
+    /// * `CLIENT` and `HELLO` - see `ConnState::handle_connection_command`.
+    ///
+    /// In MONITOR mode every other command is rejected.
     ///
     /// # Arguments
     ///
-    /// * `client_command` - The command string received from the client.
+    /// * `command` - The command string received from the client.
+    /// * `args` - The arguments associated with the command, starting with the command name.
     ///
     /// # Returns
     ///
-    /// Returns a tuple containing the command as a `String` and the arguments as an `Arc<Vec<RespResponse>>`.
-    fn get_command_with_args(client_command: &str) -> Result<(String, Arc<Vec<RespResponse>>)> {
-        // Parse the RESP message from the client command.
-        let (resp, _) = parse_message(client_command)?;
+    /// Returns the reply if the command was handled here, or `None` if it should be dispatched normally.
+    fn handle_connection_command(&mut self, command: &str, args: &[RespResponse]) -> Option<RespResponse> {
+        match command.to_ascii_uppercase().as_str() {
+            MONITOR_COMMAND | QUIT_COMMAND if lookup_command(command).is_some_and(|spec| !spec.accepts_argc(args.len())) => Some(wrong_arity(command)),
+            MONITOR_COMMAND => {
+                self.monitor = Some(self.state.monitor.subscribe());
+                Some(RespResponse::SimpleString(OK_STR.to_string()))
+            }
+            RESET_COMMAND => {
+                self.monitor = None;
+                self.conn.handle_connection_command(command, args)
+            }
+            QUIT_COMMAND => {
+                self.closing = true;
+                Some(RespResponse::SimpleString(OK_STR.to_string()))
+            }
+            _ if self.monitor.is_some() => Some(RespResponse::Error(MONITOR_MODE_ERR_STR.to_string())),
+            _ => self.conn.handle_connection_command(command, args),
+        }
+    }
+
+    /// Replies to a protocol error and ends the connection; any other error is returned as-is.
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - The error raised while parsing or processing a command.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` once the protocol error was sent to the client, or the original error otherwise.
+    async fn close_on_protocol_error(&mut self, error: anyhow::Error) -> Result<(), anyhow::Error> {
+        if !matches!(error.downcast_ref::<ParseError>(), Some(ParseError::Protocol(_))) {
+            return Err(error);
+        }
+        eprintln!("Closing client connection: {error}");
+        self.print_to_client(RespResponse::Error(format!("ERR {error}"))).await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
 
-        // Extract the command and arguments from the parsed message.
-        resp.get_command_and_args()
+    /// Extracts a command and its arguments from a parsed client message.
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - The parsed RESP message received from the client.
+    ///
+    /// # Returns
+    ///
+    /// Returns a tuple containing the command as a `String` and the arguments as an `Arc<Vec<RespResponse>>`.
+    fn get_command_with_args(message: RespResponse) -> Result<(String, Arc<Vec<RespResponse>>)> {
+        message.get_command_and_args()
     }
 
     /// Sends a response back to the client.
     ///
+    /// The response is serialized into the connection's reusable buffer and written with a single call.
+    ///
     /// # Arguments
     ///
     /// * `value` - The response to be sent to the client as a `RespResponse`.
@@ -112,39 +283,602 @@ impl CommandHandler {
     /// Returns `Ok(())` if the response was successfully sent, or an error if it failed.
     async fn print_to_client(&mut self, value: RespResponse) -> Result<(), anyhow::Error> {
         // Serialize the response and write it to the client.
-        Ok(self.writer.write_all(value.serialize().as_bytes()).await?)
+        self.reply_buffer.clear();
+        value.serialize_for(self.conn.protocol, &mut self.reply_buffer);
+        Ok(self.writer.write_all(&self.reply_buffer).await?)
     }
+}
 
-    /// Handles the client's command by mapping it to a known command and executing it.
+impl ConnState {
+    /// Creates the state of a new connection, which speaks RESP2 until HELLO negotiates another protocol.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - Shared runtime state of the server, which hands out the connection id.
+    /// * `addr` - Address of the connected client, or `None` without a socket.
+    pub fn new(state: &SharedState, addr: Option<SocketAddr>) -> Self {
+        ConnState {
+            id: state.new_client_id(),
+            addr,
+            protocol: RESP2_PROTOCOL,
+            name: Vec::new(),
+            no_touch: false,
+            created: Instant::now(),
+            last_interaction: Instant::now(),
+            last_command: String::new(),
+        }
+    }
+
+    /// Handles the commands that inspect or change the state of the connection.
+    ///
+    /// * `CLIENT` - inspects and toggles the connection's flags (see `handle_client_command`).
+    /// * `HELLO` - the connection handshake (see `handle_hello_command`).
+    /// * `RESET` - restores the state a connection starts with: no name, RESP2 and CLIENT NO-TOUCH off.
     ///
     /// # Arguments
     ///
     /// * `command` - The command string received from the client.
-    /// * `args` - The arguments associated with the command as a slice of `RespResponse`.
+    /// * `args` - The arguments associated with the command, starting with the command name.
+    ///
+    /// # Returns
+    ///
+    /// Returns the reply if the command was handled here, or `None` if it should be dispatched normally.
+    fn handle_connection_command(&mut self, command: &str, args: &[RespResponse]) -> Option<RespResponse> {
+        match command.to_ascii_uppercase().as_str() {
+            CLIENT_COMMAND | HELLO_COMMAND | RESET_COMMAND if lookup_command(command).is_some_and(|spec| !spec.accepts_argc(args.len())) => Some(wrong_arity(command)),
+            CLIENT_COMMAND => Some(self.handle_client_command(args)),
+            HELLO_COMMAND => Some(self.handle_hello_command(args)),
+            RESET_COMMAND => {
+                self.protocol = RESP2_PROTOCOL;
+                self.name.clear();
+                self.no_touch = false;
+                Some(RespResponse::SimpleString(RESET_STR.to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Handles the "CLIENT" command, which inspects and toggles flags of the current connection.
+    ///
+    /// Supported subcommands:
+    /// * `ID` - the unique id of the connection.
+    /// * `INFO` - a description of the connection, in the format of a `CLIENT LIST` entry.
+    /// * `GETNAME` / `SETNAME name` - the name of the connection; an empty name removes it.
+    /// * `NO-TOUCH ON|OFF` - reads from this connection stop (or resume) updating the keys' access
+    ///   frequency and idle time, so tools scanning the keyspace do not perturb eviction.
+    /// * `NO-EVICT ON|OFF` - accepted for compatibility; this server never evicts client connections,
+    ///   so every client is effectively exempt already.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - A slice of `RespResponse` arguments.
     ///
     /// # Returns
     ///
-    /// Returns the response to the command as a `RespResponse`, or an error if the command failed.
-    fn handle_command(
-        &self,
-        command: &str,
-        args: &[RespResponse],
-    ) -> Result<RespResponse, anyhow::Error> {
-        // Convert the command to uppercase for case-insensitive matching.
-        let command_name = command.to_ascii_uppercase();
-
-        // Match the command name to a known command, creating a `Command` object.
-        let prepared_command: Command = match command_name.as_str() {
-            PING_COMMAND => Command::Ping,
-            ECHO_COMMAND => Command::Echo(args),
-            SET_COMMAND => Command::Set(args, &self.db),
-            GET_COMMAND => Command::Get(args, &self.db),
-            CONFIG_COMMAND => Command::ConfigGet(args, &self.args_cli),
-            KEYS_COMMAND => Command::Keys(args, &self.db),
-            _ => Command::Unknown,
+    /// Returns the requested value or `+OK`, or an error for unknown subcommands, invalid names or values other than ON and OFF.
+    fn handle_client_command(&mut self, args: &[RespResponse]) -> RespResponse {
+        let subcommand: String = args.get(1).unwrap().get_value();  // Retrieve the subcommand (e.g., "NO-TOUCH").
+
+        match subcommand.to_ascii_uppercase().as_str() {
+            HELP_ARG_COMMAND => help_reply(CLIENT_COMMAND, CLIENT_HELP),
+            ID_ARG_COMMAND | GETNAME_ARG_COMMAND | INFO_ARG_COMMAND if args.len() != 2 => wrong_arity(&subcommand_name(CLIENT_COMMAND, &subcommand)),
+            ID_ARG_COMMAND => RespResponse::Integer(self.id as i64),
+            INFO_ARG_COMMAND => RespResponse::BulkString(self.client_info().into_bytes()),
+            GETNAME_ARG_COMMAND if self.name.is_empty() => RespResponse::NullBulkString,
+            GETNAME_ARG_COMMAND => RespResponse::BulkString(self.name.clone()),
+            SETNAME_ARG_COMMAND => {
+                if args.len() != 3 {
+                    return wrong_arity(&subcommand_name(CLIENT_COMMAND, &subcommand));
+                }
+                match self.set_name(args[2].get_bytes()) {
+                    Ok(()) => RespResponse::SimpleString(OK_STR.to_string()),
+                    Err(error) => error,
+                }
+            }
+            toggle @ (NO_TOUCH_ARG_COMMAND | NO_EVICT_ARG_COMMAND) => {
+                if args.len() != 3 {
+                    return wrong_arity(&subcommand_name(CLIENT_COMMAND, &subcommand));
+                }
+                let enabled = match args.get(2).unwrap().get_value().to_ascii_uppercase().as_str() {
+                    ON_ARG_COMMAND => true,
+                    OFF_ARG_COMMAND => false,
+                    _ => return RespResponse::Error(SYNTAX_ERR_STR.to_string()),
+                };
+                if toggle == NO_TOUCH_ARG_COMMAND {
+                    self.no_touch = enabled;
+                }
+                RespResponse::SimpleString(OK_STR.to_string())
+            }
+            _ => unknown_subcommand(CLIENT_COMMAND, &subcommand),
+        }
+    }
+
+    /// Handles the "HELLO" command, the handshake modern clients start a connection with.
+    ///
+    /// `HELLO [protover [AUTH username password] [SETNAME name]]` replies with a description of the
+    /// server and the connection. `protover` switches the connection to RESP2 or RESP3 once the options
+    /// were applied; leaving it out keeps the current protocol. Any other version is refused with `NOPROTO`.
+    /// Without `requirepass`, `AUTH` succeeds for the `default` user with any password, as in Redis.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - A slice of `RespResponse` arguments.
+    ///
+    /// # Returns
+    ///
+    /// Returns the server description as a map, in the protocol now in use, or an error for an unsupported
+    /// protocol, a syntax error, wrong credentials or an invalid name.
+    fn handle_hello_command(&mut self, args: &[RespResponse]) -> RespResponse {
+        let protocol = match args.get(1).map(|version| version.get_value().parse::<i64>()) {
+            None => self.protocol,
+            Some(Ok(version @ (RESP2_PROTOCOL | RESP3_PROTOCOL))) => version,
+            Some(Ok(_)) => return RespResponse::Error(NOPROTO_ERR_STR.to_string()),
+            Some(Err(_)) => return RespResponse::Error(PROTOCOL_VERSION_ERR_STR.to_string()),
         };
 
-        // Execute the matched command and return the result.
-        prepared_command.execute()
+        let mut user = None;
+        let mut name = None;
+        let mut pos = 2;
+        while pos < args.len() {
+            let option = args[pos].get_value();
+            let remaining = args.len() - pos - 1;
+            match option.to_ascii_uppercase().as_str() {
+                AUTH_ARG_COMMAND if remaining >= 2 => {
+                    user = Some(args[pos + 1].get_bytes());  // The password is not checked, since no password is configured.
+                    pos += 3;
+                }
+                SETNAME_ARG_COMMAND if remaining >= 1 => {
+                    name = Some(args[pos + 1].get_bytes());
+                    pos += 2;
+                }
+                _ => return RespResponse::Error(format!("ERR Syntax error in HELLO option '{}'", quoted_arg(&option))),
+            }
+        }
+
+        if user.is_some_and(|user| user != DEFAULT_USER.as_bytes()) {
+            return RespResponse::Error(WRONGPASS_ERR_STR.to_string());
+        }
+        if let Some(name) = name {
+            if let Err(error) = self.set_name(name) {
+                return error;
+            }
+        }
+        self.protocol = protocol;
+
+        let field = |name: &str| RespResponse::BulkString(name.as_bytes().to_vec());
+        RespResponse::Map(Arc::new(vec![
+            (field("server"), field("redis")),
+            (field("version"), field(env!("CARGO_PKG_VERSION"))),
+            (field("proto"), RespResponse::Integer(self.protocol)),
+            (field("id"), RespResponse::Integer(self.id as i64)),
+            (field("mode"), field("standalone")),
+            (field("role"), field("master")),
+            (field("modules"), RespResponse::RespArray(Arc::new(Vec::new()))),
+        ]))
+    }
+
+    /// Names the connection, as done by `CLIENT SETNAME` and `HELLO SETNAME`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The new name; an empty name removes the current one.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(())` if the name was set, or an error reply if it contains spaces, newlines or other
+    /// characters outside printable ASCII.
+    fn set_name(&mut self, name: Vec<u8>) -> Result<(), RespResponse> {
+        if !name.iter().all(u8::is_ascii_graphic) {
+            return Err(RespResponse::Error(CLIENT_NAME_ERR_STR.to_string()));
+        }
+        self.name = name;
+        Ok(())
+    }
+
+    /// Remembers a command as the connection's most recent one, for `CLIENT INFO`.
+    ///
+    /// Subcommands of container commands such as CLIENT and CONFIG are recorded as `command|subcommand`, as in Redis.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command name as received from the client.
+    /// * `args` - The arguments of the command, starting with the command name.
+    fn record_command(&mut self, command: &str, args: &[RespResponse]) {
+        self.last_interaction = Instant::now();
+        self.last_command = match args.get(1) {
+            Some(subcommand) if CONTAINER_COMMANDS.contains(&command.to_ascii_uppercase().as_str()) => {
+                subcommand_name(command, &subcommand.get_value())
+            }
+            _ => command.to_ascii_lowercase(),
+        };
+    }
+
+    /// Describes the connection in the format of a `CLIENT LIST` entry, as returned by `CLIENT INFO`.
+    ///
+    /// `addr` is empty without a socket. The fields this server has no equivalent for are reported with their defaults: a single database
+    /// (`db=0`), no special flags (`flags=N`, since a connection in MONITOR mode cannot run CLIENT),
+    /// no Pub/Sub subscriptions and no transaction (`multi=-1`).
+    ///
+    /// # Returns
+    ///
+    /// Returns a single line of space-separated `field=value` pairs, terminated by a newline.
+    fn client_info(&self) -> String {
+        format!(
+            "id={} addr={} name={} age={} idle={} flags=N db=0 sub=0 psub=0 multi=-1 cmd={} user={} resp={}\n",
+            self.id,
+            self.addr.map(|addr| addr.to_string()).unwrap_or_default(),
+            String::from_utf8_lossy(&self.name),
+            self.created.elapsed().as_secs(),
+            self.last_interaction.elapsed().as_secs(),
+            self.last_command,
+            DEFAULT_USER,
+            self.protocol,
+        )
+    }
+}
+
+/// Handles a client's command by mapping it to a known command and executing it.
+///
+/// Commands whose number of arguments does not satisfy their arity in the command table are rejected
+/// with the "wrong number of arguments" error before their handler runs.
+///
+/// This is the dispatch logic shared by `CommandHandler` and `execute_command`; it does not touch any socket.
+///
+/// # Arguments
+///
+/// * `command` - The command string received from the client.
+/// * `args` - The arguments associated with the command as a slice of `RespResponse`.
+/// * `db` - A reference to the shared database.
+/// * `args_cli` - A reference to the command-line arguments.
+/// * `state` - A reference to the shared server state.
+/// * `client_addr` - The address of the client that sent the command, if any (recorded in the slow log).
+/// * `no_touch` - Whether reads should leave the keys' access metadata untouched (CLIENT NO-TOUCH).
+///
+/// # Returns
+///
+/// Returns the response to the command as a `RespResponse`, or an error if the command failed.
+pub fn handle_command(
+    command: &str,
+    args: &[RespResponse],
+    db: &Db,
+    args_cli: &ArgsCli,
+    state: &SharedState,
+    client_addr: Option<&SocketAddr>,
+    no_touch: bool,
+) -> Result<RespResponse, anyhow::Error> {
+    // Convert the command to uppercase for case-insensitive matching.
+    let command_name = command.to_ascii_uppercase();
+
+    // Reject a wrong number of arguments before dispatching, so handlers can rely on the table's arity.
+    let spec = lookup_command(&command_name);
+    if spec.is_some_and(|spec| !spec.accepts_argc(args.len())) {
+        return Ok(wrong_arity(&command_name));
+    }
+
+    // Match the command name to a known command, creating a `Command` object.
+    let prepared_command: Command = match command_name.as_str() {
+        PING_COMMAND => Command::Ping,
+        ECHO_COMMAND => Command::Echo(args),
+        SET_COMMAND => Command::Set(args, db, state),
+        GET_COMMAND => Command::Get(args, db, state, no_touch),
+        CONFIG_COMMAND => Command::ConfigGet(args, args_cli),
+        KEYS_COMMAND => Command::Keys(args, db, args_cli),
+        GETEX_COMMAND => Command::GetEx(args, db, state, no_touch),
+        GETDEL_COMMAND => Command::GetDel(args, db, state),
+        APPEND_COMMAND => Command::Append(args, db, state),
+        INCR_COMMAND => Command::Incr(args, db, state),
+        DECR_COMMAND => Command::Decr(args, db, state),
+        INCRBY_COMMAND => Command::IncrBy(args, db, state),
+        DECRBY_COMMAND => Command::DecrBy(args, db, state),
+        OBJECT_COMMAND => Command::Object(args, db, args_cli),
+        DEBUG_COMMAND => Command::Debug(args, db, state),
+        EXPIRETIME_COMMAND => Command::ExpireTime(args, db),
+        PEXPIRETIME_COMMAND => Command::PExpireTime(args, db),
+        LOLWUT_COMMAND => Command::Lolwut(args),
+        SETBIT_COMMAND => Command::SetBit(args, db, state),
+        SETRANGE_COMMAND => Command::SetRange(args, db, args_cli, state),
+        GETBIT_COMMAND => Command::GetBit(args, db, state, no_touch),
+        BITCOUNT_COMMAND => Command::BitCount(args, db, state, no_touch),
+        BITPOS_COMMAND => Command::BitPos(args, db, state, no_touch),
+        BITOP_COMMAND => Command::BitOp(args, db, state),
+        SAVE_COMMAND => Command::Save(db, args_cli, state),
+        BGSAVE_COMMAND => Command::BgSave(db, args_cli, state),
+        FLUSHALL_COMMAND => Command::FlushAll(args, db, state),
+        DBSIZE_COMMAND => Command::DbSize(db),
+        READONLY_COMMAND | READWRITE_COMMAND | ASKING_COMMAND => Command::ClusterNoOp,
+        CLUSTER_COMMAND => Command::Cluster(args, state),
+        TIME_COMMAND => Command::Time,
+        MGET_COMMAND => Command::MGet(args, db, state, no_touch),
+        MSET_COMMAND => Command::MSet(args, db, state),
+        DEL_COMMAND => Command::Del(args, db, state),
+        EXISTS_COMMAND => Command::Exists(args, db),
+        COMMAND_COMMAND => Command::Introspect(args),
+        INFO_COMMAND => Command::Info(args, db, state),
+        DUMP_COMMAND => Command::Dump(args, db),
+        RESTORE_COMMAND => Command::Restore(args, db, args_cli, state),
+        SLOWLOG_COMMAND => Command::SlowLog(args, state),
+        LATENCY_COMMAND => Command::Latency(args, state),
+        LASTSAVE_COMMAND => Command::LastSave(state),
+        EVAL_COMMAND | EVALSHA_COMMAND | EVAL_RO_COMMAND | EVALSHA_RO_COMMAND
+            | FCALL_COMMAND | FCALL_RO_COMMAND | FUNCTION_COMMAND | SCRIPT_COMMAND => Command::Unsupported,
+        _ => Command::Unknown(command),
+    };
+
+    // Make room for the write before it allocates, if a memory limit is configured.
+    // Commands that may grow memory usage are refused if the limit cannot be restored.
+    let is_write = spec.is_some_and(CommandSpec::is_write);
+    if is_write && !evict_if_needed(db, args_cli) && spec.is_some_and(CommandSpec::is_denyoom) {
+        return Ok(RespResponse::Error(OOM_ERR_STR.to_string()));
+    }
+
+    // Execute the matched command, timing it for the slow log and the latency monitor. Write commands
+    // count the keys they change towards the next automatic save themselves.
+    let started = Instant::now();
+    let response = prepared_command.execute()?;
+    let elapsed = started.elapsed();
+    state.slowlog.lock().unwrap().record(args, elapsed, client_addr, args_cli);
+    state.latency.lock().unwrap().record(LATENCY_EVENT_COMMAND, elapsed, args_cli);
+    Ok(response)
+}
+
+
+/// Executes the RESP request(s) in `input` without a client connection and returns the serialized replies.
+///
+/// Every complete command in `input` is parsed, dispatched through `handle_command` and its reply appended
+/// to the output, so request→reply behavior can be checked byte-for-byte without sockets or a runtime.
+/// Commands may be RESP arrays or the more readable inline form, which is turned into the same array a
+/// client would send; the last inline command may omit its line terminator. A trailing incomplete RESP
+/// array is ignored, and a protocol error is answered with an error reply that ends processing, as it
+/// would close a real connection. Each call acts as a new connection (whose state CLIENT and HELLO see
+/// and change) against a fresh server state.
+///
+/// # Arguments
+///
+/// * `db` - A reference to the shared database.
+/// * `args_cli` - A reference to the command-line arguments.
+/// * `input` - The raw bytes sent by a client: RESP arrays, inline commands, or a mix of both.
+///
+/// # Returns
+///
+/// Returns the serialized replies, in order.
+///
+/// # Examples
+///
+/// ```
+/// let db: Db = Arc::new(Mutex::new(Keyspace::new()));
+/// let reply = execute_command(&db, &args_cli, b"*1\r\n$4\r\nPING\r\n");
+/// assert_eq!(reply, b"+PONG\r\n");
+///
+/// let reply = execute_command(&db, &args_cli, b"SET foo \"hello world\"\r\nGET foo");
+/// assert_eq!(reply, b"+OK\r\n$11\r\nhello world\r\n");
+/// ```
+#[cfg_attr(not(test), allow(dead_code))]  // Only the tests drive it; the server goes through `CommandHandler`.
+pub fn execute_command(db: &Db, args_cli: &ArgsCli, input: &[u8]) -> Vec<u8> {
+    let state = ServerState::new_shared();
+    let mut conn = ConnState::new(&state, None);
+    let mut output = Vec::new();
+    let mut processed = 0;
+
+    while processed < input.len() {
+        let parsed = match RequestParser::default().parse(&input[processed..], args_cli.proto_max_bulk_len, args_cli.proto_max_inline_len) {
+            Err(e) if matches!(e.downcast_ref::<ParseError>(), Some(ParseError::Incomplete)) && input[processed] != ASTERISK_ as u8 => {
+                // The last inline command may omit its line terminator.
+                let terminated = [&input[processed..], CRLF.as_bytes()].concat();
+                RequestParser::default().parse(&terminated, args_cli.proto_max_bulk_len, args_cli.proto_max_inline_len)
+                    .map(|(message, _)| (message, input.len() - processed))
+            }
+            parsed => parsed,
+        };
+        let message = match parsed {
+            Ok((message, consumed)) => {
+                processed += consumed;
+                message
+            }
+            Err(e) if matches!(e.downcast_ref::<ParseError>(), Some(ParseError::Incomplete)) => break,
+            Err(e) => {
+                RespResponse::Error(format!("ERR {e}")).serialize_for(conn.protocol, &mut output);
+                break;
+            }
+        };
+        if is_empty_request(&message) {
+            continue;
+        }
+
+        let response = message.get_command_and_args().and_then(|(command, args)| {
+            conn.record_command(&command, &args);
+            match conn.handle_connection_command(&command, &args) {
+                Some(response) => Ok(response),
+                None => handle_command(&command, &args, db, args_cli, &state, None, conn.no_touch),
+            }
+        });
+        match response {
+            Ok(response) => response.serialize_for(conn.protocol, &mut output),
+            Err(e) => {
+                RespResponse::Error(format!("ERR {e}")).serialize_for(conn.protocol, &mut output);
+                if matches!(e.downcast_ref::<ParseError>(), Some(ParseError::Protocol(_))) {
+                    break;
+                }
+            }
+        }
+    }
+    output
+}
+
+/// Checks whether a parsed request holds no command at all, like a blank inline line or `*0\r\n`.
+///
+/// Such requests are skipped without a reply, as Redis does.
+///
+/// # Arguments
+///
+/// * `message` - The parsed request.
+///
+/// # Returns
+///
+/// Returns `true` if the request is an empty array.
+fn is_empty_request(message: &RespResponse) -> bool {
+    matches!(message, RespResponse::RespArray(args) if args.is_empty())
+}
+
+/// Checks whether an error only means that the client went away, e.g. it reset the connection or closed
+/// it while a reply was still being written.
+///
+/// # Arguments
+///
+/// * `error` - The error that ended the connection.
+///
+/// # Returns
+///
+/// Returns `true` if the error is an I/O error caused by the peer disconnecting.
+fn is_disconnect(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<io::Error>().is_some_and(|e| matches!(
+        e.kind(),
+        ErrorKind::BrokenPipe | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::UnexpectedEof | ErrorKind::WouldBlock
+    ))
+}
+
+#[cfg(test)]
+pub mod tests {
+        use std::sync::Mutex;
+    use std::time::Duration;
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::server::arg_handler::ArgHandler;
+    use crate::server::common_variables::Keyspace;
+
+    /// Builds an empty database and the arguments of a server started with `args`.
+    pub fn setup(args: &[&str]) -> (Db, ArgsCli) {
+        let args_cli = ArgHandler::parse_from(std::iter::once("redis-rust").chain(args.iter().copied()));
+        (Arc::new(Mutex::new(Keyspace::new())), Arc::new(args_cli))
+    }
+
+    #[test]
+    fn executes_array_and_inline_requests() {
+        let (db, args_cli) = setup(&[]);
+        assert_eq!(execute_command(&db, &args_cli, b"*1\r\n$4\r\nPING\r\n"), b"+PONG\r\n");
+        assert_eq!(execute_command(&db, &args_cli, b"SET foo \"hello world\"\r\nGET foo"), b"+OK\r\n$11\r\nhello world\r\n");
+    }
+
+    #[test]
+    fn oversized_bulk_lengths_end_the_connection() {
+        let (db, args_cli) = setup(&["--proto-max-bulk-len", "1024"]);
+        let reply = execute_command(&db, &args_cli, b"*2\r\n$3\r\nGET\r\n$999999999999\r\n*1\r\n$4\r\nPING\r\n");
+        assert_eq!(reply, b"-ERR Protocol error: invalid bulk length\r\n");  // Nothing after the error is answered.
+    }
+
+    #[test]
+    fn unterminated_inline_lines_end_the_connection() {
+        let (db, args_cli) = setup(&["--proto-max-inline-len", "1024"]);
+        let reply = execute_command(&db, &args_cli, &[b"PING\r\n".as_slice(), &[b'a'; 2048]].concat());
+        assert_eq!(reply, b"+PONG\r\n-ERR Protocol error: too big inline request\r\n");
+    }
+
+    /// Returns the value of `field` in the CLIENT INFO line that `request` ends with.
+    fn client_info_field(db: &Db, args_cli: &ArgsCli, request: &[u8], field: &str) -> String {
+        let reply = String::from_utf8(execute_command(db, args_cli, request)).unwrap();
+        let info = reply.rsplit("\r\n").nth(1).unwrap().trim_end();  // The payload of the last bulk string.
+        info.split(' ').find_map(|pair| pair.strip_prefix(&format!("{}=", field))).unwrap().to_string()
+    }
+
+    #[test]
+    fn client_info_describes_the_calling_connection() {
+        let (db, args_cli) = setup(&[]);
+        let request = b"CLIENT SETNAME worker-1\r\nGET k\r\nCLIENT INFO\r\n";
+
+        assert_eq!(client_info_field(&db, &args_cli, request, "name"), "worker-1");
+        assert_eq!(client_info_field(&db, &args_cli, request, "id"), "1");
+        assert_eq!(client_info_field(&db, &args_cli, request, "db"), "0");
+        assert_eq!(client_info_field(&db, &args_cli, request, "multi"), "-1");
+        assert_eq!(client_info_field(&db, &args_cli, request, "cmd"), "client|info");
+        let reply = execute_command(&db, &args_cli, request);
+        assert!(reply.starts_with(b"+OK\r\n$-1\r\n$") && reply.ends_with(b"\n\r\n"));  // One line in a bulk string.
+    }
+
+    #[test]
+    fn each_connection_reports_its_own_protocol() {
+        let (db, args_cli) = setup(&[]);
+
+        let resp2_client = b"*2\r\n$6\r\nCLIENT\r\n$4\r\nINFO\r\n";
+        assert_eq!(client_info_field(&db, &args_cli, resp2_client, "resp"), "2");
+        assert_eq!(client_info_field(&db, &args_cli, resp2_client, "cmd"), "client|info");
+
+        let resp3_client = b"*2\r\n$5\r\nHELLO\r\n$1\r\n3\r\n*2\r\n$6\r\nCLIENT\r\n$4\r\nINFO\r\n";
+        assert_eq!(client_info_field(&db, &args_cli, resp3_client, "resp"), "3");
+
+        let inline_client = b"CLIENT SETNAME inline\r\nCLIENT INFO\r\n";
+        assert_eq!(client_info_field(&db, &args_cli, inline_client, "resp"), "2");
+        assert_eq!(client_info_field(&db, &args_cli, inline_client, "name"), "inline");
+        assert_eq!(client_info_field(&db, &args_cli, b"CLIENT INFO", "name"), "");  // A new connection has no name.
+    }
+
+    /// Returns the HELLO reply describing a connection with the given id that speaks `protocol`.
+    fn hello_reply(protocol: i64, id: u64) -> Vec<u8> {
+        let header = if protocol == RESP3_PROTOCOL { "%7".to_string() } else { "*14".to_string() };
+        let version = env!("CARGO_PKG_VERSION");
+        format!(
+            "{}\r\n$6\r\nserver\r\n$5\r\nredis\r\n$7\r\nversion\r\n${}\r\n{}\r\n$5\r\nproto\r\n:{}\r\n$2\r\nid\r\n:{}\r\n\
+             $4\r\nmode\r\n$10\r\nstandalone\r\n$4\r\nrole\r\n$6\r\nmaster\r\n$7\r\nmodules\r\n*0\r\n",
+            header, version.len(), version, protocol, id,
+        ).into_bytes()
+    }
+
+    #[test]
+    fn hello_without_a_version_keeps_the_protocol() {
+        let (db, args_cli) = setup(&[]);
+        assert_eq!(execute_command(&db, &args_cli, b"HELLO"), hello_reply(RESP2_PROTOCOL, 1));
+        assert_eq!(execute_command(&db, &args_cli, b"HELLO 3\r\nHELLO"), [hello_reply(RESP3_PROTOCOL, 1), hello_reply(RESP3_PROTOCOL, 1)].concat());
+    }
+
+    #[test]
+    fn hello_switches_the_protocol_of_later_replies() {
+        let (db, args_cli) = setup(&[]);
+
+        let reply = execute_command(&db, &args_cli, b"HELLO 3\r\nGET missing\r\nCONFIG GET maxclients\r\nHELLO 2\r\nGET missing");
+        let expected = [
+            hello_reply(RESP3_PROTOCOL, 1),
+            b"_\r\n%1\r\n$10\r\nmaxclients\r\n$5\r\n10000\r\n".to_vec(),
+            hello_reply(RESP2_PROTOCOL, 1),
+            b"$-1\r\n".to_vec(),
+        ].concat();
+        assert_eq!(reply, expected);
+
+        let reply = execute_command(&db, &args_cli, b"HELLO 3 AUTH default secret SETNAME app\r\nCLIENT GETNAME\r\nRESET\r\nCLIENT GETNAME");
+        assert!(reply.ends_with(b"$3\r\napp\r\n+RESET\r\n$-1\r\n"));  // RESET goes back to RESP2 and drops the name.
+    }
+
+    #[test]
+    fn hello_refuses_invalid_versions_and_options() {
+        let (db, args_cli) = setup(&[]);
+        let run = |input: &str| String::from_utf8(execute_command(&db, &args_cli, input.as_bytes())).unwrap();
+
+        assert_eq!(run("HELLO 4\r\nHELLO 1"), format!("-{0}\r\n-{0}\r\n", NOPROTO_ERR_STR));
+        assert_eq!(run("HELLO three"), format!("-{}\r\n", PROTOCOL_VERSION_ERR_STR));
+        assert_eq!(run("HELLO 3 AUTH someone secret"), format!("-{}\r\n", WRONGPASS_ERR_STR));
+        assert_eq!(run("HELLO 3 SETNAME"), "-ERR Syntax error in HELLO option 'SETNAME'\r\n");
+        assert_eq!(run("*3\r\n$5\r\nHELLO\r\n$1\r\n3\r\n$6\r\nX\r\n+OK\r\n"), "-ERR Syntax error in HELLO option 'X  +OK'\r\n");
+
+        // A refused HELLO leaves the protocol alone.
+        assert_eq!(client_info_field(&db, &args_cli, b"HELLO 3 AUTH someone secret\r\nCLIENT INFO", "resp"), "2");
+    }
+
+    #[test]
+    fn background_work_does_not_need_a_runtime() {
+        let dir = std::env::temp_dir().join(format!("redis-rust-{}-bgsave", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (db, args_cli) = setup(&["--dir", dir.to_str().unwrap()]);
+
+        assert_eq!(execute_command(&db, &args_cli, b"SET k v\r\nBGSAVE"), b"+OK\r\n+Background saving started\r\n");
+        let rdb_file = args_cli.rdb_file_path();
+        for _ in 0..100 {
+            if rdb_file.exists() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(rdb_file.exists());
+
+        assert_eq!(execute_command(&db, &args_cli, b"FLUSHALL ASYNC\r\nDBSIZE"), b"+OK\r\n:0\r\n");
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,153 @@
+/// `CommandSpec` describes a supported command the way `COMMAND INFO` and `COMMAND DOCS` report it.
+#[derive(Debug)]
+pub struct CommandSpec {
+    pub name: &'static str,            // The lowercase command name.
+    pub arity: i64,                    // The exact number of arguments including the name, or `-N` for "at least N".
+    pub flags: &'static [&'static str], // Command flags such as `write`, `readonly` or `fast`.
+    pub first_key: i64,                // Position of the first key argument (0 if the command takes no keys).
+    pub last_key: i64,                 // Position of the last key argument (-1 means the last argument).
+    pub step: i64,                     // Distance between key arguments (2 for key-value pairs).
+    pub summary: &'static str,         // One-line description reported by `COMMAND DOCS`.
+}
+
+/// The table of all commands supported by the server, in alphabetical order.
+pub const COMMAND_TABLE: &[CommandSpec] = &[
+    CommandSpec { name: "append", arity: 3, flags: &["write", "denyoom", "fast"], first_key: 1, last_key: 1, step: 1, summary: "Appends a string to the value of a key. Creates the key if it doesn't exist." },
+    CommandSpec { name: "asking", arity: 1, flags: &["fast"], first_key: 0, last_key: 0, step: 0, summary: "Signals that a cluster client is following an -ASK redirect." },
+    CommandSpec { name: "bgsave", arity: -1, flags: &["admin", "noscript"], first_key: 0, last_key: 0, step: 0, summary: "Asynchronously saves the database to disk." },
+    CommandSpec { name: "bitcount", arity: -2, flags: &["readonly"], first_key: 1, last_key: 1, step: 1, summary: "Counts the number of set bits (population counting) in a string." },
+    CommandSpec { name: "bitop", arity: -4, flags: &["write", "denyoom"], first_key: 2, last_key: -1, step: 1, summary: "Performs bitwise operations on multiple strings, and stores the result." },
+    CommandSpec { name: "bitpos", arity: -3, flags: &["readonly"], first_key: 1, last_key: 1, step: 1, summary: "Finds the first set (1) or clear (0) bit in a string." },
+    CommandSpec { name: "client", arity: -2, flags: &[], first_key: 0, last_key: 0, step: 0, summary: "A container for client connection commands." },
+    CommandSpec { name: "cluster", arity: -2, flags: &[], first_key: 0, last_key: 0, step: 0, summary: "A container for Redis Cluster commands." },
+    CommandSpec { name: "command", arity: -1, flags: &["loading", "stale"], first_key: 0, last_key: 0, step: 0, summary: "Returns detailed information about all commands." },
+    CommandSpec { name: "config", arity: -2, flags: &[], first_key: 0, last_key: 0, step: 0, summary: "A container for server configuration commands." },
+    CommandSpec { name: "dbsize", arity: 1, flags: &["readonly", "fast"], first_key: 0, last_key: 0, step: 0, summary: "Returns the number of keys in the database." },
+    CommandSpec { name: "debug", arity: -2, flags: &["admin", "noscript", "loading", "stale"], first_key: 0, last_key: 0, step: 0, summary: "A container for debugging commands." },
+    CommandSpec { name: "decr", arity: 2, flags: &["write", "denyoom", "fast"], first_key: 1, last_key: 1, step: 1, summary: "Decrements the integer value of a key by one. Uses 0 as initial value if the key doesn't exist." },
+    CommandSpec { name: "decrby", arity: 3, flags: &["write", "denyoom", "fast"], first_key: 1, last_key: 1, step: 1, summary: "Decrements a number from the integer value of a key. Uses 0 as initial value if the key doesn't exist." },
+    CommandSpec { name: "del", arity: -2, flags: &["write"], first_key: 1, last_key: -1, step: 1, summary: "Deletes one or more keys." },
+    CommandSpec { name: "dump", arity: 2, flags: &["readonly"], first_key: 1, last_key: 1, step: 1, summary: "Returns a serialized representation of the value stored at a key." },
+    CommandSpec { name: "echo", arity: 2, flags: &["fast"], first_key: 0, last_key: 0, step: 0, summary: "Returns the given string." },
+    CommandSpec { name: "exists", arity: -2, flags: &["readonly", "fast"], first_key: 1, last_key: -1, step: 1, summary: "Determines whether one or more keys exist." },
+    CommandSpec { name: "expiretime", arity: 2, flags: &["readonly", "fast"], first_key: 1, last_key: 1, step: 1, summary: "Returns the expiration time of a key as a Unix timestamp." },
+    CommandSpec { name: "flushall", arity: -1, flags: &["write"], first_key: 0, last_key: 0, step: 0, summary: "Removes all keys from the database." },
+    CommandSpec { name: "get", arity: 2, flags: &["readonly", "fast"], first_key: 1, last_key: 1, step: 1, summary: "Returns the string value of a key." },
+    CommandSpec { name: "getbit", arity: 3, flags: &["readonly", "fast"], first_key: 1, last_key: 1, step: 1, summary: "Returns a bit value by offset." },
+    CommandSpec { name: "getdel", arity: 2, flags: &["write", "fast"], first_key: 1, last_key: 1, step: 1, summary: "Returns the string value of a key after deleting the key." },
+    CommandSpec { name: "getex", arity: -2, flags: &["write", "fast"], first_key: 1, last_key: 1, step: 1, summary: "Returns the string value of a key after setting its expiration time." },
+    CommandSpec { name: "hello", arity: -1, flags: &["noscript", "loading", "stale", "fast"], first_key: 0, last_key: 0, step: 0, summary: "Handshakes with the Redis server." },
+    CommandSpec { name: "incr", arity: 2, flags: &["write", "denyoom", "fast"], first_key: 1, last_key: 1, step: 1, summary: "Increments the integer value of a key by one. Uses 0 as initial value if the key doesn't exist." },
+    CommandSpec { name: "incrby", arity: 3, flags: &["write", "denyoom", "fast"], first_key: 1, last_key: 1, step: 1, summary: "Increments the integer value of a key by a number. Uses 0 as initial value if the key doesn't exist." },
+    CommandSpec { name: "info", arity: -1, flags: &["loading", "stale"], first_key: 0, last_key: 0, step: 0, summary: "Returns information and statistics about the server." },
+    CommandSpec { name: "keys", arity: 2, flags: &["readonly"], first_key: 0, last_key: 0, step: 0, summary: "Returns all key names that match a pattern." },
+    CommandSpec { name: "lastsave", arity: 1, flags: &["loading", "stale", "fast"], first_key: 0, last_key: 0, step: 0, summary: "Returns the Unix timestamp of the last successful save to disk." },
+    CommandSpec { name: "latency", arity: -2, flags: &["admin", "noscript", "loading", "stale"], first_key: 0, last_key: 0, step: 0, summary: "A container for latency diagnostics commands." },
+    CommandSpec { name: "lolwut", arity: -1, flags: &["readonly", "fast"], first_key: 0, last_key: 0, step: 0, summary: "Displays computer art and the Redis version." },
+    CommandSpec { name: "mget", arity: -2, flags: &["readonly", "fast"], first_key: 1, last_key: -1, step: 1, summary: "Atomically returns the string values of one or more keys." },
+    CommandSpec { name: "monitor", arity: 1, flags: &["admin", "noscript", "loading", "stale"], first_key: 0, last_key: 0, step: 0, summary: "Listens for all requests received by the server in real-time." },
+    CommandSpec { name: "mset", arity: -3, flags: &["write", "denyoom"], first_key: 1, last_key: -1, step: 2, summary: "Atomically creates or modifies the string values of one or more keys." },
+    CommandSpec { name: "object", arity: -2, flags: &[], first_key: 0, last_key: 0, step: 0, summary: "A container for object introspection commands." },
+    CommandSpec { name: "pexpiretime", arity: 2, flags: &["readonly", "fast"], first_key: 1, last_key: 1, step: 1, summary: "Returns the expiration time of a key as a Unix milliseconds timestamp." },
+    CommandSpec { name: "ping", arity: -1, flags: &["fast"], first_key: 0, last_key: 0, step: 0, summary: "Returns the server's liveliness response." },
+    CommandSpec { name: "quit", arity: -1, flags: &["noscript", "loading", "stale", "fast"], first_key: 0, last_key: 0, step: 0, summary: "Closes the connection." },
+    CommandSpec { name: "readonly", arity: 1, flags: &["loading", "stale", "fast"], first_key: 0, last_key: 0, step: 0, summary: "Enables read-only queries for a connection to a Redis Cluster replica node." },
+    CommandSpec { name: "readwrite", arity: 1, flags: &["loading", "stale", "fast"], first_key: 0, last_key: 0, step: 0, summary: "Enables read-write queries for a connection to a Redis Cluster replica node." },
+    CommandSpec { name: "reset", arity: 1, flags: &["noscript", "loading", "stale", "fast"], first_key: 0, last_key: 0, step: 0, summary: "Resets the connection." },
+    CommandSpec { name: "restore", arity: -4, flags: &["write", "denyoom"], first_key: 1, last_key: 1, step: 1, summary: "Creates a key from the serialized representation of a value." },
+    CommandSpec { name: "save", arity: 1, flags: &["admin", "noscript"], first_key: 0, last_key: 0, step: 0, summary: "Synchronously saves the database to disk." },
+    CommandSpec { name: "set", arity: -3, flags: &["write", "denyoom"], first_key: 1, last_key: 1, step: 1, summary: "Sets the string value of a key, ignoring its type. The key is created if it doesn't exist." },
+    CommandSpec { name: "setbit", arity: 4, flags: &["write", "denyoom"], first_key: 1, last_key: 1, step: 1, summary: "Sets or clears the bit at offset of the string value. Creates the key if it doesn't exist." },
+    CommandSpec { name: "setrange", arity: 4, flags: &["write", "denyoom"], first_key: 1, last_key: 1, step: 1, summary: "Overwrites a part of a string value with another by an offset. Creates the key if it doesn't exist." },
+    CommandSpec { name: "slowlog", arity: -2, flags: &["admin", "loading", "stale"], first_key: 0, last_key: 0, step: 0, summary: "A container for slow log commands." },
+    CommandSpec { name: "time", arity: 1, flags: &["loading", "stale", "fast"], first_key: 0, last_key: 0, step: 0, summary: "Returns the server time." },
+];
+
+/// Looks up a command in the command table.
+///
+/// # Arguments
+///
+/// * `name` - The command name, in any case.
+///
+/// # Returns
+///
+/// Returns the `CommandSpec` of the command, or `None` if the command is not supported.
+///
+/// # Examples
+///
+/// ```
+/// let spec = lookup_command("SET").unwrap();
+/// assert_eq!(spec.arity, -3);
+/// ```
+pub fn lookup_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMAND_TABLE.iter().find(|spec| spec.name.eq_ignore_ascii_case(name))
+}
+
+impl CommandSpec {
+    /// Checks whether a number of arguments satisfies the command's arity.
+    ///
+    /// # Arguments
+    ///
+    /// * `argc` - The number of arguments, including the command name.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if `argc` matches a fixed arity, or is at least `N` for an arity of `-N`.
+    pub fn accepts_argc(&self, argc: usize) -> bool {
+        if self.arity >= 0 {
+            argc as i64 == self.arity
+        } else {
+            argc as i64 >= -self.arity
+        }
+    }
+
+    /// Checks whether the command writes to the keyspace, i.e. carries the `write` flag.
+    ///
+    /// Write commands trigger eviction before they run and count towards the next automatic save, so a
+    /// new write command only needs the flag in `COMMAND_TABLE`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` for write commands.
+    pub fn is_write(&self) -> bool {
+        self.flags.contains(&"write")
+    }
+
+    /// Checks whether the command may grow memory usage, i.e. carries the `denyoom` flag.
+    ///
+    /// Such commands are rejected while memory usage stays over `maxmemory`; reads and deletions are not.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` for commands that must be refused on out-of-memory.
+    pub fn is_denyoom(&self) -> bool {
+        self.flags.contains(&"denyoom")
+    }
+
+    /// Returns the positions of the key arguments in a full command, based on `first_key`, `last_key` and `step`.
+    ///
+    /// # Arguments
+    ///
+    /// * `argc` - The number of arguments, including the command name.
+    ///
+    /// # Returns
+    ///
+    /// Returns the indexes of the key arguments; empty if the command takes no keys.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let spec = lookup_command("MSET").unwrap();
+    /// assert_eq!(spec.key_positions(5), vec![1, 3]);
+    /// ```
+    pub fn key_positions(&self, argc: usize) -> Vec<usize> {
+        if self.first_key <= 0 || self.step <= 0 {
+            return Vec::new();
+        }
+        let last_key = if self.last_key < 0 { argc as i64 + self.last_key } else { self.last_key };
+        (self.first_key..=last_key.min(argc as i64 - 1))
+            .step_by(self.step as usize)
+            .map(|position| position as usize)
+            .collect()
+    }
+}
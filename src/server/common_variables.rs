@@ -1,13 +1,79 @@
-use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
+use indexmap::IndexMap;
+
 use crate::server::redis_item::RedisItem;
 
 //Networking
-pub const SERVER_IP_AND_PORT: &str = "127.0.0.1:6379";
+pub const DEFAULT_BIND: &str = "127.0.0.1";
+pub const DEFAULT_PORT: u16 = 6379;
+pub const DEFAULT_TCP_KEEPALIVE: u64 = 300;
+
+// Protocol
+pub const DEFAULT_PROTO_MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+pub const DEFAULT_PROTO_MAX_INLINE_LEN: usize = 64 * 1024;
+pub const DEFAULT_MAXCLIENTS: u64 = 10000;
+pub const DEFAULT_TCP_BACKLOG: u32 = 511;
+pub const MAX_MULTIBULK_LEN: usize = 1024 * 1024;
+pub const CLIENT_READ_BUFFER_LEN: usize = 16 * 1024;  // Bytes read from a client at a time, like Redis's PROTO_IOBUF_LEN.
+pub const RESP2_PROTOCOL: i64 = 2;  // The protocol every connection starts with.
+pub const RESP3_PROTOCOL: i64 = 3;  // The protocol `HELLO 3` switches to, with maps and a dedicated null.
 
 // Types
-pub type Db = Arc<Mutex<HashMap<String, RedisItem>>>;
+pub type Keyspace = IndexMap<Vec<u8>, RedisItem>;  // Keys in insertion order, so random keys can be drawn by position.
+pub type Db = Arc<Mutex<Keyspace>>;
+
+
+// Expiration
+pub const ACTIVE_EXPIRE_CYCLE_MS: u64 = 100;
+pub const ACTIVE_EXPIRE_CYCLE_BUDGET_MS: u64 = 25;  // At most a quarter of each cycle is spent removing keys, like Redis.
+pub const ACTIVE_EXPIRE_KEYS_PER_LOOP: usize = 20;  // Keys with an expiration checked per batch.
+pub const ACTIVE_EXPIRE_MAX_VISITS_PER_LOOP: usize = ACTIVE_EXPIRE_KEYS_PER_LOOP * 20;  // Keys walked per batch, with or without expiration.
+pub const ACTIVE_EXPIRE_ACCEPTABLE_STALE_PERCENT: usize = 10;  // A cycle stops once at most this share of a batch was expired.
+pub const DBSIZE_EXPIRE_CLEANUP_LIMIT: usize = 20;
+
+
+// Strings
+pub const MAX_BIT_OFFSET: u64 = 512 * 1024 * 1024 * 8 - 1;  // Strings are limited to 512MB.
+
+
+// Encodings
+pub const ENCODING_INT: &str = "int";
+pub const ENCODING_EMBSTR: &str = "embstr";
+pub const ENCODING_RAW: &str = "raw";
+pub const EMBSTR_SIZE_LIMIT: usize = 44;
+pub const LRU_CLOCK_MAX: u64 = (1 << 24) - 1;
+
+// Server
+pub const INFO_SECTION_SERVER: &str = "server";
+pub const INFO_SECTION_PERSISTENCE: &str = "persistence";
+pub const INFO_SECTION_STATS: &str = "stats";
+pub const INFO_SECTION_REPLICATION: &str = "replication";
+pub const INFO_SECTION_KEYSPACE: &str = "keyspace";
+pub const INFO_SECTIONS_ALL: &str = "all";
+pub const INFO_SECTIONS_DEFAULT: &str = "default";
+pub const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+pub const NODE_ID_LEN: usize = 40;
+pub const MONITOR_CHANNEL_CAPACITY: usize = 1024;
+pub const GLOB_MAX_NESTING: usize = 1000;  // Deeper `*` nesting in a glob pattern never matches.
+
+// Slow log
+pub const DEFAULT_SLOWLOG_LOG_SLOWER_THAN: i64 = 10000;
+pub const DEFAULT_SLOWLOG_MAX_LEN: usize = 128;
+pub const SLOWLOG_DEFAULT_GET_COUNT: usize = 10;
+pub const SLOWLOG_ENTRY_MAX_ARGC: usize = 32;
+pub const SLOWLOG_ENTRY_MAX_STRING: usize = 128;
+
+// Latency monitor
+pub const LATENCY_HISTORY_LEN: usize = 160;
+pub const LATENCY_EVENT_COMMAND: &str = "command";
+pub const LATENCY_EVENT_RDB_SAVE: &str = "rdb-save";
+
+// LFU
+pub const LFU_INIT_VAL: u8 = 5;
+pub const LFU_LOG_FACTOR: f64 = 10.0;
+pub const LFU_DECAY_TIME_MINUTES: u64 = 1;
+pub const MAXMEMORY_SAMPLES: usize = 5;  // Keys compared per eviction, like Redis's `maxmemory-samples`.
 
 
 // Command Names
@@ -17,15 +83,261 @@ pub const SET_COMMAND: &str = "SET";
 pub const GET_COMMAND: &str = "GET";
 pub const CONFIG_COMMAND: &str = "CONFIG";
 pub const KEYS_COMMAND: &str = "KEYS";
+pub const GETEX_COMMAND: &str = "GETEX";
+pub const GETDEL_COMMAND: &str = "GETDEL";
+pub const APPEND_COMMAND: &str = "APPEND";
+pub const INCR_COMMAND: &str = "INCR";
+pub const DECR_COMMAND: &str = "DECR";
+pub const INCRBY_COMMAND: &str = "INCRBY";
+pub const DECRBY_COMMAND: &str = "DECRBY";
+pub const OBJECT_COMMAND: &str = "OBJECT";
+pub const DEBUG_COMMAND: &str = "DEBUG";
+pub const EXPIRETIME_COMMAND: &str = "EXPIRETIME";
+pub const PEXPIRETIME_COMMAND: &str = "PEXPIRETIME";
+pub const LOLWUT_COMMAND: &str = "LOLWUT";
+pub const SETBIT_COMMAND: &str = "SETBIT";
+pub const SETRANGE_COMMAND: &str = "SETRANGE";
+pub const GETBIT_COMMAND: &str = "GETBIT";
+pub const BITCOUNT_COMMAND: &str = "BITCOUNT";
+pub const BITPOS_COMMAND: &str = "BITPOS";
+pub const BITOP_COMMAND: &str = "BITOP";
+pub const SAVE_COMMAND: &str = "SAVE";
+pub const BGSAVE_COMMAND: &str = "BGSAVE";
+pub const FLUSHALL_COMMAND: &str = "FLUSHALL";
+pub const DBSIZE_COMMAND: &str = "DBSIZE";
+pub const READONLY_COMMAND: &str = "READONLY";
+pub const READWRITE_COMMAND: &str = "READWRITE";
+pub const ASKING_COMMAND: &str = "ASKING";
+pub const CLUSTER_COMMAND: &str = "CLUSTER";
+pub const TIME_COMMAND: &str = "TIME";
+pub const MGET_COMMAND: &str = "MGET";
+pub const MSET_COMMAND: &str = "MSET";
+pub const DEL_COMMAND: &str = "DEL";
+pub const EXISTS_COMMAND: &str = "EXISTS";
+pub const COMMAND_COMMAND: &str = "COMMAND";
+pub const INFO_COMMAND: &str = "INFO";
+pub const DUMP_COMMAND: &str = "DUMP";
+pub const RESTORE_COMMAND: &str = "RESTORE";
+pub const MONITOR_COMMAND: &str = "MONITOR";
+pub const QUIT_COMMAND: &str = "QUIT";
+pub const RESET_COMMAND: &str = "RESET";
+pub const SLOWLOG_COMMAND: &str = "SLOWLOG";
+pub const LATENCY_COMMAND: &str = "LATENCY";
+pub const LASTSAVE_COMMAND: &str = "LASTSAVE";
+pub const EVAL_COMMAND: &str = "EVAL";
+pub const EVALSHA_COMMAND: &str = "EVALSHA";
+pub const EVAL_RO_COMMAND: &str = "EVAL_RO";
+pub const EVALSHA_RO_COMMAND: &str = "EVALSHA_RO";
+pub const FCALL_COMMAND: &str = "FCALL";
+pub const FCALL_RO_COMMAND: &str = "FCALL_RO";
+pub const FUNCTION_COMMAND: &str = "FUNCTION";
+pub const SCRIPT_COMMAND: &str = "SCRIPT";
+pub const CLIENT_COMMAND: &str = "CLIENT";
+pub const HELLO_COMMAND: &str = "HELLO";
 
 // Command args
 pub const DIR_ARG_COMMAND: &str = "dir";
 pub const DB_FILENAME_ARG_COMMAND: &str = "dbfilename";
+pub const SAVE_CONFIG: &str = "save";
+pub const BIND_CONFIG: &str = "bind";
+pub const PORT_CONFIG: &str = "port";
+pub const CONFIG_CONFIG: &str = "config";
+pub const MAXMEMORY_CONFIG: &str = "maxmemory";
+pub const MAXMEMORY_POLICY_CONFIG: &str = "maxmemory-policy";
+pub const ACTIVE_EXPIRE_JITTER_CONFIG: &str = "active-expire-jitter";
+pub const PROTO_MAX_BULK_LEN_CONFIG: &str = "proto-max-bulk-len";
+pub const PROTO_MAX_INLINE_LEN_CONFIG: &str = "proto-max-inline-len";
+pub const MAXCLIENTS_CONFIG: &str = "maxclients";
+pub const TCP_BACKLOG_CONFIG: &str = "tcp-backlog";
+pub const TCP_KEEPALIVE_CONFIG: &str = "tcp-keepalive";
+pub const SLOWLOG_LOG_SLOWER_THAN_CONFIG: &str = "slowlog-log-slower-than";
+pub const SLOWLOG_MAX_LEN_CONFIG: &str = "slowlog-max-len";
+pub const LATENCY_MONITOR_THRESHOLD_CONFIG: &str = "latency-monitor-threshold";
+pub const REWRITE_ARG_COMMAND: &str = "REWRITE";
 pub const PX_ARG_COMMAND: &str = "PX";
+pub const EX_ARG_COMMAND: &str = "EX";
+pub const EXAT_ARG_COMMAND: &str = "EXAT";
+pub const PXAT_ARG_COMMAND: &str = "PXAT";
+pub const PERSIST_ARG_COMMAND: &str = "PERSIST";
+pub const KEEPTTL_ARG_COMMAND: &str = "KEEPTTL";
+pub const NX_ARG_COMMAND: &str = "NX";
+pub const XX_ARG_COMMAND: &str = "XX";
+pub const FREQ_ARG_COMMAND: &str = "FREQ";
+pub const ENCODING_ARG_COMMAND: &str = "ENCODING";
+pub const OBJECT_ARG_COMMAND: &str = "OBJECT";
+pub const SET_ACTIVE_EXPIRE_ARG_COMMAND: &str = "SET-ACTIVE-EXPIRE";
+pub const VERSION_ARG_COMMAND: &str = "VERSION";
+pub const HELP_ARG_COMMAND: &str = "HELP";
+pub const INFO_ARG_COMMAND: &str = "INFO";
+pub const MYID_ARG_COMMAND: &str = "MYID";
+pub const COUNT_ARG_COMMAND: &str = "COUNT";
+pub const GETKEYS_ARG_COMMAND: &str = "GETKEYS";
+pub const LIST_ARG_COMMAND: &str = "LIST";
+pub const DOCS_ARG_COMMAND: &str = "DOCS";
+pub const CHANGE_REPL_ID_ARG_COMMAND: &str = "CHANGE-REPL-ID";
+pub const ASYNC_ARG_COMMAND: &str = "ASYNC";
+pub const SYNC_ARG_COMMAND: &str = "SYNC";
+pub const LEN_ARG_COMMAND: &str = "LEN";
+pub const SLEEP_ARG_COMMAND: &str = "SLEEP";
+pub const SLEEP_BEFORE_REPLY_ARG_COMMAND: &str = "SLEEP-BEFORE-REPLY";
+pub const ERROR_ARG_COMMAND: &str = "ERROR";
+pub const STRINGMATCH_LEN_ARG_COMMAND: &str = "STRINGMATCH-LEN";
+pub const LATEST_ARG_COMMAND: &str = "LATEST";
+pub const HISTORY_ARG_COMMAND: &str = "HISTORY";
+pub const REPLACE_ARG_COMMAND: &str = "REPLACE";
+pub const ABSTTL_ARG_COMMAND: &str = "ABSTTL";
+pub const AND_ARG_COMMAND: &str = "AND";
+pub const OR_ARG_COMMAND: &str = "OR";
+pub const XOR_ARG_COMMAND: &str = "XOR";
+pub const NOT_ARG_COMMAND: &str = "NOT";
+pub const IDLETIME_ARG_COMMAND: &str = "IDLETIME";
+pub const NO_EVICT_ARG_COMMAND: &str = "NO-EVICT";
+pub const NO_TOUCH_ARG_COMMAND: &str = "NO-TOUCH";
+pub const ON_ARG_COMMAND: &str = "ON";
+pub const OFF_ARG_COMMAND: &str = "OFF";
+pub const ID_ARG_COMMAND: &str = "ID";
+pub const GETNAME_ARG_COMMAND: &str = "GETNAME";
+pub const SETNAME_ARG_COMMAND: &str = "SETNAME";
+pub const AUTH_ARG_COMMAND: &str = "AUTH";
+pub const DEFAULT_USER: &str = "default";
+pub const CONTAINER_COMMANDS: &[&str] = &[CLIENT_COMMAND, CLUSTER_COMMAND, COMMAND_COMMAND, CONFIG_COMMAND, LATENCY_COMMAND, OBJECT_COMMAND, SLOWLOG_COMMAND];  // Commands reported as `command|subcommand`.
 
 // Responses
 pub const OK_STR: &str = "OK";
+pub const RESET_STR: &str = "RESET";
 pub const PONG_STR: &str = "PONG";
+pub const SYNTAX_ERR_STR: &str = "ERR syntax error";
+pub const NOT_INTEGER_ERR_STR: &str = "ERR value is not an integer or out of range";
+pub const NOT_FLOAT_ERR_STR: &str = "ERR value is not a valid float";
+pub const OVERFLOW_ERR_STR: &str = "ERR increment or decrement would overflow";
+pub const BIT_OFFSET_ERR_STR: &str = "ERR bit offset is not an integer or out of range";
+pub const OFFSET_RANGE_ERR_STR: &str = "ERR offset is out of range";
+pub const STRING_TOO_LONG_ERR_STR: &str = "ERR string exceeds maximum allowed size (proto-max-bulk-len)";
+pub const BGSAVE_STARTED_STR: &str = "Background saving started";
+pub const BGSAVE_IN_PROGRESS_ERR_STR: &str = "ERR Background save already in progress";
+pub const MAX_QUOTED_ARG_LEN: usize = 128;  // Client arguments quoted in an error reply are cut to this many characters, like Redis.
+pub const CLUSTER_INFO_STR: &str = "cluster_enabled:0\r\n";
+pub const NO_CONFIG_FILE_ERR_STR: &str = "ERR The server is running without a config file";
+pub const INVALID_COMMAND_ERR_STR: &str = "ERR Invalid command specified";
+pub const INVALID_COMMAND_ARITY_ERR_STR: &str = "ERR Invalid number of arguments specified for command";
+pub const NO_KEY_ARGUMENTS_ERR_STR: &str = "ERR The command has no key arguments";
+pub const NO_SUCH_KEY_ERR_STR: &str = "ERR no such key";
+pub const BIT_VALUE_ERR_STR: &str = "ERR bit is not an integer or out of range";
+pub const BIT_ARG_ERR_STR: &str = "ERR The bit argument must be 1 or 0.";
+pub const BITOP_NOT_ERR_STR: &str = "ERR BITOP NOT must be called with a single source key.";
+pub const BUSYKEY_ERR_STR: &str = "BUSYKEY Target key name already exists.";
+pub const DUMP_PAYLOAD_ERR_STR: &str = "ERR DUMP payload version or checksum are wrong";
+pub const BAD_DATA_FORMAT_ERR_STR: &str = "ERR Bad data format";
+pub const INVALID_TTL_ERR_STR: &str = "ERR Invalid TTL value, must be >= 0";
+pub const INVALID_IDLETIME_ERR_STR: &str = "ERR Invalid IDLETIME value, must be >= 0";
+pub const INVALID_FREQ_ERR_STR: &str = "ERR Invalid FREQ value, must be >= 0 and <= 255";
+pub const FREQ_REQUIRES_LFU_ERR_STR: &str = "ERR FREQ can only be restored when an LFU maxmemory policy is selected";
+pub const UNSUPPORTED_COMMAND_ERR_STR: &str = "ERR This Redis command is not supported in this server";
+pub const OOM_ERR_STR: &str = "OOM command not allowed when used memory > 'maxmemory'.";
+pub const MAX_CLIENTS_ERR_STR: &str = "ERR max number of clients reached";
+pub const MONITOR_MODE_ERR_STR: &str = "ERR only RESET and QUIT are allowed in MONITOR mode";
+pub const NOPROTO_ERR_STR: &str = "NOPROTO unsupported protocol version";
+pub const PROTOCOL_VERSION_ERR_STR: &str = "ERR Protocol version is not an integer or out of range";
+pub const WRONGPASS_ERR_STR: &str = "WRONGPASS invalid username-password pair or user is disabled.";
+pub const CLIENT_NAME_ERR_STR: &str = "ERR Client names cannot contain spaces, newlines or special characters.";
+
+// Help
+pub const OBJECT_HELP: &[&str] = &[
+    "ENCODING <key>",
+    "    Return the kind of internal representation used in order to store the value",
+    "    associated with a <key>.",
+    "FREQ <key>",
+    "    Return the access frequency index of the <key>. The returned integer is",
+    "    proportional to the logarithm of the recent access frequency of the key.",
+    "IDLETIME <key>",
+    "    Return the idle time of the <key>, that is the approximated number of",
+    "    seconds elapsed since the last access to the key.",
+];
+pub const COMMAND_HELP: &[&str] = &[
+    "(no subcommand)",
+    "    Return details about all commands.",
+    "COUNT",
+    "    Return the total number of commands in this server.",
+    "LIST",
+    "    Return a list of all commands in this server.",
+    "DOCS [<command-name> ...]",
+    "    Return documentation details about multiple commands.",
+    "    If no command names are given, documentation details for all",
+    "    commands are returned.",
+    "GETKEYS <full-command>",
+    "    Return the keys from a full Redis command.",
+    "INFO [<command-name> ...]",
+    "    Return details about multiple commands.",
+    "    If no command names are given, documentation details for all",
+    "    commands are returned.",
+];
+pub const CONFIG_HELP: &[&str] = &[
+    "GET <pattern> [<pattern> ...]",
+    "    Return parameters matching the glob-like <pattern>s and their values.",
+    "REWRITE",
+    "    Rewrite the configuration file.",
+];
+pub const CLUSTER_HELP: &[&str] = &[
+    "INFO",
+    "    Return information about the cluster (cluster support is disabled on this server).",
+    "MYID",
+    "    Return the node id.",
+];
+pub const SLOWLOG_HELP: &[&str] = &[
+    "GET [<count>]",
+    "    Return top <count> entries from the slowlog (default: 10, -1 mean all).",
+    "    Entries are made of:",
+    "    id, timestamp, time in microseconds, arguments array, client IP and port,",
+    "    client name",
+    "LEN",
+    "    Return the length of the slowlog.",
+    "RESET",
+    "    Reset the slowlog.",
+];
+pub const LATENCY_HELP: &[&str] = &[
+    "HISTORY <event>",
+    "    Return time-latency samples for the <event> class.",
+    "LATEST",
+    "    Return the latest latency samples for all events.",
+    "RESET [<event> ...]",
+    "    Reset latency data of one or more <event> classes.",
+    "    (default: reset all data for all event classes)",
+];
+pub const CLIENT_HELP: &[&str] = &[
+    "GETNAME",
+    "    Return the name of the current connection.",
+    "ID",
+    "    Return the ID of the current connection.",
+    "INFO",
+    "    Return information about the current client connection.",
+    "NO-EVICT (ON|OFF)",
+    "    Protect current client connection from eviction.",
+    "NO-TOUCH (ON|OFF)",
+    "    Will not touch LRU/LFU stats when this mode is on.",
+    "SETNAME <name>",
+    "    Assign the name <name> to the current connection.",
+];
+pub const DEBUG_HELP: &[&str] = &[
+    "CHANGE-REPL-ID",
+    "    Change the replication IDs of the instance.",
+    "    Dangerous: should be used only for testing the replication subsystem.",
+    "ERROR <string>",
+    "    Return a Redis protocol error with <string> as message. Useful for clients",
+    "    unit tests to simulate Redis errors.",
+    "OBJECT <key>",
+    "    Show low level info about the <key> and associated value.",
+    "SLEEP <seconds>",
+    "    Stop the server for <seconds>. Decimals allowed.",
+    "SLEEP-BEFORE-REPLY <milliseconds>",
+    "    Wait <milliseconds> before replying, without holding any lock. Useful for",
+    "    testing client timeouts.",
+    "STRINGMATCH-LEN <pattern> <string>",
+    "    Return 1 if the glob-style <pattern> matches <string>, 0 otherwise.",
+    "SET-ACTIVE-EXPIRE <0|1>",
+    "    Setting it to 0 disables expiring keys in background when they are not",
+    "    accessed (otherwise the Redis behavior). Setting it to 1 reenables back the",
+    "    default.",
+];
 
 
 //SPECIAL CHARACTERS
@@ -36,9 +348,28 @@ pub const CRLF: &str = "\r\n";
 pub const PLUS_CHAR: char = '+';
 pub const DOLLAR_SIGN_CHAR: char = '$';
 pub const ASTERISK_: char = '*';
+pub const MINUS_CHAR: char = '-';
+pub const PERCENT_CHAR: char = '%';
+pub const COLON_CHAR: char = ':';
 
 // HEX codes
 pub const VALUE_TYPE_STRING: u8 = 0x00;
 pub const EXPIRE_IN_MILLISECONDS: u8 = 0xFC;
 pub const EXPIRE_IN_SECONDS: u8 = 0xFD;
 pub const HASH_TABLE_SELECTOR: u8 = 0xFB;
+pub const AUX_FIELD: u8 = 0xFA;
+pub const SELECT_DB: u8 = 0xFE;
+pub const END_OF_FILE: u8 = 0xFF;
+
+// RDB
+pub const RDB_MAGIC: &[u8] = b"REDIS";
+pub const RDB_VERSION: u16 = 11;
+pub const RDB_MAX_VERSION: u32 = 12;
+pub const RDB_HEADER_LEN: usize = 9;
+pub const RDB_FOOTER_LEN: usize = 10;
+pub const CRC64_POLY: u64 = 0x95AC_9329_AC4B_C9B5;
+pub const DEFAULT_DIR: &str = ".";
+pub const DEFAULT_DB_FILENAME: &str = "dump.rdb";
+pub const DEFAULT_SAVE_POINTS: [(u64, u64); 3] = [(3600, 1), (300, 100), (60, 10000)];
+pub const AUTO_SAVE_CYCLE_MS: u64 = 1000;
+pub const BGSAVE_RETRY_DELAY_SECS: u64 = 5;
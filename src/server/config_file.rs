@@ -0,0 +1,292 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use anyhow::Context;
+use clap::ValueEnum;
+
+use crate::server::arg_handler::ArgHandler;
+use crate::server::common_variables::{ACTIVE_EXPIRE_JITTER_CONFIG, BIND_CONFIG, DB_FILENAME_ARG_COMMAND, DIR_ARG_COMMAND, LATENCY_MONITOR_THRESHOLD_CONFIG, MAXCLIENTS_CONFIG, MAXMEMORY_CONFIG, MAXMEMORY_POLICY_CONFIG, PORT_CONFIG, PROTO_MAX_BULK_LEN_CONFIG, PROTO_MAX_INLINE_LEN_CONFIG, SAVE_CONFIG, SLOWLOG_LOG_SLOWER_THAN_CONFIG, SLOWLOG_MAX_LEN_CONFIG, TCP_BACKLOG_CONFIG, TCP_KEEPALIVE_CONFIG};
+use crate::server::resp_response::split_inline_args;
+
+/// `ConfigDirective` is a single `name value...` line of a config file.
+#[derive(Debug)]
+pub struct ConfigDirective {
+    pub name: String,         // The lowercase directive name (e.g. "dir").
+    pub values: Vec<String>,  // The arguments following the name, with surrounding quotes removed.
+    pub line: usize,          // The 1-based line number, used in error messages.
+}
+
+/// Reads a redis.conf-style config file.
+///
+/// Each non-blank line that does not start with `#` is a directive name followed by its arguments,
+/// separated by whitespace. As in Redis, arguments may be quoted (e.g. `save ""` or `dir "/my data"`),
+/// with the same escapes as inline commands (see `split_inline_args`).
+///
+/// # Arguments
+///
+/// * `path` - The path of the config file.
+///
+/// # Returns
+///
+/// Returns the directives in file order, or an error naming the path if the file could not be read
+/// and naming the line if a directive has no arguments or unbalanced quotes.
+pub fn read_config_file(path: &Path) -> Result<Vec<ConfigDirective>, anyhow::Error> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Could not read config file {}", path.display()))?;
+
+    let mut directives = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let bad_directive = || anyhow::anyhow!("Bad directive or wrong number of arguments in {} at line {}: '{}'", path.display(), index + 1, line);
+        let mut tokens = split_inline_args(line.as_bytes())
+            .ok_or_else(bad_directive)?
+            .into_iter()
+            .map(|token| String::from_utf8_lossy(&token).into_owned());
+        let name = tokens.next().unwrap_or_default().to_ascii_lowercase();
+        let values: Vec<String> = tokens.collect();
+        if values.is_empty() {
+            return Err(bad_directive());
+        }
+        directives.push(ConfigDirective { name, values, line: index + 1 });
+    }
+    Ok(directives)
+}
+
+/// Returns the current configuration as `redis.conf` directives.
+///
+/// # Arguments
+///
+/// * `args` - The runtime configuration.
+///
+/// # Returns
+///
+/// Returns `(name, value)` pairs in the order they are written to a new config file.
+pub fn config_entries(args: &ArgHandler) -> Vec<(&'static str, String)> {
+    let mut entries = vec![
+        (BIND_CONFIG, args.bind.clone()),
+        (PORT_CONFIG, args.port.to_string()),
+    ];
+    if let Some(dir) = &args.dir {
+        entries.push((DIR_ARG_COMMAND, dir.clone()));
+    }
+    if let Some(dbfilename) = &args.dbfilename {
+        entries.push((DB_FILENAME_ARG_COMMAND, dbfilename.clone()));
+    }
+    let save_points: Vec<String> = args.save_points().iter()
+        .map(|(seconds, changes)| format!("{} {}", seconds, changes))
+        .collect();
+    entries.push((SAVE_CONFIG, save_points.join(" ")));
+    entries.push((ACTIVE_EXPIRE_JITTER_CONFIG, args.active_expire_jitter.to_string()));
+    entries.push((MAXMEMORY_CONFIG, args.maxmemory.to_string()));
+    let policy = args.maxmemory_policy.to_possible_value().map(|value| value.get_name().to_string()).unwrap_or_default();
+    entries.push((MAXMEMORY_POLICY_CONFIG, policy));
+    entries.push((PROTO_MAX_BULK_LEN_CONFIG, args.proto_max_bulk_len.to_string()));
+    entries.push((PROTO_MAX_INLINE_LEN_CONFIG, args.proto_max_inline_len.to_string()));
+    entries.push((MAXCLIENTS_CONFIG, args.maxclients.to_string()));
+    entries.push((TCP_BACKLOG_CONFIG, args.tcp_backlog.to_string()));
+    entries.push((TCP_KEEPALIVE_CONFIG, args.tcp_keepalive.to_string()));
+    entries.push((SLOWLOG_LOG_SLOWER_THAN_CONFIG, args.slowlog_log_slower_than.to_string()));
+    entries.push((SLOWLOG_MAX_LEN_CONFIG, args.slowlog_max_len.to_string()));
+    entries.push((LATENCY_MONITOR_THRESHOLD_CONFIG, args.latency_monitor_threshold.to_string()));
+    entries
+}
+
+/// Rewrites the config file so it reflects the current configuration (`CONFIG REWRITE`).
+///
+/// Comments, blank lines and directives this server does not manage are kept as they are. The first
+/// line of each managed directive is replaced with its current value and any repeated lines of it are
+/// dropped; directives missing from the file are appended at the end. Values are quoted where needed
+/// (see `quote_config_arg`), so paths with spaces read back unchanged.
+///
+/// There is no `CONFIG SET` yet, so the configuration can only differ from the file by command-line
+/// flags and defaults: a rewrite records those, but has no runtime changes to persist.
+///
+/// # Arguments
+///
+/// * `path` - The path of the config file.
+/// * `args` - The runtime configuration.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the file was rewritten, or an error naming the path if it could not be read or written.
+pub fn rewrite_config(path: &Path, args: &ArgHandler) -> Result<(), anyhow::Error> {
+    let existing = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("Could not read config file {}", path.display())),
+    };
+
+    let mut entries: Vec<(&str, Option<String>)> = config_entries(args).into_iter()
+        .map(|(name, value)| (name, Some(value)))
+        .collect();
+    let mut lines = Vec::new();
+
+    for line in existing.lines() {
+        let name = line.split_whitespace().next().unwrap_or("").to_ascii_lowercase();
+        match entries.iter_mut().find(|(entry_name, _)| *entry_name == name) {
+            Some((entry_name, value)) => {
+                // Keep only the first occurrence of a managed directive, with its current value.
+                if let Some(value) = value.take() {
+                    lines.push(directive_line(entry_name, &directive_args(entry_name, &value)));
+                }
+            }
+            None => lines.push(line.to_string()),
+        }
+    }
+    for (name, value) in entries {
+        if let Some(value) = value {
+            lines.push(directive_line(name, &directive_args(name, &value)));
+        }
+    }
+
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    let temp_path = path.with_extension(format!("tmp-{}", std::process::id()));
+    fs::write(&temp_path, contents)
+        .and_then(|_| fs::rename(&temp_path, path))
+        .with_context(|| format!("Could not write config file {}", path.display()))
+}
+
+/// Splits a value reported by `config_entries` into the arguments of its config file directive.
+///
+/// # Arguments
+///
+/// * `name` - The directive name.
+/// * `value` - The directive value.
+///
+/// # Returns
+///
+/// Returns the arguments: `save` lists its numbers as separate arguments (none when automatic saving is
+/// disabled); every other directive has its value as a single argument.
+fn directive_args<'a>(name: &str, value: &'a str) -> Vec<&'a str> {
+    if name == SAVE_CONFIG {
+        value.split_whitespace().collect()
+    } else {
+        vec![value]
+    }
+}
+
+/// Formats a directive line for the config file, quoting each argument as needed.
+///
+/// # Arguments
+///
+/// * `name` - The directive name.
+/// * `args` - The directive arguments; none is written as a single `""`.
+///
+/// # Returns
+///
+/// Returns the `name arg...` line, which `read_config_file` reads back to the same arguments.
+fn directive_line(name: &str, args: &[&str]) -> String {
+    let args: Vec<String> = if args.is_empty() {
+        vec![quote_config_arg("")]
+    } else {
+        args.iter().map(|arg| quote_config_arg(arg)).collect()
+    };
+    format!("{} {}", name, args.join(" "))
+}
+
+/// Quotes a config file argument the way `read_config_file` unquotes it, like Redis's `sdscatrepr`.
+///
+/// Arguments made of printable characters other than quotes and backslashes are written as they are.
+/// Anything else (including an empty argument) is wrapped in double quotes, with `"` and `\` escaped,
+/// common control characters written as `\n`, `\r`, `\t`, `\a` and `\b`, and other bytes as `\xHH`.
+///
+/// # Arguments
+///
+/// * `arg` - The argument to quote.
+///
+/// # Returns
+///
+/// Returns the argument as it is written to the file.
+fn quote_config_arg(arg: &str) -> String {
+    let is_plain = |byte: &u8| byte.is_ascii_graphic() && !matches!(byte, b'"' | b'\'' | b'\\');
+    if !arg.is_empty() && arg.as_bytes().iter().all(is_plain) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for &byte in arg.as_bytes() {
+        match byte {
+            b'"' | b'\\' => {
+                quoted.push('\\');
+                quoted.push(byte as char);
+            }
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            0x07 => quoted.push_str("\\a"),
+            0x08 => quoted.push_str("\\b"),
+            b' ' => quoted.push(' '),
+            byte if byte.is_ascii_graphic() => quoted.push(byte as char),
+            byte => quoted.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    /// Returns a path in the temp directory that is unique to this test process and `name`.
+    fn temp_config(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("redis-rust-{}-{}.conf", std::process::id(), name))
+    }
+
+    /// Returns the values of the first directive called `name`.
+    fn values_of(directives: &[ConfigDirective], name: &str) -> Vec<String> {
+        directives.iter().find(|directive| directive.name == name).unwrap().values.clone()
+    }
+
+    #[test]
+    fn reads_quoted_arguments() {
+        let path = temp_config("read");
+        fs::write(&path, "# comment\n\nDir \"/my data\"\nsave \"\"\nmaxmemory 100\n").unwrap();
+        let directives = read_config_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(values_of(&directives, "dir"), vec!["/my data"]);
+        assert_eq!(values_of(&directives, "save"), vec![""]);
+        assert_eq!(values_of(&directives, "maxmemory"), vec!["100"]);
+    }
+
+    #[test]
+    fn rejects_directives_without_arguments_or_with_unbalanced_quotes() {
+        let path = temp_config("invalid");
+        fs::write(&path, "dir\n").unwrap();
+        assert!(read_config_file(&path).is_err());
+        fs::write(&path, "dir \"/tmp\n").unwrap();
+        assert!(read_config_file(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rewrite_round_trips_values_and_keeps_unmanaged_lines() {
+        let path = temp_config("rewrite");
+        fs::write(&path, "# keep me\nappendonly no\nport 1\nport 2\n").unwrap();
+        let args = ArgHandler::try_parse_from(["redis-rust", "--dir", "/my \"data\"", "--save", "", "--port", "7000"]).unwrap();
+        rewrite_config(&path, &args).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        let directives = read_config_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(contents.starts_with("# keep me\nappendonly no\nport 7000\n"));
+        assert_eq!(contents.matches("port ").count(), 1);
+        assert_eq!(values_of(&directives, "dir"), vec!["/my \"data\""]);
+        assert_eq!(values_of(&directives, "save"), vec![""]);
+    }
+
+    #[test]
+    fn rewrite_writes_save_points_as_separate_arguments() {
+        assert_eq!(directive_line(SAVE_CONFIG, &directive_args(SAVE_CONFIG, "3600 1 300 100")), "save 3600 1 300 100");
+        assert_eq!(directive_line(SAVE_CONFIG, &directive_args(SAVE_CONFIG, "")), "save \"\"");
+        assert_eq!(quote_config_arg("a\tb\x01"), "\"a\\tb\\x01\"");
+    }
+}
@@ -0,0 +1,208 @@
+use crate::server::common_variables::GLOB_MAX_NESTING;
+
+/// Checks whether a string matches a glob-style pattern, following Redis's `stringmatchlen`.
+///
+/// Supported syntax:
+/// * `*` - any sequence of bytes, including none.
+/// * `?` - exactly one byte.
+/// * `[abc]`, `[a-z]`, `[^a]` - one byte from (or, with `^`, not from) a set of bytes and ranges;
+///   reversed ranges such as `[z-a]` are accepted, and an unterminated set extends to the end of the pattern.
+/// * `\x` - the byte `x` literally, both inside and outside of sets.
+///
+/// Matching works on raw bytes, so binary keys and patterns are supported. Patterns nesting `*` more
+/// than `GLOB_MAX_NESTING` levels deep never match, which bounds the cost of abusive patterns.
+///
+/// # Arguments
+///
+/// * `pattern` - The glob pattern.
+/// * `string` - The string to match against the pattern.
+/// * `nocase` - Whether ASCII letters are compared case-insensitively.
+///
+/// # Returns
+///
+/// Returns `true` if the whole string matches the pattern.
+///
+/// # Examples
+///
+/// ```
+/// assert!(glob_match(b"h[a-c]llo", b"hallo", false));
+/// assert!(!glob_match(b"h\\*llo", b"hello", false));
+/// ```
+pub fn glob_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    let mut skip_longer_matches = false;
+    match_from(pattern, string, nocase, &mut skip_longer_matches, 0)
+}
+
+/// Matches `string` against `pattern`, recursing at every `*`.
+///
+/// Once the rest of a pattern after a `*` failed to match at every position of the string,
+/// `skip_longer_matches` is set: an earlier `*` consuming more bytes cannot help either, so the
+/// whole search stops instead of backtracking exponentially.
+///
+/// # Arguments
+///
+/// * `pattern` - The remaining pattern.
+/// * `string` - The remaining string.
+/// * `nocase` - Whether ASCII letters are compared case-insensitively.
+/// * `skip_longer_matches` - Set once backtracking into earlier `*`s is known to be useless.
+/// * `nesting` - The number of `*`s the search is currently nested in.
+///
+/// # Returns
+///
+/// Returns `true` if the remaining string matches the remaining pattern.
+fn match_from(pattern: &[u8], string: &[u8], nocase: bool, skip_longer_matches: &mut bool, nesting: usize) -> bool {
+    if nesting > GLOB_MAX_NESTING {
+        return false;
+    }
+    let same = |a: u8, b: u8| if nocase { a.eq_ignore_ascii_case(&b) } else { a == b };
+    let (mut p, mut s) = (0, 0);
+
+    while p < pattern.len() && s < string.len() {
+        match pattern[p] {
+            b'*' => {
+                // Consecutive stars are equivalent to one; a trailing star matches whatever is left.
+                while p + 1 < pattern.len() && pattern[p + 1] == b'*' {
+                    p += 1;
+                }
+                if p + 1 == pattern.len() {
+                    return true;
+                }
+                while s < string.len() {
+                    if match_from(&pattern[p + 1..], &string[s..], nocase, skip_longer_matches, nesting + 1) {
+                        return true;
+                    }
+                    if *skip_longer_matches {
+                        return false;
+                    }
+                    s += 1;
+                }
+                *skip_longer_matches = true;
+                return false;
+            }
+            b'?' => s += 1,
+            b'[' => {
+                p += 1;
+                let negate = pattern.get(p) == Some(&b'^');
+                if negate {
+                    p += 1;
+                }
+                let mut matched = false;
+                loop {
+                    match pattern.get(p) {
+                        Some(b'\\') if p + 1 < pattern.len() => {
+                            p += 1;
+                            matched |= pattern[p] == string[s];
+                        }
+                        Some(b']') => break,
+                        None => {
+                            p -= 1;  // An unterminated set ends with the pattern.
+                            break;
+                        }
+                        Some(&first) if p + 2 < pattern.len() && pattern[p + 1] == b'-' => {
+                            let (mut start, mut end, mut c) = (first.min(pattern[p + 2]), first.max(pattern[p + 2]), string[s]);
+                            if nocase {
+                                start = start.to_ascii_lowercase();
+                                end = end.to_ascii_lowercase();
+                                c = c.to_ascii_lowercase();
+                            }
+                            p += 2;
+                            matched |= (start..=end).contains(&c);
+                        }
+                        Some(&literal) => matched |= same(literal, string[s]),
+                    }
+                    p += 1;
+                }
+                if matched == negate {
+                    return false;
+                }
+                s += 1;
+            }
+            literal => {
+                // A backslash escapes the next byte, unless it ends the pattern.
+                let literal = if literal == b'\\' && p + 1 < pattern.len() {
+                    p += 1;
+                    pattern[p]
+                } else {
+                    literal
+                };
+                if !same(literal, string[s]) {
+                    return false;
+                }
+                s += 1;
+            }
+        }
+        p += 1;
+
+        // Trailing stars match the empty rest of the string.
+        if s == string.len() {
+            while p < pattern.len() && pattern[p] == b'*' {
+                p += 1;
+            }
+            break;
+        }
+    }
+    p == pattern.len() && s == string.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::command_handler::execute_command;
+    use crate::server::command_handler::tests::setup;
+
+    #[test]
+    fn matches_wildcards_sets_and_escapes() {
+        assert!(!glob_match(b"*", b"", false));  // Like Redis, the empty string matches no pattern but the empty one.
+        assert!(glob_match(b"", b"", false));
+        assert!(glob_match(b"h?llo", b"hello", false));
+        assert!(!glob_match(b"h?llo", b"hllo", false));
+        assert!(glob_match(b"h*llo", b"heeeello", false));
+        assert!(glob_match(b"h**o*", b"hello", false));
+        assert!(glob_match(b"h[ae]llo", b"hallo", false));
+        assert!(!glob_match(b"h[ae]llo", b"hillo", false));
+        assert!(glob_match(b"h[^e]llo", b"hallo", false));
+        assert!(!glob_match(b"h[^e]llo", b"hello", false));
+        assert!(glob_match(b"h[a-b]llo", b"hbllo", false));
+        assert!(glob_match(b"h[z-a]llo", b"hmllo", false));  // Reversed ranges are accepted.
+        assert!(glob_match(b"h[\\]]llo", b"h]llo", false));
+        assert!(glob_match(b"h\\*llo", b"h*llo", false));
+        assert!(!glob_match(b"h\\*llo", b"hello", false));
+        assert!(glob_match(b"ab[c", b"abc", false));  // An unterminated set extends to the end.
+        assert!(glob_match(b"a\\", b"a\\", false));  // A trailing backslash is literal.
+    }
+
+    #[test]
+    fn matches_case_insensitively_on_request() {
+        assert!(!glob_match(b"HELLO", b"hello", false));
+        assert!(glob_match(b"HELLO", b"hello", true));
+        assert!(glob_match(b"h[A-Z]llo", b"hello", true));
+    }
+
+    #[test]
+    fn matches_binary_strings() {
+        assert!(glob_match(b"\x00*\xff", b"\x00\x01\x02\xff", false));
+        assert!(glob_match(b"[\x00-\x10]", b"\x05", false));
+    }
+
+    #[test]
+    fn abusive_patterns_are_bounded() {
+        // Backtracking stops after the first failed suffix match instead of trying every split.
+        let pattern = b"a*".repeat(30);
+        assert!(!glob_match(&[pattern.as_slice(), b"b"].concat(), &[b'a'; 60], false));
+
+        let too_deep = b"*a".repeat(GLOB_MAX_NESTING + 1);
+        assert!(!glob_match(&too_deep, &b"a".repeat(GLOB_MAX_NESTING + 1), false));
+    }
+
+    #[test]
+    fn debug_stringmatch_len_agrees_with_redis() {
+        let (db, args_cli) = setup(&[]);
+        let matches = |pattern: &str, string: &str| execute_command(&db, &args_cli, format!("DEBUG STRINGMATCH-LEN \"{}\" \"{}\"", pattern, string).as_bytes());
+
+        assert_eq!(matches("h[a-c]llo", "hbllo"), b":1\r\n");
+        assert_eq!(matches("h[a-c]llo", "hello"), b":0\r\n");
+        assert_eq!(matches("h\\\\*llo", "h*llo"), b":1\r\n");
+        assert_eq!(matches("h\\\\*llo", "hello"), b":0\r\n");
+        assert_eq!(matches("", ""), b":1\r\n");
+    }
+}
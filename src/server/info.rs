@@ -0,0 +1,194 @@
+use std::sync::atomic::Ordering;
+
+use crate::server::common_variables::{Db, INFO_SECTIONS_ALL, INFO_SECTIONS_DEFAULT, INFO_SECTION_KEYSPACE, INFO_SECTION_PERSISTENCE, INFO_SECTION_REPLICATION, INFO_SECTION_SERVER, INFO_SECTION_STATS, SECONDS_PER_DAY};
+use crate::server::server_state::SharedState;
+
+/// The sections reported by `INFO`, in output order.
+const SECTIONS: &[&str] = &[INFO_SECTION_SERVER, INFO_SECTION_PERSISTENCE, INFO_SECTION_STATS, INFO_SECTION_REPLICATION, INFO_SECTION_KEYSPACE];
+
+/// Builds the `INFO` report for the requested sections.
+///
+/// Each section starts with a `# Title` header followed by `field:value` lines, and sections are
+/// separated by a blank line. Without arguments, or with `all` or `default`, every section is included.
+/// Unknown section names are ignored.
+///
+/// # Arguments
+///
+/// * `requested` - The lowercase section names given to `INFO`.
+/// * `db` - The shared database, summarized by the keyspace section.
+/// * `state` - The shared server state.
+///
+/// # Returns
+///
+/// Returns the report as a CRLF-separated `String`.
+pub fn build_info(requested: &[String], db: &Db, state: &SharedState) -> String {
+    let include_all = requested.is_empty()
+        || requested.iter().any(|name| name == INFO_SECTIONS_ALL || name == INFO_SECTIONS_DEFAULT);
+
+    SECTIONS.iter()
+        .filter(|section| include_all || requested.iter().any(|name| name == *section))
+        .map(|section| info_section(section, db, state))
+        .collect::<Vec<String>>()
+        .join("\r\n")
+}
+
+/// Builds a single `INFO` section.
+///
+/// # Arguments
+///
+/// * `section` - The section name, one of `SECTIONS`.
+/// * `db` - The shared database.
+/// * `state` - The shared server state.
+///
+/// # Returns
+///
+/// Returns the section header and its fields, each line terminated by CRLF.
+fn info_section(section: &str, db: &Db, state: &SharedState) -> String {
+    let (title, fields): (&str, Vec<(&str, String)>) = match section {
+        INFO_SECTION_SERVER => {
+            let uptime = state.started.elapsed().as_secs();
+            ("Server", vec![
+                ("run_id", state.run_id.clone()),
+                ("uptime_in_seconds", uptime.to_string()),
+                ("uptime_in_days", (uptime / SECONDS_PER_DAY).to_string()),
+            ])
+        }
+        INFO_SECTION_PERSISTENCE => ("Persistence", vec![
+            ("loading", "0".to_string()),
+            ("rdb_changes_since_last_save", state.dirty.load(Ordering::Relaxed).to_string()),
+            ("rdb_bgsave_in_progress", (state.bgsave_in_progress.load(Ordering::Relaxed) as u8).to_string()),
+            ("rdb_last_save_time", state.last_save.load(Ordering::Relaxed).to_string()),
+            ("rdb_last_bgsave_status", if state.last_bgsave_ok.load(Ordering::Relaxed) { "ok" } else { "err" }.to_string()),
+        ]),
+        INFO_SECTION_STATS => ("Stats", vec![
+            ("keyspace_hits", state.keyspace_hits.load(Ordering::Relaxed).to_string()),
+            ("keyspace_misses", state.keyspace_misses.load(Ordering::Relaxed).to_string()),
+        ]),
+        INFO_SECTION_REPLICATION => ("Replication", vec![
+            ("role", "master".to_string()),
+            ("connected_slaves", "0".to_string()),
+            ("master_replid", state.replid.read().unwrap().clone()),
+            ("master_repl_offset", "0".to_string()),
+        ]),
+        INFO_SECTION_KEYSPACE => ("Keyspace", keyspace_fields(db)),
+        _ => (section, Vec::new()),
+    };
+
+    let mut output = format!("# {}\r\n", title);
+    for (name, value) in fields {
+        output.push_str(&format!("{}:{}\r\n", name, value));
+    }
+    output
+}
+
+/// Builds the fields of the keyspace section: one `dbN:keys=...,expires=...,avg_ttl=0` line per non-empty database.
+///
+/// Keys that have expired but were not removed yet are not counted. This server has a single database, `db0`.
+///
+/// # Arguments
+///
+/// * `db` - The shared database.
+///
+/// # Returns
+///
+/// Returns the `(name, value)` fields, empty if the database holds no live keys.
+fn keyspace_fields(db: &Db) -> Vec<(&'static str, String)> {
+    let db = db.lock().unwrap();
+    let (keys, expires) = db.values()
+        .filter(|redis_item| !redis_item.is_expired())
+        .fold((0, 0), |(keys, expires), redis_item| (keys + 1, expires + redis_item.get_expiration().is_some() as usize));
+
+    if keys == 0 {
+        return Vec::new();
+    }
+    vec![("db0", format!("keys={},expires={},avg_ttl=0", keys, expires))]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::server::command_handler::handle_command;
+    use crate::server::command_handler::tests::setup;
+    use crate::server::redis_item::RedisItem;
+    use crate::server::resp_response::RespResponse;
+    use crate::server::server_state::ServerState;
+
+    /// Returns the value of `field` in the given `INFO` section.
+    fn info_field(section: &str, db: &Db, state: &SharedState, field: &str) -> String {
+        let report = build_info(&[section.to_string()], db, state);
+        report.lines().find_map(|line| line.strip_prefix(&format!("{}:", field))).unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn the_replication_id_changes_only_on_request() {
+        let (db, args_cli) = setup(&[]);
+        let state = ServerState::new_shared();
+        let replid = info_field(INFO_SECTION_REPLICATION, &db, &state, "master_replid");
+        assert_eq!(replid.len(), 40);
+        assert!(replid.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+        assert_eq!(info_field(INFO_SECTION_REPLICATION, &db, &state, "master_replid"), replid);
+
+        let args = ["DEBUG", "CHANGE-REPL-ID"].map(|part| RespResponse::BulkString(part.as_bytes().to_vec()));
+        handle_command("DEBUG", &args, &db, &args_cli, &state, None, false).unwrap();
+        let changed = info_field(INFO_SECTION_REPLICATION, &db, &state, "master_replid");
+        assert_eq!(changed.len(), 40);
+        assert_ne!(changed, replid);
+    }
+
+    #[test]
+    fn reads_count_keyspace_hits_and_misses() {
+        let (db, args_cli) = setup(&[]);
+        let state = ServerState::new_shared();
+        let run = |parts: &[&str]| {
+            let args: Vec<RespResponse> = parts.iter().map(|part| RespResponse::BulkString(part.as_bytes().to_vec())).collect();
+            handle_command(parts[0], &args, &db, &args_cli, &state, None, false).unwrap();
+        };
+        let stats = |field| info_field(INFO_SECTION_STATS, &db, &state, field);
+
+        run(&["SET", "present", "v"]);
+        run(&["GET", "present"]);
+        run(&["GET", "absent"]);
+        assert_eq!((stats("keyspace_hits"), stats("keyspace_misses")), ("1".to_string(), "1".to_string()));
+
+        run(&["SET", "expiring", "v", "PX", "1"]);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        run(&["GET", "expiring"]);  // An expired key is a miss.
+        assert_eq!((stats("keyspace_hits"), stats("keyspace_misses")), ("1".to_string(), "2".to_string()));
+    }
+
+    #[test]
+    fn the_keyspace_section_counts_live_keys_and_expirations() {
+        let (db, _) = setup(&[]);
+        let state = ServerState::new_shared();
+        assert_eq!(build_info(&[INFO_SECTION_KEYSPACE.to_string()], &db, &state), "# Keyspace\r\n");  // Empty databases are left out.
+
+        let future = SystemTime::now() + Duration::from_secs(100);
+        let past = SystemTime::now() - Duration::from_secs(1);
+        db.lock().unwrap().extend([
+            (b"persistent".to_vec(), RedisItem::new(b"v".to_vec())),
+            (b"volatile".to_vec(), RedisItem::new_with_expiration(b"v".to_vec(), future)),
+            (b"expired".to_vec(), RedisItem::new_with_expiration(b"v".to_vec(), past)),
+        ]);
+        assert_eq!(info_field(INFO_SECTION_KEYSPACE, &db, &state, "db0"), "keys=2,expires=1,avg_ttl=0");
+    }
+
+    #[test]
+    fn the_run_id_is_stable_while_the_uptime_grows() {
+        let (db, _) = setup(&[]);
+        let mut state = ServerState::new_shared();
+        let run_id = info_field(INFO_SECTION_SERVER, &db, &state, "run_id");
+        assert_eq!(run_id.len(), 40);
+        assert!(run_id.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(run_id, info_field(INFO_SECTION_REPLICATION, &db, &state, "master_replid"));
+        assert_eq!(info_field(INFO_SECTION_SERVER, &db, &state, "uptime_in_seconds"), "0");
+
+        let started = &mut Arc::get_mut(&mut state).unwrap().started;
+        *started = started.checked_sub(Duration::from_secs(3)).unwrap();  // As if the server had run for three seconds.
+        assert_eq!(info_field(INFO_SECTION_SERVER, &db, &state, "run_id"), run_id);
+        assert_eq!(info_field(INFO_SECTION_SERVER, &db, &state, "uptime_in_seconds"), "3");
+        assert_eq!(info_field(INFO_SECTION_SERVER, &db, &state, "uptime_in_days"), "0");
+    }
+}
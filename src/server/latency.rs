@@ -0,0 +1,188 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::server::arg_handler::ArgsCli;
+use crate::server::common_variables::LATENCY_HISTORY_LEN;
+use crate::server::resp_response::RespResponse;
+use crate::server::snapshot::unix_time_secs;
+
+/// The latency samples recorded for one event category (e.g. `command`).
+#[derive(Debug, Default)]
+struct LatencyEvent {
+    samples: VecDeque<(u64, u64)>,  // (unix time in seconds, latency in milliseconds), oldest first.
+    max_latency: u64,               // Highest latency ever recorded for the event, in milliseconds.
+}
+
+/// `LatencyMonitor` records latency spikes per event category, as reported by the `LATENCY` command.
+#[derive(Debug, Default)]
+pub struct LatencyMonitor {
+    events: BTreeMap<&'static str, LatencyEvent>,
+}
+
+impl LatencyMonitor {
+    /// Records a latency sample for an event if it reaches the `latency-monitor-threshold`.
+    ///
+    /// Samples within the same second are merged, keeping the highest latency, and only the last 160
+    /// samples of each event are kept. A threshold of 0 disables the monitor.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event category, e.g. `command` or `rdb-save`.
+    /// * `duration` - How long the operation took.
+    /// * `args_cli` - The command-line arguments holding the threshold.
+    pub fn record(&mut self, event: &'static str, duration: Duration, args_cli: &ArgsCli) {
+        let threshold = args_cli.latency_monitor_threshold;
+        let latency = duration.as_millis() as u64;
+        if threshold == 0 || latency < threshold {
+            return;
+        }
+
+        let now = unix_time_secs();
+        let entry = self.events.entry(event).or_default();
+        entry.max_latency = entry.max_latency.max(latency);
+        match entry.samples.back_mut() {
+            Some((time, sample)) if *time == now => *sample = (*sample).max(latency),
+            _ => {
+                entry.samples.push_back((now, latency));
+                if entry.samples.len() > LATENCY_HISTORY_LEN {
+                    entry.samples.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Describes the latest sample of every event the way `LATENCY LATEST` reports it.
+    ///
+    /// # Returns
+    ///
+    /// Returns an array with, for each event, its name, the time and latency of the latest sample and
+    /// the highest latency ever recorded.
+    pub fn latest(&self) -> RespResponse {
+        let events = self.events.iter()
+            .filter_map(|(name, event)| {
+                let &(time, latency) = event.samples.back()?;
+                Some(RespResponse::RespArray(Arc::new(vec![
+                    RespResponse::BulkString(name.as_bytes().to_vec()),
+                    RespResponse::Integer(time as i64),
+                    RespResponse::Integer(latency as i64),
+                    RespResponse::Integer(event.max_latency as i64),
+                ])))
+            })
+            .collect();
+        RespResponse::RespArray(Arc::new(events))
+    }
+
+    /// Describes the samples of one event the way `LATENCY HISTORY` reports them.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event name.
+    ///
+    /// # Returns
+    ///
+    /// Returns an array of `[time, latency]` pairs, oldest first; empty for an unknown event.
+    pub fn history(&self, event: &str) -> RespResponse {
+        let samples = self.events.get(event)
+            .map(|event| event.samples.iter()
+                .map(|&(time, latency)| RespResponse::RespArray(Arc::new(vec![
+                    RespResponse::Integer(time as i64),
+                    RespResponse::Integer(latency as i64),
+                ])))
+                .collect())
+            .unwrap_or_default();
+        RespResponse::RespArray(Arc::new(samples))
+    }
+
+    /// Removes the samples of the given events, or of every event if none are given.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - The names of the events to reset.
+    ///
+    /// # Returns
+    ///
+    /// Returns the number of events that were reset.
+    pub fn reset(&mut self, events: &[String]) -> usize {
+        if events.is_empty() {
+            let count = self.events.len();
+            self.events.clear();
+            return count;
+        }
+        events.iter().filter(|event| self.events.remove(event.as_str()).is_some()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::server::arg_handler::ArgHandler;
+    use crate::server::command_handler::execute_command;
+    use crate::server::command_handler::tests::setup;
+    use crate::server::common_variables::{LATENCY_EVENT_COMMAND, LATENCY_EVENT_RDB_SAVE};
+
+    /// Builds the arguments of a server started with `args`.
+    fn server_args(args: &[&str]) -> ArgsCli {
+        Arc::new(ArgHandler::parse_from(std::iter::once("redis-rust").chain(args.iter().copied())))
+    }
+
+    #[test]
+    fn records_spikes_reaching_the_threshold() {
+        let mut monitor = LatencyMonitor::default();
+        monitor.record(LATENCY_EVENT_COMMAND, Duration::from_millis(500), &server_args(&[]));
+        assert!(monitor.events.is_empty());  // A threshold of 0 disables the monitor.
+
+        let args_cli = server_args(&["--latency-monitor-threshold", "100"]);
+        monitor.record(LATENCY_EVENT_COMMAND, Duration::from_millis(99), &args_cli);
+        assert!(monitor.events.is_empty());
+        monitor.record(LATENCY_EVENT_COMMAND, Duration::from_millis(100), &args_cli);
+        monitor.record(LATENCY_EVENT_COMMAND, Duration::from_millis(300), &args_cli);
+        monitor.record(LATENCY_EVENT_COMMAND, Duration::from_millis(200), &args_cli);
+
+        // Samples of the same second are merged, keeping the highest.
+        let event = &monitor.events[LATENCY_EVENT_COMMAND];
+        assert_eq!(event.samples.iter().map(|&(_, latency)| latency).collect::<Vec<_>>(), [300]);
+        assert_eq!(event.max_latency, 300);
+    }
+
+    #[test]
+    fn keeps_a_bounded_history() {
+        let mut monitor = LatencyMonitor::default();
+        let event = monitor.events.entry(LATENCY_EVENT_COMMAND).or_default();
+        event.samples.extend((0..LATENCY_HISTORY_LEN as u64).map(|time| (time, 1)));
+
+        monitor.record(LATENCY_EVENT_COMMAND, Duration::from_millis(100), &server_args(&["--latency-monitor-threshold", "1"]));
+        let samples = &monitor.events[LATENCY_EVENT_COMMAND].samples;
+        assert_eq!(samples.len(), LATENCY_HISTORY_LEN);
+        assert_eq!(samples.front(), Some(&(1, 1)));
+        assert_eq!(samples.back().map(|&(_, latency)| latency), Some(100));
+    }
+
+    #[test]
+    fn resets_the_given_events() {
+        let args_cli = server_args(&["--latency-monitor-threshold", "1"]);
+        let mut monitor = LatencyMonitor::default();
+        monitor.record(LATENCY_EVENT_COMMAND, Duration::from_millis(5), &args_cli);
+        monitor.record(LATENCY_EVENT_RDB_SAVE, Duration::from_millis(5), &args_cli);
+
+        assert_eq!(monitor.reset(&[LATENCY_EVENT_RDB_SAVE.to_string(), "unknown".to_string()]), 1);
+        assert_eq!(monitor.history(LATENCY_EVENT_RDB_SAVE).serialize(), b"*0\r\n");
+        assert_eq!(monitor.reset(&[]), 1);
+        assert_eq!(monitor.latest().serialize(), b"*0\r\n");
+    }
+
+    #[test]
+    fn latency_latest_reports_a_synthetic_spike() {
+        let (db, args_cli) = setup(&["--latency-monitor-threshold", "10"]);
+        let reply = String::from_utf8(execute_command(&db, &args_cli, b"PING\r\nLATENCY LATEST\r\nDEBUG SLEEP 0.02\r\nLATENCY LATEST")).unwrap();
+        let lines: Vec<&str> = reply.split("\r\n").collect();
+
+        assert_eq!(lines[..7], ["+PONG", "*0", "+OK", "*1", "*4", "$7", LATENCY_EVENT_COMMAND]);
+        assert!(lines[7].trim_start_matches(':').parse::<u64>().unwrap() > 0);  // The unix time of the spike.
+        let latest: u64 = lines[8].trim_start_matches(':').parse().unwrap();
+        let max: u64 = lines[9].trim_start_matches(':').parse().unwrap();
+        assert!(latest >= 20 && max == latest, "latest {} max {}", latest, max);
+    }
+}
@@ -0,0 +1,154 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rand::Rng;
+
+use crate::server::arg_handler::{ArgsCli, MaxMemoryPolicy};
+use crate::server::common_variables::{Db, Keyspace, MAXMEMORY_SAMPLES};
+
+/// Total number of bytes currently allocated through `CountingAllocator`.
+static USED_MEMORY: AtomicUsize = AtomicUsize::new(0);
+
+/// `CountingAllocator` wraps the system allocator and keeps track of the number of allocated bytes,
+/// which is what `maxmemory` is compared against (like Redis's `used_memory`).
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            USED_MEMORY.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        USED_MEMORY.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            USED_MEMORY.fetch_sub(layout.size(), Ordering::Relaxed);
+            USED_MEMORY.fetch_add(new_size, Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+/// Returns the number of bytes currently allocated by the server.
+///
+/// # Returns
+///
+/// Returns the allocated byte count as tracked by `CountingAllocator`.
+pub fn used_memory() -> usize {
+    USED_MEMORY.load(Ordering::Relaxed)
+}
+
+/// Evicts keys until memory usage is back under the `maxmemory` limit, according to `maxmemory-policy`.
+///
+/// Under `allkeys-lfu`, eviction is approximate like in Redis: each victim is the least frequently used
+/// of `MAXMEMORY_SAMPLES` keys, so evicting a key costs the same however large the keyspace is. Under
+/// `noeviction`, or when no limit is configured, nothing is evicted.
+///
+/// # Arguments
+///
+/// * `db` - A reference to the shared database.
+/// * `args_cli` - A reference to the command-line arguments.
+///
+/// # Returns
+///
+/// Returns `true` if memory usage is within the limit (or no limit is configured), or `false` if it is
+/// still over the limit, in which case commands that may grow memory usage must be rejected.
+pub fn evict_if_needed(db: &Db, args_cli: &ArgsCli) -> bool {
+    if args_cli.maxmemory == 0 {
+        return true;
+    }
+    if args_cli.maxmemory_policy == MaxMemoryPolicy::Noeviction {
+        return used_memory() <= args_cli.maxmemory;
+    }
+
+    let mut db = db.lock().unwrap();
+    let mut rng = rand::thread_rng();
+    while used_memory() > args_cli.maxmemory {
+        match sample_victim(&db, &mut rng) {
+            Some(key) => {
+                db.swap_remove(&key);
+            }
+            None => break,
+        }
+    }
+    used_memory() <= args_cli.maxmemory
+}
+
+/// Picks the key to evict among `MAXMEMORY_SAMPLES` keys drawn at random from the whole keyspace.
+///
+/// Keys are drawn by position, which the keyspace can look up directly, so every key is equally likely
+/// to be sampled and drawing a sample costs the same however large the keyspace is.
+///
+/// # Arguments
+///
+/// * `db` - The keyspace.
+/// * `rng` - The source of the random positions.
+///
+/// # Returns
+///
+/// Returns the least frequently used key of the sample, or `None` if the keyspace is empty.
+fn sample_victim(db: &Keyspace, rng: &mut impl Rng) -> Option<Vec<u8>> {
+    if db.is_empty() {
+        return None;
+    }
+    (0..MAXMEMORY_SAMPLES)
+        .filter_map(|_| db.get_index(rng.gen_range(0..db.len())))
+        .min_by_key(|(_, redis_item)| redis_item.get_frequency())
+        .map(|(key, _)| key.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::server::redis_item::RedisItem;
+
+    /// Builds a keyspace of `count` keys accessed with the given frequency.
+    fn keyspace(count: usize, frequency: u8) -> Keyspace {
+        (0..count).map(|index| {
+            let mut redis_item = RedisItem::new(b"value".to_vec());
+            redis_item.set_frequency(frequency);
+            (format!("key:{}", index).into_bytes(), redis_item)
+        }).collect()
+    }
+
+    #[test]
+    fn evicts_the_least_frequently_used_key_of_the_sample() {
+        let mut db = keyspace(MAXMEMORY_SAMPLES - 1, 1);
+        let mut hot = RedisItem::new(b"value".to_vec());
+        hot.set_frequency(200);
+        db.insert(b"hot".to_vec(), hot);
+
+        // The hot key could only be picked if it were the only key sampled, five times in a row.
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            assert_ne!(sample_victim(&db, &mut rng), Some(b"hot".to_vec()));
+        }
+        assert_eq!(sample_victim(&Keyspace::new(), &mut rng), None);
+    }
+
+    #[test]
+    fn samples_are_drawn_from_the_whole_keyspace() {
+        let mut db = keyspace(10_000, 200);
+        let mut cold = RedisItem::new(b"value".to_vec());
+        cold.set_frequency(1);
+        db.insert(b"cold".to_vec(), cold);  // The last of 10,001 keys, far past the first 1024.
+
+        let mut rng = StdRng::seed_from_u64(7);
+        while db.contains_key(b"cold".as_slice()) {
+            let victim = sample_victim(&db, &mut rng).unwrap();
+            db.swap_remove(&victim);
+        }
+        assert!(db.len() > 5_000, "{} keys were evicted before the cold one", 10_000 - db.len());
+    }
+}
@@ -6,3 +6,15 @@ pub mod arg_handler;
 pub mod rdb_parser;
 pub mod client_handler;
 pub mod common_variables;
+pub mod memory;
+pub mod server_state;
+pub mod active_expire;
+pub mod rdb_writer;
+pub mod snapshot;
+pub mod config_file;
+pub mod command_table;
+pub mod info;
+pub mod monitor;
+pub mod slowlog;
+pub mod latency;
+pub mod glob;
@@ -0,0 +1,82 @@
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::server::command_table::lookup_command;
+use crate::server::common_variables::MONITOR_COMMAND;
+use crate::server::resp_response::RespResponse;
+use crate::server::server_state::SharedState;
+
+/// Publishes a received command to every connection in MONITOR mode.
+///
+/// Like Redis, unknown commands, administrative commands and MONITOR itself are not shown. Nothing is
+/// formatted when no client is monitoring.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state holding the monitor channel.
+/// * `addr` - The address of the client that sent the command.
+/// * `command` - The command name as sent by the client.
+/// * `args` - The full command, including its name.
+pub fn publish_command(state: &SharedState, addr: &SocketAddr, command: &str, args: &[RespResponse]) {
+    if state.monitor.receiver_count() == 0 || command.eq_ignore_ascii_case(MONITOR_COMMAND) {
+        return;
+    }
+    match lookup_command(command) {
+        Some(spec) if !spec.flags.contains(&"admin") => {
+            // Sending only fails when every monitor disconnected in the meantime, which is fine.
+            let _ = state.monitor.send(format_monitor_line(addr, args));
+        }
+        _ => {}
+    }
+}
+
+/// Formats a command the way MONITOR reports it: `<unix time> [<db> <addr>] "arg" "arg" ...`.
+///
+/// # Arguments
+///
+/// * `addr` - The address of the client that sent the command.
+/// * `args` - The full command, including its name.
+///
+/// # Returns
+///
+/// Returns the formatted line, without a trailing CRLF.
+///
+/// # Examples
+///
+/// ```
+/// let line = format_monitor_line(&addr, &args);
+/// // 1339518083.107412 [0 127.0.0.1:60866] "set" "foo" "bar"
+/// ```
+fn format_monitor_line(addr: &SocketAddr, args: &[RespResponse]) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let quoted: Vec<String> = args.iter().map(|arg| quote_argument(&arg.get_bytes())).collect();
+    format!("{}.{:06} [0 {}] {}", now.as_secs(), now.subsec_micros(), addr, quoted.join(" "))
+}
+
+/// Quotes an argument, escaping quotes, backslashes and non-printable bytes.
+///
+/// # Arguments
+///
+/// * `bytes` - The raw bytes of the argument.
+///
+/// # Returns
+///
+/// Returns the argument wrapped in double quotes.
+fn quote_argument(bytes: &[u8]) -> String {
+    let mut quoted = String::from("\"");
+    for &byte in bytes {
+        match byte {
+            b'\\' => quoted.push_str("\\\\"),
+            b'"' => quoted.push_str("\\\""),
+            b'\n' => quoted.push_str("\\n"),
+            b'\r' => quoted.push_str("\\r"),
+            b'\t' => quoted.push_str("\\t"),
+            0x07 => quoted.push_str("\\a"),
+            0x08 => quoted.push_str("\\b"),
+            byte if byte.is_ascii_graphic() || byte == b' ' => quoted.push(byte as char),
+            byte => quoted.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -1,13 +1,12 @@
-use std::collections::HashMap;
 use std::fs::File;
-use std::io::Read;
-use std::path::Path;
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use anyhow::anyhow;
+use anyhow::{anyhow, Context};
 
 use crate::server::arg_handler::ArgsCli;
-use crate::server::common_variables::{Db, EXPIRE_IN_MILLISECONDS, EXPIRE_IN_SECONDS, HASH_TABLE_SELECTOR, VALUE_TYPE_STRING};
+use crate::server::common_variables::{AUX_FIELD, BAD_DATA_FORMAT_ERR_STR, CRC64_POLY, Db, DUMP_PAYLOAD_ERR_STR, END_OF_FILE, EXPIRE_IN_MILLISECONDS, EXPIRE_IN_SECONDS, HASH_TABLE_SELECTOR, Keyspace, RDB_FOOTER_LEN, RDB_HEADER_LEN, RDB_MAGIC, RDB_MAX_VERSION, SELECT_DB, VALUE_TYPE_STRING};
 use crate::server::redis_item::RedisItem;
 
 /// `RdbParser` is responsible for parsing the RDB file and populating the in-memory database.
@@ -15,6 +14,7 @@ use crate::server::redis_item::RedisItem;
 pub struct RdbParser {
     dir: String,
     db_filname: String,
+    strict: bool,
     db: Db,
 }
 
@@ -33,33 +33,56 @@ impl RdbParser {
         RdbParser {
             dir: args_cli.dir.clone().unwrap(),
             db_filname: args_cli.dbfilename.clone().unwrap(),
-            db: Arc::new(Mutex::new(HashMap::new())),
+            strict: args_cli.rdb_strict,
+            db: Arc::new(Mutex::new(Keyspace::new())),
         }
     }
 
 
     /// Populates the database by reading and parsing the RDB file.
     ///
+    /// A missing file is not an error: the server starts with an empty database. Any other I/O error
+    /// (e.g. permission denied) is logged and also yields an empty database, unless `--rdb-strict` is
+    /// set, in which case it is returned as an error naming the full path.
+    ///
     /// # Returns
     ///
-    /// Returns the populated database wrapped in `Result`, or an error if the file couldn't be read or parsed.
+    /// Returns the populated database wrapped in `Result`, or an error if the file couldn't be read (strict mode) or parsed.
     pub fn populate_database(self) -> Result<Db, anyhow::Error> {
         let file_contents = match read_file(self.dir.as_str(), self.db_filname.as_str()) {
             Ok(contents) => contents,
-            Err(_) => {
+            Err(e) if is_not_found(&e) => {
+                println!("No RDB file found at {}, starting with an empty database", rdb_path(&self.dir, &self.db_filname).display());
+                return Ok(self.db)
+            }
+            Err(e) if self.strict => return Err(e),
+            Err(e) => {
+                eprintln!("Warning: {:#}, starting with an empty database", e);
                 return Ok(self.db)
             }
         };
 
         match parse_rdb_file(file_contents) {
             Ok(db) => Ok(db),
-            Err(e) => {
-                return Err(anyhow!("Could not parse the file! {:?}", e))
-            }
+            Err(e) => Err(anyhow!("Could not parse the file! {:?}", e)),
         }
     }
 }
 
+/// Builds the full path of the RDB file from the directory and filename.
+///
+/// # Arguments
+///
+/// * `dir` - Directory where the file is located.
+/// * `db_filename` - Name of the file.
+///
+/// # Returns
+///
+/// Returns the joined path.
+pub fn rdb_path(dir: &str, db_filename: &str) -> PathBuf {
+    Path::new(dir).join(db_filename)
+}
+
 /// Reads the contents of the specified file.
 ///
 /// # Arguments
@@ -69,17 +92,35 @@ impl RdbParser {
 ///
 /// # Returns
 ///
-/// Returns a vector of bytes wrapped in `Result`, or an error if the file could not be read.
+/// Returns a vector of bytes wrapped in `Result`, or an error naming the full path if the file could not be read.
 pub fn read_file(dir: &str, db_filename: &str) -> Result<Vec<u8>, anyhow::Error> {
-    let full_path = Path::new(dir).join(db_filename);
-    let mut file = File::open(&full_path)?;
+    let full_path = rdb_path(dir, db_filename);
     let mut buffer = Vec::new();
-    file.read_to_end(&mut buffer)?;
+    File::open(&full_path)
+        .and_then(|mut file| file.read_to_end(&mut buffer))
+        .with_context(|| format!("Could not read RDB file {}", full_path.display()))?;
     Ok(buffer)
 }
 
+/// Checks whether an error returned by `read_file` means the file does not exist.
+///
+/// # Arguments
+///
+/// * `error` - The error returned by `read_file`.
+///
+/// # Returns
+///
+/// Returns `true` if the underlying I/O error is `NotFound`.
+fn is_not_found(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<std::io::Error>()
+        .is_some_and(|io_error| io_error.kind() == ErrorKind::NotFound)
+}
+
 /// Parses the contents of an RDB file and returns the populated database.
 ///
+/// The header is validated first. Auxiliary fields, database selectors and resize hints are skipped.
+/// An expiry opcode applies to the key-value pair that directly follows it.
+///
 /// # Arguments
 ///
 /// * `contents` - Byte content of the RDB file.
@@ -88,62 +129,134 @@ pub fn read_file(dir: &str, db_filename: &str) -> Result<Vec<u8>, anyhow::Error>
 ///
 /// Returns the populated database wrapped in `Result`, or an error if parsing fails.
 fn parse_rdb_file(contents: Vec<u8>) -> Result<Db, anyhow::Error> {
-    let mut db = HashMap::new();
-    let mut pos;
-
-    pos = skip_header_metadata(&contents);
+    let mut db = Keyspace::new();
+    let (version, mut pos) = read_header(&contents)?;
+    println!("Loading RDB produced by format version {}", version);
     let mut current_expiry: Option<SystemTime> = None;
-    let mut global_key: Option<String> = None;
-    let mut global_value: Option<String> = None;
 
     while pos < contents.len() {
         match contents[pos] {
             VALUE_TYPE_STRING => {
-                pos += 1;
-                let (key, new_pos) = get_decoded_string(&contents, pos)?;
+                let (key, new_pos) = get_decoded_bytes(&contents, pos + 1)?;
+                let (value, new_pos) = get_decoded_bytes(&contents, new_pos)?;
                 pos = new_pos;
-                let (value, new_pos) = get_decoded_string(&contents, pos)?;
-                pos = new_pos;
-
-                global_key = Some(key);
-                global_value = Some(value);
 
-                if contents[pos] == EXPIRE_IN_MILLISECONDS || contents[pos] == EXPIRE_IN_SECONDS {
-                    continue;
-                }
+                let redis_item = match current_expiry.take() {
+                    Some(expiry) => RedisItem::new_with_expiration(value, expiry),
+                    None => RedisItem::new(value),
+                };
+                db.insert(key, redis_item);
             }
             EXPIRE_IN_MILLISECONDS => {
-                pos += 1;
-                let (expiry, new_pos) = get_decoded_expiry_time_ms(&contents, pos)?;
+                let (expiry, new_pos) = get_decoded_expiry_time_ms(&contents, pos + 1)?;
                 current_expiry = Some(expiry);
                 pos = new_pos;
             }
             EXPIRE_IN_SECONDS => {
-                pos += 1;
-                let (expiry, new_pos) = get_decoded_expiry_time_seconds(&contents, pos)?;
+                let (expiry, new_pos) = get_decoded_expiry_time_seconds(&contents, pos + 1)?;
                 current_expiry = Some(expiry);
                 pos = new_pos;
             }
+            AUX_FIELD => {
+                let (_, new_pos) = get_decoded_bytes(&contents, pos + 1)?;
+                let (_, new_pos) = get_decoded_bytes(&contents, new_pos)?;
+                pos = new_pos;
+            }
+            SELECT_DB => {
+                let (_, new_pos) = get_decoded_length(&contents, pos + 1)?;
+                pos = new_pos;
+            }
+            HASH_TABLE_SELECTOR => {
+                let (_, new_pos) = get_decoded_length(&contents, pos + 1)?;
+                let (_, new_pos) = get_decoded_length(&contents, new_pos)?;
+                pos = new_pos;
+            }
+            END_OF_FILE => break,
             _ => {
                 pos += 1;
             }
         }
+    }
 
-        if let (Some(key), Some(value)) = (global_key.take(), global_value.take()) {
-            let redis_item = if let Some(expiry) = current_expiry.take() {
-                RedisItem::new_with_expiration(value, expiry)
-            } else {
-                RedisItem::new(value)
-            };
+    Ok(Arc::new(Mutex::new(db)))
+}
 
-            db.insert(key.clone(), redis_item);
-        }
+/// Computes the CRC-64 checksum (Jones polynomial, reflected) that Redis uses for RDB files and DUMP payloads.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes to checksum.
+///
+/// # Returns
+///
+/// Returns the checksum, which is stored little-endian.
+pub fn crc64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0u64, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u64, |crc, _| {
+            if crc & 1 == 1 { (crc >> 1) ^ CRC64_POLY } else { crc >> 1 }
+        })
+    })
+}
+
+/// Appends the footer shared by DUMP payloads: the 2-byte little-endian RDB version followed by the
+/// 8-byte little-endian CRC-64 of everything before it.
+///
+/// # Arguments
+///
+/// * `version` - The RDB version the payload was encoded with.
+/// * `payload` - The serialized value.
+///
+/// # Returns
+///
+/// Returns the payload with its footer appended.
+///
+/// # Examples
+///
+/// ```
+/// let dumped = rdb_footer(RDB_VERSION, b"\x00\x03bar");
+/// assert_eq!(dumped.len(), 5 + RDB_FOOTER_LEN);
+/// ```
+pub fn rdb_footer(version: u16, payload: &[u8]) -> Vec<u8> {
+    let mut dumped = payload.to_vec();
+    dumped.extend_from_slice(&version.to_le_bytes());
+    let checksum = crc64(&dumped);
+    dumped.extend_from_slice(&checksum.to_le_bytes());
+    dumped
+}
+
+/// Decodes a DUMP payload produced by `rdb_footer` back into the value it holds.
+///
+/// The footer is checked first: the RDB version must be one this server can read and the checksum must
+/// match. Only string values are supported.
+///
+/// # Arguments
+///
+/// * `dumped` - The serialized value followed by its footer.
+///
+/// # Returns
+///
+/// Returns the bytes of the value, or an error whose message is the reply to send to the client.
+pub fn decode_dump_payload(dumped: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    let Some(payload_len) = dumped.len().checked_sub(RDB_FOOTER_LEN) else {
+        return Err(anyhow!(DUMP_PAYLOAD_ERR_STR));
+    };
+    let version = u16::from_le_bytes([dumped[payload_len], dumped[payload_len + 1]]);
+    let checksum = u64::from_le_bytes(dumped[payload_len + 2..].try_into()?);
+    if version as u32 > RDB_MAX_VERSION || crc64(&dumped[..payload_len + 2]) != checksum {
+        return Err(anyhow!(DUMP_PAYLOAD_ERR_STR));
     }
 
-    Ok(Arc::new(Mutex::new(db)))
+    let payload = &dumped[..payload_len];
+    match payload.first() {
+        Some(&VALUE_TYPE_STRING) => match get_decoded_bytes(payload, 1) {
+            Ok((value, end)) if end == payload.len() => Ok(value),
+            _ => Err(anyhow!(BAD_DATA_FORMAT_ERR_STR)),
+        },
+        _ => Err(anyhow!(BAD_DATA_FORMAT_ERR_STR)),
+    }
 }
 
-/// Skips the header metadata of the RDB file and returns the position of the first data byte.
+/// Validates the header of the RDB file: the `REDIS` magic string followed by a 4-digit ASCII version.
 ///
 /// # Arguments
 ///
@@ -151,35 +264,78 @@ fn parse_rdb_file(contents: Vec<u8>) -> Result<Db, anyhow::Error> {
 ///
 /// # Returns
 ///
-/// Returns the position of the first data byte.
-fn skip_header_metadata(contents: &[u8]) -> usize {
-    let mut pos: usize = 9;
+/// Returns the RDB version and the position of the first byte after the header wrapped in `Result`,
+/// or an error if the magic string is wrong or the version is missing or unsupported.
+fn read_header(contents: &[u8]) -> Result<(u32, usize), anyhow::Error> {
+    let header = take_bytes(contents, 0, RDB_HEADER_LEN)
+        .map_err(|_| anyhow!("RDB file is too short to contain a header"))?;
+    let (magic, version) = header.split_at(RDB_MAGIC.len());
 
-    while pos < contents.len() {
-        match contents[pos] {
-            HASH_TABLE_SELECTOR => {
-                pos += 3;
-                match contents[pos] {
-                    EXPIRE_IN_MILLISECONDS => {
-                        pos += 1;
-                        let (_, new_pos) = get_decoded_expiry_time_ms(&contents, pos).unwrap();
-                        pos = new_pos;
-                    }
-                    EXPIRE_IN_SECONDS => {
-                        pos += 1;
-                        let (_, new_pos) = get_decoded_expiry_time_seconds(&contents, pos).unwrap();
-                        pos = new_pos;
-                    }
-                    _ => {}
-                }
-                return pos;
-            }
-            _ => pos += 1
-        }
+    if magic != RDB_MAGIC {
+        return Err(anyhow!("Wrong signature trying to load DB from file"));
     }
-    pos
+    let version: u32 = std::str::from_utf8(version)
+        .ok()
+        .filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|digits| digits.parse().ok())
+        .ok_or_else(|| anyhow!("Invalid RDB version {:?}", String::from_utf8_lossy(version)))?;
+    if !(1..=RDB_MAX_VERSION).contains(&version) {
+        return Err(anyhow!("Can't handle RDB format version {}", version));
+    }
+
+    Ok((version, RDB_HEADER_LEN))
 }
 
+/// Returns `len` bytes of the RDB file contents starting at `pos`.
+///
+/// # Arguments
+///
+/// * `contents` - Byte content of the RDB file.
+/// * `pos` - Current position in the byte content.
+/// * `len` - Number of bytes to take.
+///
+/// # Returns
+///
+/// Returns the requested bytes, or an error if the file is truncated.
+fn take_bytes(contents: &[u8], pos: usize, len: usize) -> Result<&[u8], anyhow::Error> {
+    pos.checked_add(len)
+        .and_then(|end| contents.get(pos..end))
+        .ok_or_else(|| anyhow!("Unexpected end of RDB file at byte {}", pos))
+}
+
+/// Decodes a length-encoded value from the RDB file contents.
+///
+/// The two most significant bits of the first byte select the encoding: a 6-bit length, a 14-bit
+/// length, a 32/64-bit big-endian length, or a special string encoding (returned as `RdbLength::Encoded`).
+///
+/// # Arguments
+///
+/// * `contents` - Byte content of the RDB file.
+/// * `pos` - Current position in the byte content.
+///
+/// # Returns
+///
+/// Returns the decoded length and the new position wrapped in `Result`, or an error if decoding fails.
+fn get_decoded_length(contents: &[u8], pos: usize) -> Result<(RdbLength, usize), anyhow::Error> {
+    let first = take_bytes(contents, pos, 1)?[0];
+    match first >> 6 {
+        0b00 => Ok((RdbLength::Length((first & 0x3F) as usize), pos + 1)),
+        0b01 => {
+            let next = take_bytes(contents, pos + 1, 1)?[0];
+            Ok((RdbLength::Length((((first & 0x3F) as usize) << 8) | next as usize), pos + 2))
+        }
+        0b10 if first == 0x80 => {
+            let bytes = take_bytes(contents, pos + 1, 4)?;
+            Ok((RdbLength::Length(u32::from_be_bytes(bytes.try_into()?) as usize), pos + 5))
+        }
+        0b10 if first == 0x81 => {
+            let bytes = take_bytes(contents, pos + 1, 8)?;
+            Ok((RdbLength::Length(u64::from_be_bytes(bytes.try_into()?) as usize), pos + 9))
+        }
+        0b11 => Ok((RdbLength::Encoded(first & 0x3F), pos + 1)),
+        _ => Err(anyhow!("Unsupported length encoding 0x{:02X}", first)),
+    }
+}
 
 /// Decodes an expiry time in milliseconds from the RDB file contents.
 ///
@@ -225,7 +381,9 @@ fn get_decoded_expiry_time_seconds(contents: &[u8], pos: usize) -> Result<(Syste
 }
 
 
-/// Decodes a string from the RDB file contents.
+/// Decodes a binary-safe string from the RDB file contents.
+///
+/// Length-prefixed strings are returned as-is; integer-encoded strings are converted to their decimal representation.
 ///
 /// # Arguments
 ///
@@ -234,13 +392,116 @@ fn get_decoded_expiry_time_seconds(contents: &[u8], pos: usize) -> Result<(Syste
 ///
 /// # Returns
 ///
-/// Returns the decoded string and the new position wrapped in `Result`, or an error if decoding fails.
-fn get_decoded_string(contents: &[u8], pos: usize) -> Result<(String, usize), std::string::FromUtf8Error> {
-    let string_size = contents[pos] as usize;
-    let string_slice = &contents[pos + 1..pos + 1 + string_size];
-
-    let decoded_string = String::from_utf8(string_slice.to_vec())?;
+/// Returns the decoded bytes and the new position wrapped in `Result`, or an error if decoding fails.
+fn get_decoded_bytes(contents: &[u8], pos: usize) -> Result<(Vec<u8>, usize), anyhow::Error> {
+    let (length, pos) = get_decoded_length(contents, pos)?;
+    let (value, size) = match length {
+        RdbLength::Length(size) => (take_bytes(contents, pos, size)?.to_vec(), size),
+        RdbLength::Encoded(0) => ((take_bytes(contents, pos, 1)?[0] as i8).to_string().into_bytes(), 1),
+        RdbLength::Encoded(1) => (i16::from_le_bytes(take_bytes(contents, pos, 2)?.try_into()?).to_string().into_bytes(), 2),
+        RdbLength::Encoded(2) => (i32::from_le_bytes(take_bytes(contents, pos, 4)?.try_into()?).to_string().into_bytes(), 4),
+        RdbLength::Encoded(encoding) => return Err(anyhow!("Unsupported string encoding {}", encoding)),
+    };
+    Ok((value, pos + size))
+}
 
-    Ok((decoded_string, pos + 1 + string_size))
+/// `RdbLength` is the result of decoding a length-encoded value.
+enum RdbLength {
+    Length(usize),   // A plain length.
+    Encoded(u8),     // A special string encoding (0-2: 8/16/32-bit integer, 3: LZF-compressed).
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::command_handler::execute_command;
+    use crate::server::command_handler::tests::setup;
+    use crate::server::common_variables::RDB_VERSION;
+    use crate::server::rdb_writer::{encode_database, encode_dump_payload};
+
+    #[test]
+    fn crc64_matches_redis() {
+        assert_eq!(crc64(b"123456789"), 0xe9c6d914c4b8d9ca);  // The check value from Redis's crc64 test.
+        assert_eq!(crc64(b""), 0);
+    }
+
+    #[test]
+    fn lengths_decode_every_width() {
+        let length = |contents: &[u8]| match get_decoded_length(contents, 0).unwrap() {
+            (RdbLength::Length(length), pos) => (length, pos),
+            (RdbLength::Encoded(_), _) => panic!("not a plain length"),
+        };
+
+        assert_eq!(length(&[0x3F]), (63, 1));
+        assert_eq!(length(&[0x7F, 0xFF]), (16_383, 2));
+        assert_eq!(length(&[0x80, 0x00, 0x00, 0x40, 0x00]), (16_384, 5));
+        assert_eq!(length(&[0x81, 0x00, 0x00, 0x00, 0x01, 0x40, 0x00, 0x00, 0x00]), (5 << 30, 9));
+    }
+
+    #[test]
+    fn dump_payloads_round_trip() {
+        let value = b"binary \x00\r\n value".to_vec();
+        let dumped = encode_dump_payload(&RedisItem::new(value.clone()));
+        assert_eq!(decode_dump_payload(&dumped).unwrap(), value);
+    }
+
+    #[test]
+    fn dump_payloads_are_checked() {
+        let dumped = encode_dump_payload(&RedisItem::new(b"value".to_vec()));
+        let error = |dumped: &[u8]| decode_dump_payload(dumped).unwrap_err().to_string();
+
+        let mut corrupted = dumped.clone();
+        corrupted[3] ^= 1;
+        assert_eq!(error(&corrupted), DUMP_PAYLOAD_ERR_STR);
+        assert_eq!(error(&dumped[..RDB_FOOTER_LEN - 1]), DUMP_PAYLOAD_ERR_STR);
+
+        let newer = rdb_footer(RDB_MAX_VERSION as u16 + 1, &dumped[..dumped.len() - RDB_FOOTER_LEN]);
+        assert_eq!(error(&newer), DUMP_PAYLOAD_ERR_STR);
+
+        let trailing_garbage = rdb_footer(RDB_VERSION, &[&dumped[..dumped.len() - RDB_FOOTER_LEN], b"x"].concat());
+        assert_eq!(error(&trailing_garbage), BAD_DATA_FORMAT_ERR_STR);
+    }
+
+    #[test]
+    fn the_footer_holds_the_version_and_checksum() {
+        let dumped = rdb_footer(RDB_VERSION, b"\x00\x03bar");
+        assert_eq!(dumped.len(), 5 + RDB_FOOTER_LEN);
+        assert_eq!(&dumped[..5], b"\x00\x03bar");
+        assert_eq!(dumped[5..7], RDB_VERSION.to_le_bytes());
+        assert_eq!(dumped[7..], crc64(&dumped[..7]).to_le_bytes());
+    }
+
+    #[test]
+    fn restore_accepts_what_dump_produces() {
+        let (db, args_cli) = setup(&[]);
+        execute_command(&db, &args_cli, b"SET k \"binary \\x00 value\"");
+        let dumped = execute_command(&db, &args_cli, b"DUMP k");
+        let payload = &dumped[dumped.iter().position(|&byte| byte == b'\n').unwrap() + 1..dumped.len() - 2];
+
+        let restore = [format!("*4\r\n$7\r\nRESTORE\r\n$2\r\nk2\r\n$1\r\n0\r\n${}\r\n", payload.len()).as_bytes(), payload, b"\r\nGET k2"].concat();
+        assert_eq!(execute_command(&db, &args_cli, &restore), b"+OK\r\n$14\r\nbinary \x00 value\r\n");
+    }
+
+    #[test]
+    fn rdb_files_round_trip() {
+        let expiration = SystemTime::now() + Duration::from_secs(3600);
+        let keyspace = Keyspace::from([
+            (b"plain".to_vec(), RedisItem::new(b"value".to_vec())),
+            (b"volatile".to_vec(), RedisItem::new_with_expiration(b"x".repeat(20_000), expiration)),
+            (b"expired".to_vec(), RedisItem::new_with_expiration(b"gone".to_vec(), SystemTime::now() - Duration::from_secs(1))),
+        ]);
+
+        let contents = encode_database(&keyspace);
+        assert_eq!(u64::from_le_bytes(contents[contents.len() - 8..].try_into().unwrap()), crc64(&contents[..contents.len() - 8]));
+
+        let db = parse_rdb_file(contents).unwrap();
+        let db = db.lock().unwrap();
+        assert_eq!(db.len(), 2);
+        assert_eq!(db[b"plain".as_slice()].get_data(), b"value");
+        assert_eq!(db[b"plain".as_slice()].get_expiration(), None);
+        assert_eq!(db[b"volatile".as_slice()].get_data().len(), 20_000);
+        let restored = db[b"volatile".as_slice()].get_expiration().unwrap();
+        let drift = restored.duration_since(expiration).unwrap_or_else(|error| error.duration());
+        assert!(drift < Duration::from_secs(1));
+    }
+}
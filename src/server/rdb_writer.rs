@@ -0,0 +1,148 @@
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Context;
+
+use crate::server::common_variables::{END_OF_FILE, EXPIRE_IN_MILLISECONDS, HASH_TABLE_SELECTOR, Keyspace, RDB_MAGIC, RDB_VERSION, SELECT_DB, VALUE_TYPE_STRING};
+use crate::server::rdb_parser::{crc64, rdb_footer};
+use crate::server::redis_item::RedisItem;
+
+/// Encodes the keyspace into the RDB format understood by `RdbParser`.
+///
+/// Expired items are skipped. The file ends with the CRC-64 checksum of everything before it.
+///
+/// # Arguments
+///
+/// * `db` - The keyspace to encode.
+///
+/// # Returns
+///
+/// Returns the bytes of the RDB file.
+pub fn encode_database(db: &Keyspace) -> Vec<u8> {
+    let live: Vec<(&Vec<u8>, &RedisItem)> = db.iter()
+        .filter(|(_, redis_item)| !redis_item.is_expired())
+        .collect();
+    let expires = live.iter().filter(|(_, redis_item)| redis_item.get_expiration().is_some()).count();
+
+    let mut contents = RDB_MAGIC.to_vec();
+    contents.extend_from_slice(format!("{:04}", RDB_VERSION).as_bytes());
+    contents.extend_from_slice(&[SELECT_DB, 0x00]);
+    contents.push(HASH_TABLE_SELECTOR);
+    encode_length(&mut contents, live.len());
+    encode_length(&mut contents, expires);
+
+    for (key, redis_item) in live {
+        if let Some(expiration) = redis_item.get_expiration() {
+            let millis = expiration.duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0);
+            contents.push(EXPIRE_IN_MILLISECONDS);
+            contents.extend_from_slice(&millis.to_le_bytes());
+        }
+        contents.push(VALUE_TYPE_STRING);
+        encode_string(&mut contents, key);
+        encode_string(&mut contents, redis_item.get_data());
+    }
+
+    contents.push(END_OF_FILE);
+    let checksum = crc64(&contents);
+    contents.extend_from_slice(&checksum.to_le_bytes());
+    contents
+}
+
+/// Serializes a single value the way DUMP returns it: the RDB-encoded value followed by the version and checksum footer.
+///
+/// # Arguments
+///
+/// * `redis_item` - The item whose value is serialized.
+///
+/// # Returns
+///
+/// Returns the DUMP payload, which RESTORE accepts.
+pub fn encode_dump_payload(redis_item: &RedisItem) -> Vec<u8> {
+    let mut payload = vec![VALUE_TYPE_STRING];
+    encode_string(&mut payload, redis_item.get_data());
+    rdb_footer(RDB_VERSION, &payload)
+}
+
+/// Writes the RDB file atomically: the contents go to a temporary file that is then renamed over the target.
+///
+/// # Arguments
+///
+/// * `path` - The path of the RDB file.
+/// * `contents` - The bytes produced by `encode_database`.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the file was written, or an error naming the path if it could not be.
+pub fn write_rdb_file(path: &Path, contents: &[u8]) -> Result<(), anyhow::Error> {
+    let temp_path = path.with_extension(format!("tmp-{}", std::process::id()));
+    fs::write(&temp_path, contents)
+        .and_then(|_| fs::rename(&temp_path, path))
+        .with_context(|| format!("Could not write RDB file {}", path.display()))
+}
+
+/// Returns the number of bytes a value takes once encoded as an RDB string.
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes of the value.
+///
+/// # Returns
+///
+/// Returns the serialized length, including the length prefix.
+pub fn serialized_length(bytes: &[u8]) -> usize {
+    let mut contents = Vec::new();
+    encode_length(&mut contents, bytes.len());
+    contents.len() + bytes.len()
+}
+
+/// Appends a length-encoded value (6-bit, 14-bit, 32-bit or 64-bit) to the buffer.
+///
+/// # Arguments
+///
+/// * `contents` - The buffer to append to.
+/// * `length` - The length to encode.
+fn encode_length(contents: &mut Vec<u8>, length: usize) {
+    if length < 1 << 6 {
+        contents.push(length as u8);
+    } else if length < 1 << 14 {
+        contents.extend_from_slice(&[0x40 | (length >> 8) as u8, length as u8]);
+    } else if let Ok(length) = u32::try_from(length) {
+        contents.push(0x80);
+        contents.extend_from_slice(&length.to_be_bytes());
+    } else {
+        contents.push(0x81);  // Lengths past 4GB take the 64-bit form, like Redis.
+        contents.extend_from_slice(&(length as u64).to_be_bytes());
+    }
+}
+
+/// Appends a length-prefixed string to the buffer.
+///
+/// # Arguments
+///
+/// * `contents` - The buffer to append to.
+/// * `bytes` - The bytes of the string.
+fn encode_string(contents: &mut Vec<u8>, bytes: &[u8]) {
+    encode_length(contents, bytes.len());
+    contents.extend_from_slice(bytes);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lengths_take_the_smallest_encoding_that_fits() {
+        let encoded = |length: usize| {
+            let mut contents = Vec::new();
+            encode_length(&mut contents, length);
+            contents
+        };
+
+        assert_eq!(encoded(63), [0x3F]);
+        assert_eq!(encoded(16_383), [0x7F, 0xFF]);
+        assert_eq!(encoded(16_384), [0x80, 0x00, 0x00, 0x40, 0x00]);
+        assert_eq!(encoded(5 << 30), [0x81, 0x00, 0x00, 0x00, 0x01, 0x40, 0x00, 0x00, 0x00]);  // Not truncated to 32 bits.
+    }
+}
@@ -1,10 +1,33 @@
-use std::time::SystemTime;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use rand::Rng;
+
+use crate::server::common_variables::{EMBSTR_SIZE_LIMIT, ENCODING_EMBSTR, ENCODING_INT, ENCODING_RAW, LFU_DECAY_TIME_MINUTES, LFU_INIT_VAL, LFU_LOG_FACTOR};
 
 /// Represents an item in a Redis-like database with optional expiration.
-#[derive(Debug)]
+///
+/// The expiration is kept both as the absolute wall-clock `SystemTime` (used for reporting, e.g. by
+/// EXPIRETIME) and as a monotonic `Instant` deadline, which is what expiry checks compare against so
+/// that a backward or forward jump of the system clock does not resurrect or prematurely drop keys.
+///
+/// Each item also carries a logarithmic access-frequency counter (as in Redis's LFU policy), which is
+/// bumped probabilistically on access and decays over time.
+///
+/// Like Redis, a value edited in place (APPEND, SETBIT) is reported with the `raw` encoding from then on,
+/// while values written whole are classified by their content.
+///
+/// The value is held behind an `Arc`, so replies can share it instead of copying it (see `get_shared_data`);
+/// an in-place edit only copies the bytes if a reply still holds on to them.
+#[derive(Debug, Clone)]
 pub struct RedisItem {
-    data: String,
+    data: Arc<Vec<u8>>,
     expiration: Option<SystemTime>,
+    deadline: Option<Instant>,
+    lfu_counter: u8,
+    lfu_decrement_time: Instant,
+    edited_in_place: bool,
+    idle_offset_seconds: u64,
 }
 
 impl RedisItem {
@@ -12,7 +35,7 @@ impl RedisItem {
     ///
     /// # Arguments
     ///
-    /// * `data` - A `String` containing the data for the `RedisItem`.
+    /// * `data` - The bytes of the value for the `RedisItem`.
     ///
     /// # Returns
     ///
@@ -21,12 +44,17 @@ impl RedisItem {
     /// # Examples
     ///
     /// ```
-    /// let item = RedisItem::new("value".to_string());
+    /// let item = RedisItem::new(b"value".to_vec());
     /// ```
-    pub fn new(data: String) -> Self {
+    pub fn new(data: Vec<u8>) -> Self {
         RedisItem {
-            data,
+            data: Arc::new(data),
             expiration: None,
+            deadline: None,
+            lfu_counter: LFU_INIT_VAL,
+            lfu_decrement_time: Instant::now(),
+            edited_in_place: false,
+            idle_offset_seconds: 0,
         }
     }
 
@@ -34,7 +62,7 @@ impl RedisItem {
     ///
     /// # Arguments
     ///
-    /// * `data` - A `String` containing the data for the `RedisItem`.
+    /// * `data` - The bytes of the value for the `RedisItem`.
     /// * `expiration` - A `SystemTime` representing the expiration time for the `RedisItem`.
     ///
     /// # Returns
@@ -47,33 +75,277 @@ impl RedisItem {
     /// use std::time::{SystemTime, Duration};
     ///
     /// let expiration = SystemTime::now() + Duration::from_secs(60);
-    /// let item = RedisItem::new_with_expiration("value".to_string(), expiration);
+    /// let item = RedisItem::new_with_expiration(b"value".to_vec(), expiration);
     /// ```
-    pub fn new_with_expiration(data: String, expiration: SystemTime) -> Self {
+    pub fn new_with_expiration(data: Vec<u8>, expiration: SystemTime) -> Self {
         RedisItem {
-            data,
+            data: Arc::new(data),
             expiration: Some(expiration),
+            deadline: to_deadline(expiration),
+            lfu_counter: LFU_INIT_VAL,
+            lfu_decrement_time: Instant::now(),
+            edited_in_place: false,
+            idle_offset_seconds: 0,
         }
     }
 
-
     /// Checks whether the `RedisItem` has expired.
     ///
     /// # Returns
     ///
     /// Returns `true` if the current time is later than the expiration time, or `false` if the item has not expired or has no expiration set.
+    /// The comparison uses the monotonic deadline, so it is not affected by changes to the system clock.
     ///
     /// # Examples
     ///
     /// ```
-    /// let item = RedisItem::new("value".to_string());
+    /// let item = RedisItem::new(b"value".to_vec());
     /// assert!(!item.is_expired());
     /// ```
     pub fn is_expired(&self) -> bool {
-        if let Some(expiration_time) = self.expiration {
-            SystemTime::now() > expiration_time
+        match self.deadline {
+            Some(deadline) => Instant::now() > deadline,
+            None => false,
+        }
+    }
+
+    /// Checks whether the `RedisItem` expired more than `grace` ago.
+    ///
+    /// # Arguments
+    ///
+    /// * `grace` - How long the item must have been expired for.
+    ///
+    /// # Returns
+    ///
+    /// Returns `true` if the item has an expiration and the current time is later than it plus `grace`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let item = RedisItem::new_with_expiration(b"value".to_vec(), SystemTime::now());
+    /// assert!(!item.is_expired_for(Duration::from_secs(60)));
+    /// ```
+    pub fn is_expired_for(&self, grace: Duration) -> bool {
+        match self.deadline {
+            Some(deadline) => deadline.checked_add(grace).is_some_and(|deadline| Instant::now() > deadline),
+            None => false,
+        }
+    }
+
+    /// Sets a new expiration time on the `RedisItem`, replacing any existing one.
+    ///
+    /// # Arguments
+    ///
+    /// * `expiration` - A `SystemTime` representing the new expiration time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::{SystemTime, Duration};
+    ///
+    /// let mut item = RedisItem::new(b"value".to_vec());
+    /// item.set_expiration(SystemTime::now() + Duration::from_secs(60));
+    /// ```
+    pub fn set_expiration(&mut self, expiration: SystemTime) {
+        self.expiration = Some(expiration);
+        self.deadline = to_deadline(expiration);
+    }
+
+    /// Removes any expiration from the `RedisItem`, making it persistent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut item = RedisItem::new(b"value".to_vec());
+    /// item.persist();
+    /// assert!(!item.is_expired());
+    /// ```
+    pub fn persist(&mut self) {
+        self.expiration = None;
+        self.deadline = None;
+    }
+
+    /// Retrieves the absolute expiration time of the `RedisItem`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the expiration `SystemTime`, or `None` if the item has no expiration set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let item = RedisItem::new(b"value".to_vec());
+    /// assert!(item.get_expiration().is_none());
+    /// ```
+    pub fn get_expiration(&self) -> Option<SystemTime> {
+        self.expiration
+    }
+
+    /// Replaces the data stored in the `RedisItem` while keeping its expiration intact.
+    ///
+    /// In-place mutations (APPEND, INCR and friends) must go through this method rather than creating
+    /// a fresh `RedisItem`, so that the key's TTL survives the write, matching Redis.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - The new bytes of the value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut item = RedisItem::new(b"value".to_vec());
+    /// item.set_data(b"other".to_vec());
+    /// assert_eq!(item.get_data(), b"other");
+    /// ```
+    pub fn set_data(&mut self, data: Vec<u8>) {
+        self.data = Arc::new(data);
+        self.edited_in_place = false;
+    }
+
+    /// Retrieves mutable access to the data stored in the `RedisItem`, for in-place edits that keep the expiration.
+    ///
+    /// The value is reported with the `raw` encoding afterwards.
+    ///
+    /// # Returns
+    ///
+    /// Returns a mutable reference to the bytes of the value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut item = RedisItem::new(b"value".to_vec());
+    /// item.get_data_mut().push(b'!');
+    /// ```
+    pub fn get_data_mut(&mut self) -> &mut Vec<u8> {
+        self.edited_in_place = true;
+        Arc::make_mut(&mut self.data)
+    }
+
+    /// Records an access to the `RedisItem`, updating its access-frequency counter.
+    ///
+    /// The counter is first decayed by the time elapsed since the last access and then incremented
+    /// with a probability that shrinks as the counter grows, so it saturates logarithmically.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut item = RedisItem::new(b"value".to_vec());
+    /// item.touch();
+    /// ```
+    pub fn touch(&mut self) {
+        let counter = self.get_frequency();
+        self.lfu_counter = log_increment(counter);
+        self.lfu_decrement_time = Instant::now();
+        self.idle_offset_seconds = 0;
+    }
+
+    /// Retrieves the access-frequency counter of the `RedisItem`, decayed by the time since the last access.
+    ///
+    /// # Returns
+    ///
+    /// Returns the logarithmic access-frequency counter (0-255).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let item = RedisItem::new(b"value".to_vec());
+    /// assert_eq!(item.get_frequency(), 5);
+    /// ```
+    pub fn get_frequency(&self) -> u8 {
+        let elapsed_minutes = self.get_idle_seconds() / 60;
+        let periods = elapsed_minutes / LFU_DECAY_TIME_MINUTES;
+        self.lfu_counter.saturating_sub(periods.min(u8::MAX as u64) as u8)
+    }
+
+    /// Retrieves the number of seconds since the `RedisItem` was last accessed.
+    ///
+    /// # Returns
+    ///
+    /// Returns the idle time in seconds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let item = RedisItem::new(b"value".to_vec());
+    /// assert_eq!(item.get_idle_seconds(), 0);
+    /// ```
+    pub fn get_idle_seconds(&self) -> u64 {
+        self.lfu_decrement_time.elapsed().as_secs() + self.idle_offset_seconds
+    }
+
+    /// Sets the access-frequency counter of the `RedisItem`, as if it had just been accessed.
+    ///
+    /// Used by RESTORE to carry a migrated key's LFU state over from the source server.
+    ///
+    /// # Arguments
+    ///
+    /// * `frequency` - The logarithmic access-frequency counter (0-255).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut item = RedisItem::new(b"value".to_vec());
+    /// item.set_frequency(100);
+    /// assert_eq!(item.get_frequency(), 100);
+    /// ```
+    pub fn set_frequency(&mut self, frequency: u8) {
+        self.lfu_counter = frequency;
+        self.lfu_decrement_time = Instant::now();
+        self.idle_offset_seconds = 0;
+    }
+
+    /// Sets the number of seconds since the `RedisItem` was last accessed.
+    ///
+    /// Used by RESTORE to carry a migrated key's idle time over from the source server. The idle time is
+    /// kept as an offset rather than a backdated `Instant`, since the monotonic clock may not reach that
+    /// far back (it usually starts at boot).
+    ///
+    /// # Arguments
+    ///
+    /// * `idle_seconds` - The idle time in seconds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut item = RedisItem::new(b"value".to_vec());
+    /// item.set_idle_seconds(60);
+    /// assert_eq!(item.get_idle_seconds(), 60);
+    /// ```
+    pub fn set_idle_seconds(&mut self, idle_seconds: u64) {
+        self.lfu_decrement_time = Instant::now();
+        self.idle_offset_seconds = idle_seconds;
+    }
+
+    /// Classifies the value the way Redis reports it in `OBJECT ENCODING`.
+    ///
+    /// Values that are the canonical decimal form of a 64-bit integer are `int`, short strings (up to 44
+    /// bytes) are `embstr`, and longer strings are `raw`. Values edited in place are always `raw`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the name of the encoding.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let item = RedisItem::new(b"12345".to_vec());
+    /// assert_eq!(item.get_encoding(), "int");
+    /// ```
+    pub fn get_encoding(&self) -> &'static str {
+        if self.edited_in_place {
+            return ENCODING_RAW;
+        }
+        let is_int = std::str::from_utf8(&self.data)
+            .ok()
+            .and_then(|s| s.parse::<i64>().ok().map(|n| n.to_string() == s))
+            .unwrap_or(false);
+
+        if is_int {
+            ENCODING_INT
+        } else if self.data.len() <= EMBSTR_SIZE_LIMIT {
+            ENCODING_EMBSTR
         } else {
-            false
+            ENCODING_RAW
         }
     }
 
@@ -81,15 +353,147 @@ impl RedisItem {
     ///
     /// # Returns
     ///
-    /// Returns a reference to the `String` containing the data.
+    /// Returns a reference to the bytes of the value.
     ///
     /// # Examples
     ///
     /// ```
-    /// let item = RedisItem::new("value".to_string());
-    /// assert_eq!(item.get_data(), "value");
+    /// let item = RedisItem::new(b"value".to_vec());
+    /// assert_eq!(item.get_data(), b"value");
     /// ```
-    pub fn get_data(&self) -> &String {
+    pub fn get_data(&self) -> &Vec<u8> {
         &self.data
     }
+
+    /// Retrieves a shared handle to the data stored in the `RedisItem`, without copying the bytes.
+    ///
+    /// Replies of large values built from it skip the copy out of the database that `get_data().clone()` makes.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `Arc` pointing at the bytes of the value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let item = RedisItem::new(b"value".to_vec());
+    /// let reply = RespResponse::SharedBulkString(item.get_shared_data());
+    /// ```
+    pub fn get_shared_data(&self) -> Arc<Vec<u8>> {
+        Arc::clone(&self.data)
+    }
+}
+
+/// Logarithmically increments an access-frequency counter.
+///
+/// # Arguments
+///
+/// * `counter` - The current counter value.
+///
+/// # Returns
+///
+/// Returns the counter, incremented with probability `1 / ((counter - LFU_INIT_VAL) * LFU_LOG_FACTOR + 1)`.
+fn log_increment(counter: u8) -> u8 {
+    if counter == u8::MAX {
+        return counter;
+    }
+    let base = counter.saturating_sub(LFU_INIT_VAL) as f64;
+    let probability = 1.0 / (base * LFU_LOG_FACTOR + 1.0);
+    if rand::thread_rng().gen::<f64>() < probability {
+        counter + 1
+    } else {
+        counter
+    }
+}
+
+/// Converts an absolute wall-clock expiration time into a monotonic `Instant` deadline.
+///
+/// The remaining time is measured once against the current system clock. If the expiration time is
+/// already in the past (`duration_since` fails), the deadline is placed the same distance in the past,
+/// falling back to "now" if that cannot be represented, so the item is reported as expired.
+///
+/// # Arguments
+///
+/// * `expiration` - The absolute expiration time.
+///
+/// # Returns
+///
+/// Returns the `Instant` at which the item should be considered expired, or `None` if the expiration
+/// is too far in the future to be represented (the item then never expires in practice).
+fn to_deadline(expiration: SystemTime) -> Option<Instant> {
+    let now = Instant::now();
+    match expiration.duration_since(SystemTime::now()) {
+        Ok(remaining) => now.checked_add(remaining),
+        Err(elapsed) => Some(now.checked_sub(elapsed.duration()).unwrap_or(now)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expirations_slightly_in_the_past_have_expired() {
+        let past = SystemTime::now() - Duration::from_millis(1);
+        let redis_item = RedisItem::new_with_expiration(b"value".to_vec(), past);
+        assert!(redis_item.is_expired());
+        assert!(!redis_item.is_expired_for(Duration::from_secs(60)));
+        assert_eq!(redis_item.get_expiration(), Some(past));
+
+        let mut redis_item = RedisItem::new(b"value".to_vec());
+        redis_item.set_expiration(past);
+        assert!(redis_item.is_expired());
+        redis_item.persist();
+        assert!(!redis_item.is_expired());
+    }
+
+    #[test]
+    fn expirations_are_measured_once_on_the_monotonic_clock() {
+        let created = Instant::now();
+        let redis_item = RedisItem::new_with_expiration(b"value".to_vec(), SystemTime::now() + Duration::from_secs(60));
+        assert!(!redis_item.is_expired());
+
+        // Later changes to the system clock cannot move a deadline that is already an `Instant`.
+        let remaining = redis_item.deadline.unwrap().duration_since(created);
+        assert!(remaining > Duration::from_secs(59) && remaining <= Duration::from_secs(61), "remaining {:?}", remaining);
+
+        let past = SystemTime::now() - Duration::from_secs(1);
+        assert!(to_deadline(past).unwrap() < Instant::now());
+    }
+
+    #[test]
+    fn lfu_counter_starts_at_the_initial_value_and_grows_logarithmically() {
+        let mut redis_item = RedisItem::new(b"value".to_vec());
+        assert_eq!(redis_item.get_frequency(), LFU_INIT_VAL);
+
+        redis_item.touch();  // At the initial value, an access always counts.
+        assert_eq!(redis_item.get_frequency(), LFU_INIT_VAL + 1);
+
+        for _ in 0..1000 {
+            redis_item.touch();
+        }
+        let frequency = redis_item.get_frequency();
+        assert!(frequency > LFU_INIT_VAL + 5 && frequency < LFU_INIT_VAL + 40, "frequency {}", frequency);
+    }
+
+    #[test]
+    fn lfu_counter_saturates() {
+        assert_eq!(log_increment(u8::MAX), u8::MAX);
+        assert_eq!(log_increment(0), 1);  // Below the initial value the increment is certain.
+    }
+
+    #[test]
+    fn lfu_counter_decays_with_idle_time() {
+        let mut redis_item = RedisItem::new(b"value".to_vec());
+        redis_item.set_frequency(10);
+        redis_item.set_idle_seconds(3 * 60 * LFU_DECAY_TIME_MINUTES);
+        assert_eq!(redis_item.get_frequency(), 7);
+
+        redis_item.set_idle_seconds(u64::MAX / 2);
+        assert_eq!(redis_item.get_frequency(), 0);
+
+        redis_item.touch();  // An access restarts the idle time from the decayed counter.
+        assert_eq!(redis_item.get_idle_seconds(), 0);
+        assert_eq!(redis_item.get_frequency(), 1);
+    }
 }
@@ -1,52 +1,164 @@
+use std::fmt;
+use std::io::Write;
 use std::sync::Arc;
 use anyhow::Result;
-use crate::server::common_variables::{ASTERISK_, CRLF, DOLLAR_SIGN_CHAR, PLUS_CHAR};
+use crate::server::common_variables::{ASTERISK_, COLON_CHAR, CRLF, DOLLAR_SIGN_CHAR, MAX_MULTIBULK_LEN, MINUS_CHAR, PERCENT_CHAR, PLUS_CHAR, RESP2_PROTOCOL, RESP3_PROTOCOL};
 use crate::server::resp_response::RespResponse::SimpleString;
 
 /// `RespResponse` represents different types of Redis Serialization Protocol (RESP) responses.
 #[derive(Debug, Clone)]
 pub enum RespResponse {
     SimpleString(String),                   // A simple string response (e.g., "+OK\r\n").
-    BulkString(String),                     // A bulk string response (e.g., "$6\r\nfoobar\r\n").
+    BulkString(Vec<u8>),                    // A binary-safe bulk string response (e.g., "$6\r\nfoobar\r\n").
+    SharedBulkString(Arc<Vec<u8>>),         // A bulk string reply sharing a stored value's bytes instead of copying them.
     RespArray(Arc<Vec<RespResponse>>),      // An array of RESP responses.
+    Map(Arc<Vec<(RespResponse, RespResponse)>>), // Field-value pairs: a RESP3 map, or a flat array of alternating fields and values in RESP2.
     NullBulkString,                         // A null bulk string (e.g., "$-1\r\n").
+    Error(String),                          // An error response (e.g., "-ERR syntax error\r\n").
+    Integer(i64),                           // An integer response (e.g., ":1000\r\n").
+}
+
+/// `ParseError` describes why a RESP message could not be parsed yet.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    Incomplete,        // The message is not complete yet; more bytes are needed.
+    Protocol(String),  // The message violates the protocol (e.g. an oversized length); the connection should be closed.
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Incomplete => write!(f, "Incomplete RESP message"),
+            ParseError::Protocol(reason) => write!(f, "Protocol error: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// `RequestParser` parses the requests of one connection as their bytes arrive.
+///
+/// A RESP array that is still incomplete after a read is remembered with the elements parsed so far, so
+/// the next attempt resumes at the first missing element instead of parsing the request again from its
+/// start. Without this, a large request arriving over many reads would be parsed once per read.
+#[derive(Default)]
+pub struct RequestParser {
+    partial: Option<PartialArray>,  // The array request whose elements have only partly arrived, if any.
+}
+
+/// A RESP array request whose elements have only partly arrived.
+struct PartialArray {
+    elements: Vec<RespResponse>,  // The elements parsed so far.
+    remaining: usize,             // The number of elements still to parse.
+    pos: usize,                   // The position right after the last parsed element, from the start of the request.
 }
 
 impl RespResponse {
-    /// Serializes the `RespResponse` into a string format according to the RESP specification.
+    /// Serializes the `RespResponse` into bytes according to the RESP specification.
+    ///
+    /// This is a convenience wrapper around `serialize_into` for callers that need an owned buffer.
     ///
     /// # Returns
     ///
-    /// Returns the serialized string representing the `RespResponse`.
-    pub fn serialize(&self) -> String {
+    /// Returns the serialized bytes representing the `RespResponse`.
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.serialize_into(&mut buf);
+        buf
+    }
+
+    /// Serializes the `RespResponse` according to the RESP2 specification, appending the bytes to `buf`.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - The buffer the serialized bytes are appended to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut buf = Vec::new();
+    /// RespResponse::Integer(42).serialize_into(&mut buf);
+    /// assert_eq!(buf, b":42\r\n");
+    /// ```
+    pub fn serialize_into(&self, buf: &mut Vec<u8>) {
+        self.serialize_for(RESP2_PROTOCOL, buf);
+    }
+
+    /// Serializes the `RespResponse` in the given protocol version, appending the bytes to `buf`.
+    ///
+    /// RESP2 and RESP3 only differ in the types RESP3 added: a map is written as a RESP3 map (`%`) or as
+    /// a flat RESP2 array of alternating fields and values, and a null as the RESP3 null (`_`) or the RESP2
+    /// null bulk string. Nested arrays are written into the same buffer, so large replies are built
+    /// without intermediate allocations, and bulk strings are copied as-is, so they stay binary-safe.
+    ///
+    /// # Arguments
+    ///
+    /// * `protocol` - The protocol version negotiated with the client (`RESP2_PROTOCOL` or `RESP3_PROTOCOL`).
+    /// * `buf` - The buffer the serialized bytes are appended to.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut buf = Vec::new();
+    /// RespResponse::NullBulkString.serialize_for(RESP3_PROTOCOL, &mut buf);
+    /// assert_eq!(buf, b"_\r\n");
+    /// ```
+    pub fn serialize_for(&self, protocol: i64, buf: &mut Vec<u8>) {
+        // Writing into a `Vec<u8>` cannot fail.
         match self {
-            SimpleString(s) => format!("+{}\r\n", s),  // Serialize a simple string.
-            RespResponse::BulkString(s) => format!("${}\r\n{}\r\n", s.len(), s),  // Serialize a bulk string.
+            SimpleString(s) => {
+                let _ = write!(buf, "+{}\r\n", s);  // Serialize a simple string.
+            }
+            RespResponse::BulkString(s) => serialize_bulk_string(s, buf),
+            RespResponse::SharedBulkString(s) => serialize_bulk_string(s, buf),  // Borrow the stored value's bytes.
             RespResponse::RespArray(arr) => {
-                let mut array_join = String::new();
-                array_join.push_str(&format!("*{}\r\n", arr.len()));  // Start with the array length.
+                let _ = write!(buf, "*{}\r\n", arr.len());  // Start with the array length.
                 for resp in arr.iter() {
-                    array_join.push_str(&resp.serialize());  // Serialize each element in the array.
+                    resp.serialize_for(protocol, buf);  // Serialize each element in the array.
+                }
+            }
+            RespResponse::Map(pairs) => {
+                let _ = match protocol {
+                    RESP3_PROTOCOL => write!(buf, "%{}\r\n", pairs.len()),  // Start with the number of pairs.
+                    _ => write!(buf, "*{}\r\n", pairs.len() * 2),  // RESP2 has no maps, only flat arrays.
+                };
+                for (field, value) in pairs.iter() {
+                    field.serialize_for(protocol, buf);
+                    value.serialize_for(protocol, buf);
                 }
-                array_join
             }
-            RespResponse::NullBulkString => format!("${}\r\n", "-1".to_string())  // Serialize a null bulk string.
+            RespResponse::NullBulkString if protocol == RESP3_PROTOCOL => buf.extend_from_slice(b"_\r\n"),  // RESP3 has a single null type.
+            RespResponse::NullBulkString => buf.extend_from_slice(b"$-1\r\n"),  // Serialize a null bulk string.
+            RespResponse::Error(e) => {
+                let _ = write!(buf, "-{}\r\n", e);  // Serialize an error.
+            }
+            RespResponse::Integer(i) => {
+                let _ = write!(buf, ":{}\r\n", i);  // Serialize an integer.
+            }
         }
     }
 
     /// Extracts the command and its arguments from a `RespResponse`.
     ///
+    /// Command arrays must consist of non-null bulk strings only; any other element is a protocol error.
+    ///
     /// # Returns
     ///
-    /// Returns a tuple containing the command as a `String` and the arguments as an `Arc<Vec<RespResponse>>`.
+    /// Returns a tuple containing the command as a `String` and the arguments as an `Arc<Vec<RespResponse>>`,
+    /// or an error wrapping `ParseError::Protocol` if an element is not a bulk string.
     pub fn get_command_and_args(self) -> Result<(String, Arc<Vec<RespResponse>>)> {
         match self {
             SimpleString(s) => {
                 Ok((s, Arc::new(Vec::new())))  // If it's a simple string, treat it as a command with no arguments.
             },
             RespResponse::RespArray(arr) if !arr.is_empty() => {
+                match arr.iter().find(|element| !matches!(element, RespResponse::BulkString(_))) {
+                    Some(RespResponse::NullBulkString) => return Err(ParseError::Protocol("invalid bulk length".to_string()).into()),
+                    Some(element) => return Err(ParseError::Protocol(format!("expected '{}', got '{}'", DOLLAR_SIGN_CHAR, element.type_prefix())).into()),
+                    None => {}
+                }
                 if let RespResponse::BulkString(cmd) = &arr[0] {
-                    Ok((cmd.clone(), Arc::clone(&arr)))  // The first element is the command, and the rest are arguments.
+                    Ok((String::from_utf8_lossy(cmd).into_owned(), Arc::clone(&arr)))  // The first element is the command, and the rest are arguments.
                 } else {
                     Err(anyhow::anyhow!("First element in array is not a command string"))
                 }
@@ -55,100 +167,447 @@ impl RespResponse {
         }
     }
 
+    /// Returns the RESP type prefix of the `RespResponse` (e.g. `$` for bulk strings).
+    ///
+    /// # Returns
+    ///
+    /// Returns the first byte of the serialized form as a `char`.
+    pub fn type_prefix(&self) -> char {
+        match self {
+            SimpleString(_) => PLUS_CHAR,
+            RespResponse::BulkString(_) | RespResponse::SharedBulkString(_) | RespResponse::NullBulkString => DOLLAR_SIGN_CHAR,
+            RespResponse::RespArray(_) => ASTERISK_,
+            RespResponse::Map(_) => PERCENT_CHAR,
+            RespResponse::Error(_) => MINUS_CHAR,
+            RespResponse::Integer(_) => COLON_CHAR,
+        }
+    }
+
     /// Retrieves the value from a `RespResponse` as a `String`.
     ///
+    /// Bulk strings that are not valid UTF-8 are converted lossily; use `get_bytes` for binary-safe access.
+    ///
     /// # Returns
     ///
     /// Returns the value as a `String`. Panics if the response type is not a string.
     pub fn get_value(&self) -> String {
         match self {
             SimpleString(s) => s.to_string(),  // Return the value if it's a simple string.
-            RespResponse::BulkString(s) => s.to_string(),  // Return the value if it's a bulk string.
+            RespResponse::BulkString(s) => String::from_utf8_lossy(s).into_owned(),  // Return the value if it's a bulk string.
+            RespResponse::SharedBulkString(s) => String::from_utf8_lossy(s).into_owned(),
+            _ => panic!("Not implemented")  // Panic for unimplemented cases.
+        }
+    }
+
+    /// Retrieves the raw bytes of a `RespResponse`.
+    ///
+    /// # Returns
+    ///
+    /// Returns the value as a `Vec<u8>`. Panics if the response type is not a string.
+    pub fn get_bytes(&self) -> Vec<u8> {
+        match self {
+            SimpleString(s) => s.as_bytes().to_vec(),  // Return the bytes if it's a simple string.
+            RespResponse::BulkString(s) => s.clone(),  // Return the bytes if it's a bulk string.
+            RespResponse::SharedBulkString(s) => s.to_vec(),
             _ => panic!("Not implemented")  // Panic for unimplemented cases.
         }
     }
 }
 
-/// Parses a RESP message from a string.
+/// Appends a bulk string to `buf`: the payload length, the bytes as-is and a trailing CRLF.
+///
+/// # Arguments
+///
+/// * `bytes` - The payload of the bulk string.
+/// * `buf` - The buffer the serialized bytes are appended to.
+fn serialize_bulk_string(bytes: &[u8], buf: &mut Vec<u8>) {
+    let _ = write!(buf, "${}\r\n", bytes.len());  // Start with the payload length.
+    buf.extend_from_slice(bytes);
+    buf.extend_from_slice(CRLF.as_bytes());
+}
+
+impl RequestParser {
+    /// Parses one request sent by a client, choosing the format from its first byte.
+    ///
+    /// A request starting with `*` is a RESP array of bulk strings, the format client libraries use. Anything
+    /// else is an inline command, as typed in a telnet session (see `parse_inline`). The format is detected
+    /// again for every request, so a connection may mix both.
+    ///
+    /// An array left incomplete by the previous call is resumed where it stopped. Until a request has been
+    /// parsed, `command` must start at that request every time, with more bytes appended in between; the
+    /// number of bytes consumed is counted from that start.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The bytes received so far, starting with the request to parse.
+    /// * `max_bulk_len` - The largest bulk string length a client may declare.
+    /// * `max_inline_len` - The longest line (inline command or header) a client may send without its CRLF.
+    ///
+    /// # Returns
+    ///
+    /// Returns a tuple containing the request as a `RespArray` and the number of bytes consumed, or an error
+    /// wrapping `ParseError::Incomplete` if more bytes are needed and `ParseError::Protocol` if the request is invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut parser = RequestParser::default();
+    /// let (request, consumed) = parser.parse(b"SET key \"two words\"\r\n", 1024, 1024)?;
+    /// assert_eq!(consumed, 21);
+    ///
+    /// assert!(parser.parse(b"*1\r\n$4\r\nPI", 1024, 1024).is_err());
+    /// let (request, consumed) = parser.parse(b"*1\r\n$4\r\nPING\r\n", 1024, 1024)?;
+    /// assert_eq!(consumed, 14);
+    /// ```
+    pub fn parse(&mut self, command: &[u8], max_bulk_len: usize, max_inline_len: usize) -> Result<(RespResponse, usize)> {
+        let mut array = match self.partial.take() {
+            Some(array) => array,
+            None => match command.first() {
+                None => return Err(ParseError::Incomplete.into()),
+                Some(&first_byte) if first_byte as char == ASTERISK_ => parse_array_header(command, max_inline_len)?,
+                Some(_) => return parse_inline(command, max_inline_len),
+            },
+        };
+        match parse_array_elements(command, &mut array, max_bulk_len, max_inline_len) {
+            Ok(()) => Ok((RespResponse::RespArray(Arc::new(array.elements)), array.pos)),
+            Err(e) if matches!(e.downcast_ref::<ParseError>(), Some(ParseError::Incomplete)) => {
+                self.partial = Some(array);  // Keep the parsed elements for the next attempt.
+                Err(e)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Parses an inline command: a single line of arguments separated by spaces, terminated by LF or CRLF.
+///
+/// Arguments may be quoted as described in `split_inline_args`. A blank line is an empty array, which
+/// the caller ignores.
+///
+/// # Arguments
+///
+/// * `command` - The bytes to parse.
+/// * `max_inline_len` - The longest line a client may send without its line terminator.
+///
+/// # Returns
+///
+/// Returns a tuple containing the arguments as a `RespArray` of bulk strings and the number of bytes consumed.
+fn parse_inline(command: &[u8], max_inline_len: usize) -> Result<(RespResponse, usize)> {
+    let Some(newline) = command.iter().position(|&byte| byte == b'\n') else {
+        if command.len() > max_inline_len {
+            return Err(ParseError::Protocol("too big inline request".to_string()).into());
+        }
+        return Err(ParseError::Incomplete.into());
+    };
+    let line = command[..newline].strip_suffix(b"\r").unwrap_or(&command[..newline]);
+    let args = split_inline_args(line).ok_or_else(|| ParseError::Protocol("unbalanced quotes in request".to_string()))?;
+    Ok((RespResponse::RespArray(Arc::new(args.into_iter().map(RespResponse::BulkString).collect())), newline + 1))
+}
+
+/// Splits an inline command line into arguments, following Redis's `sdssplitargs`.
+///
+/// Arguments are separated by whitespace. In `"double quotes"` the escapes `\n`, `\r`, `\t`, `\b`, `\a`
+/// and `\xHH` are decoded and any other escaped byte is taken literally; in `'single quotes'` only `\'`
+/// is an escape. A closing quote must be followed by whitespace or the end of the line.
 ///
 /// # Arguments
 ///
-/// * `command` - The command string to parse.
+/// * `line` - The line, without its terminator.
 ///
 /// # Returns
 ///
-/// Returns a tuple containing the parsed `RespResponse` and the length of the command.
-pub fn parse_message(command: &str) -> Result<(RespResponse, i32)> {
-    match command.as_bytes()[0] as char {
-        PLUS_CHAR => parse_simple_string(command),  // Handle simple strings.
-        DOLLAR_SIGN_CHAR => parse_bulk_string(command),  // Handle bulk strings.
-        ASTERISK_ => parse_array(command),  // Handle arrays.
-        _ => Ok((SimpleString("-ERR unknown command".to_string()), 0)),  // Return an error for unknown commands.
+/// Returns the arguments, or `None` if a quote is unbalanced.
+pub fn split_inline_args(line: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let is_space = |byte: u8| byte.is_ascii_whitespace() || byte == 0x0b;  // C's isspace also accepts \v.
+    let hex_value = |byte: u8| (byte as char).to_digit(16).map(|digit| digit as u8);
+    let mut args = Vec::new();
+    let mut pos = 0;
+
+    loop {
+        while pos < line.len() && is_space(line[pos]) {
+            pos += 1;
+        }
+        if pos == line.len() {
+            return Some(args);
+        }
+
+        let mut arg = Vec::new();
+        let mut quote = None;  // The quote character of the argument being read, if any.
+        loop {
+            let byte = line.get(pos).copied();
+            match (quote, byte) {
+                (Some(_), None) => return None,  // The line ended inside quotes.
+                (None, None) => break,
+                (None, Some(byte)) if is_space(byte) => break,
+                (None, Some(byte @ (b'"' | b'\''))) => quote = Some(byte),
+                (None, Some(byte)) => arg.push(byte),
+                (Some(quote_byte), Some(byte)) if byte == quote_byte => {
+                    if line.get(pos + 1).is_some_and(|&next| !is_space(next)) {
+                        return None;  // A closing quote must end the argument.
+                    }
+                    pos += 1;
+                    break;
+                }
+                (Some(b'"'), Some(b'\\')) if pos + 3 < line.len() && line[pos + 1] == b'x' => {
+                    match (hex_value(line[pos + 2]), hex_value(line[pos + 3])) {
+                        (Some(high), Some(low)) => {
+                            arg.push(high * 16 + low);
+                            pos += 3;
+                        }
+                        _ => {
+                            arg.push(b'x');
+                            pos += 1;
+                        }
+                    }
+                }
+                (Some(b'"'), Some(b'\\')) if pos + 1 < line.len() => {
+                    pos += 1;
+                    arg.push(match line[pos] {
+                        b'n' => b'\n',
+                        b'r' => b'\r',
+                        b't' => b'\t',
+                        b'b' => 0x08,
+                        b'a' => 0x07,
+                        escaped => escaped,
+                    });
+                }
+                (Some(b'\''), Some(b'\\')) if line.get(pos + 1) == Some(&b'\'') => {
+                    pos += 1;
+                    arg.push(b'\'');
+                }
+                (Some(_), Some(byte)) => arg.push(byte),
+            }
+            pos += 1;
+        }
+        args.push(arg);
     }
 }
 
-/// Parses a simple string from a RESP command.
+/// Reads a CRLF-terminated line starting at `start`.
 ///
 /// # Arguments
 ///
-/// * `command` - The command string to parse.
+/// * `command` - The bytes to read from.
+/// * `start` - The position of the first byte of the line.
+/// * `max_len` - The longest line that may still be waiting for its CRLF.
+/// * `kind` - What the line holds, named in the error when it is too long (e.g. "bulk count string").
 ///
 /// # Returns
 ///
-/// Returns a tuple containing the parsed `RespResponse` and the length of the string.
-fn parse_simple_string(command: &str) -> Result<(RespResponse, i32)> {
-    let data: String = command[1..].to_string();  // Extract the data from the command.
-    Ok((SimpleString(data.clone()), data.len() as i32))  // Return the data as a `SimpleString`.
+/// Returns the line (without the CRLF) and the position right after the CRLF, `ParseError::Incomplete` if there is no CRLF
+/// yet, or `ParseError::Protocol` if more than `max_len` bytes arrived without one (so an unterminated line cannot grow the buffer forever).
+fn read_line<'a>(command: &'a [u8], start: usize, max_len: usize, kind: &str) -> Result<(&'a [u8], usize)> {
+    match command[start..].windows(CRLF.len()).position(|window| window == CRLF.as_bytes()) {
+        Some(offset) => Ok((&command[start..start + offset], start + offset + CRLF.len())),
+        None if command.len() - start > max_len => Err(ParseError::Protocol(format!("too big {}", kind)).into()),
+        None => Err(ParseError::Incomplete.into()),
+    }
+}
+
+/// Parses the integer that follows a RESP type prefix (e.g. the length in `$6\r\n`).
+///
+/// # Arguments
+///
+/// * `command` - The bytes to parse, starting with the type prefix.
+/// * `max_inline_len` - The longest line a client may send without its CRLF.
+/// * `kind` - What the header holds, for the error when it is too long.
+///
+/// # Returns
+///
+/// Returns the parsed integer (or `None` if it is not a valid integer) and the position right after its CRLF.
+fn parse_header(command: &[u8], max_inline_len: usize, kind: &str) -> Result<(Option<i64>, usize)> {
+    let (line, next) = read_line(command, 1, max_inline_len, kind)?;
+    let value = std::str::from_utf8(line).ok().and_then(|line| line.parse().ok());
+    Ok((value, next))
 }
 
 /// Parses a bulk string from a RESP command.
 ///
+/// The payload is read by its declared length, so it may contain any bytes, including CRLF; a length of 0
+/// is an empty string. A length of -1 (`$-1\r\n`) is the null bulk string, which has no payload.
+///
 /// # Arguments
 ///
-/// * `command` - The command string to parse.
+/// * `command` - The bytes to parse.
+/// * `max_bulk_len` - The largest length the bulk string may declare.
+/// * `max_inline_len` - The longest line (inline command or header) a client may send without its CRLF.
 ///
 /// # Returns
 ///
-/// Returns a tuple containing the parsed `RespResponse` and the length of the string.
-pub fn parse_bulk_string(command: &str) -> Result<(RespResponse, i32), anyhow::Error> {
-    let parts: Vec<&str> = command[1..].split(CRLF).collect();  // Split the command by CRLF.
-    if parts.len() < 2 {
-        return Err(anyhow::anyhow!("Invalid RESP bulk string format"));  // Return an error if the format is invalid.
+/// Returns a tuple containing the parsed `RespResponse` and the number of bytes consumed.
+pub fn parse_bulk_string(command: &[u8], max_bulk_len: usize, max_inline_len: usize) -> Result<(RespResponse, usize), anyhow::Error> {
+    let (length, start) = parse_header(command, max_inline_len, "bulk count string")?;  // Parse the length of the bulk string.
+    if length == Some(-1) {
+        return Ok((RespResponse::NullBulkString, start));
+    }
+    let length = length
+        .and_then(|length| usize::try_from(length).ok())
+        .filter(|&length| length <= max_bulk_len)
+        .ok_or_else(|| ParseError::Protocol("invalid bulk length".to_string()))?;
+
+    let end = start + length;
+    if command.len() < end + CRLF.len() {
+        return Err(ParseError::Incomplete.into());  // Wait for the rest of the payload.
     }
+    let terminator = &command[end..end + CRLF.len()];
+    if terminator != CRLF.as_bytes() {
+        let got = terminator.escape_ascii();  // Escaped, so the reply cannot carry the client's CR or LF.
+        return Err(ParseError::Protocol(format!("expected '\\r\\n', got '{}'", got)).into());
+    }
+
+    let data = command[start..end].to_vec();  // Extract the data.
+    Ok((RespResponse::BulkString(data), end + CRLF.len()))  // Return the data as a `BulkString`.
+}
 
-    let length: i32 = parts[0].parse().map_err(|e| anyhow::anyhow!("Failed to parse length: {}", e))?;  // Parse the length of the bulk string.
-    let data: String = parts[1].to_string();  // Extract the data.
+/// Parses the header of an array request, such as `*3\r\n`.
+///
+/// # Arguments
+///
+/// * `command` - The bytes to parse, starting with the `*`.
+/// * `max_inline_len` - The longest line (inline command or header) a client may send without its CRLF.
+///
+/// # Returns
+///
+/// Returns the array with no elements parsed yet, positioned right after the header.
+fn parse_array_header(command: &[u8], max_inline_len: usize) -> Result<PartialArray> {
+    let (arr_size, pos) = parse_header(command, max_inline_len, "mbulk count string")?;  // Parse the size of the array.
+    let arr_size = arr_size
+        .and_then(|arr_size| usize::try_from(arr_size).ok())
+        .filter(|&arr_size| arr_size <= MAX_MULTIBULK_LEN)
+        .ok_or_else(|| ParseError::Protocol("invalid multibulk length".to_string()))?;
 
-    Ok((RespResponse::BulkString(data), length))  // Return the data as a `BulkString`.
+    // Grown as elements arrive, so the declared size alone does not allocate.
+    Ok(PartialArray { elements: Vec::new(), remaining: arr_size, pos })
 }
 
-/// Parses an array from a RESP command.
+/// Parses the remaining elements of an array request.
+///
+/// Requests are arrays of bulk strings, so every element must start with `$`; anything else (including a
+/// nested array) is a protocol error, as in Redis. The elements are parsed in place rather than recursively,
+/// so no input can nest deep enough to overflow the stack. Every complete element is added to `array` as
+/// soon as it is parsed, so an incomplete request keeps its progress.
 ///
 /// # Arguments
 ///
-/// * `command` - The command string to parse.
+/// * `command` - The bytes to parse, starting with the array's header.
+/// * `array` - The array being parsed.
+/// * `max_bulk_len` - The largest length a bulk string element may declare.
+/// * `max_inline_len` - The longest line (inline command or header) a client may send without its CRLF.
 ///
 /// # Returns
 ///
-/// Returns a tuple containing the parsed `RespResponse` and the size of the array.
-pub fn parse_array(command: &str) -> Result<(RespResponse, i32)> {
-    let parts: Vec<&str> = command[1..].split(CRLF).collect();  // Split the command by CRLF.
-    let arr_size: i32 = parts[0].parse().map_err(|e| anyhow::anyhow!("Failed to parse array size: {}", e))?;  // Parse the size of the array.
+/// Returns `Ok(())` once every element is parsed, or the error that stopped parsing.
+fn parse_array_elements(command: &[u8], array: &mut PartialArray, max_bulk_len: usize, max_inline_len: usize) -> Result<()> {
+    while array.remaining > 0 {
+        match command.get(array.pos) {
+            None => return Err(ParseError::Incomplete.into()),
+            Some(&prefix) if prefix as char != DOLLAR_SIGN_CHAR => {
+                return Err(ParseError::Protocol(format!("expected '{}', got '{}'", DOLLAR_SIGN_CHAR, prefix as char)).into());
+            }
+            Some(_) => {}
+        }
+        let (response, consumed) = parse_bulk_string(&command[array.pos..], max_bulk_len, max_inline_len)?;  // Parse the element as a bulk string.
+        if matches!(response, RespResponse::NullBulkString) {
+            return Err(ParseError::Protocol("invalid bulk length".to_string()).into());
+        }
+        array.elements.push(response);  // Add the parsed element to the array.
 
-    let mut responses = Vec::with_capacity(arr_size as usize);  // Prepare a vector to hold the array elements.
-    let mut index = 1;
+        array.pos += consumed;  // Move to the next element.
+        array.remaining -= 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    for _ in 0..arr_size {
-        if index >= parts.len() {
-            return Err(anyhow::anyhow!("Unexpected end of input while parsing array elements"));  // Return an error if the input is incomplete.
+    /// Returns the protocol error reason parsing `input` fails with, panicking on any other outcome.
+    fn protocol_error(input: &[u8]) -> String {
+        match RequestParser::default().parse(input, 1024, 1024).unwrap_err().downcast::<ParseError>() {
+            Ok(ParseError::Protocol(reason)) => reason,
+            other => panic!("expected a protocol error, got {:?}", other),
         }
+    }
 
-        let element_str = parts[index].to_string() + CRLF + parts[index + 1];  // Reconstruct the element string.
-        let (response, _) = parse_message(&element_str)?;  // Parse the element as a RESP message.
-        responses.push(response);  // Add the parsed element to the array.
+    #[test]
+    fn deeply_nested_arrays_are_rejected_without_recursing() {
+        let input = b"*1\r\n".repeat(200_000);
+        assert_eq!(protocol_error(&input), "expected '$', got '*'");
+    }
 
-        index += 2;  // Move to the next element.
+    #[test]
+    fn non_bulk_elements_are_rejected() {
+        assert_eq!(protocol_error(b"*2\r\n$4\r\nPING\r\n:1\r\n"), "expected '$', got ':'");
+        assert_eq!(protocol_error(b"*1\r\n$-1\r\n"), "invalid bulk length");
     }
 
-    Ok((RespResponse::RespArray(Arc::new(responses)), arr_size))  // Return the parsed array.
+    #[test]
+    fn bulk_strings_must_end_with_crlf() {
+        assert_eq!(protocol_error(b"*1\r\n$4\r\nPINGxx"), "expected '\\r\\n', got 'xx'");
+        assert_eq!(protocol_error(b"*1\r\n$2\r\nhi\n\r"), "expected '\\r\\n', got '\\n\\r'");
+    }
+
+    #[test]
+    fn partial_requests_are_incomplete() {
+        let pipeline = b"PING\r\n*2\r\n$4\r\nECHO\r\n$2\r\nhi\r\n";
+        for end in 0..6 {
+            let error = RequestParser::default().parse(&pipeline[..end], 1024, 1024).unwrap_err();
+            assert_eq!(error.downcast_ref::<ParseError>(), Some(&ParseError::Incomplete));
+        }
+        let (_, consumed) = RequestParser::default().parse(pipeline, 1024, 1024).unwrap();
+        assert_eq!(consumed, 6);
+        for end in 6..pipeline.len() {
+            let error = RequestParser::default().parse(&pipeline[6..end], 1024, 1024).unwrap_err();
+            assert_eq!(error.downcast_ref::<ParseError>(), Some(&ParseError::Incomplete));
+        }
+        let (message, consumed) = RequestParser::default().parse(&pipeline[6..], 1024, 1024).unwrap();
+        assert_eq!((message.serialize(), consumed), (pipeline[6..].to_vec(), pipeline.len() - 6));
+    }
+
+    #[test]
+    fn bulk_and_multibulk_lengths_are_limited() {
+        assert_eq!(protocol_error(b"*1\r\n$1025\r\n"), "invalid bulk length");
+        assert_eq!(protocol_error(format!("*{}\r\n", MAX_MULTIBULK_LEN + 1).as_bytes()), "invalid multibulk length");
+
+        // Right at the limit, the bulk string is accepted.
+        let (message, _) = RequestParser::default().parse(format!("*1\r\n$1024\r\n{}\r\n", "x".repeat(1024)).as_bytes(), 1024, 1024).unwrap();
+        assert_eq!(message.serialize().len(), 4 + 7 + 1024 + 2);
+    }
+
+    #[test]
+    fn inline_and_count_lines_are_limited() {
+        assert_eq!(protocol_error(&[b'a'; 1025]), "too big inline request");
+        assert_eq!(protocol_error(&[b"*1\r\n$".as_slice(), &[b'1'; 1025]].concat()), "too big bulk count string");
+        assert_eq!(protocol_error(&[b"*".as_slice(), &[b'1'; 1025]].concat()), "too big mbulk count string");
+
+        // Right at the limit, the line is only incomplete.
+        let error = RequestParser::default().parse(&[b'a'; 1024], 1024, 1024).unwrap_err();
+        assert_eq!(error.downcast_ref::<ParseError>(), Some(&ParseError::Incomplete));
+    }
+
+    #[test]
+    fn inline_arguments_may_be_quoted() {
+        let request = b"SET k \"a b\\x41\" 'c\\'d'\n";
+        let (message, consumed) = RequestParser::default().parse(request, 1024, 1024).unwrap();
+        assert_eq!(consumed, request.len());
+        assert_eq!(message.serialize(), b"*4\r\n$3\r\nSET\r\n$1\r\nk\r\n$4\r\na bA\r\n$3\r\nc'd\r\n");
+        assert_eq!(protocol_error(b"SET k \"unbalanced\r\n"), "unbalanced quotes in request");
+    }
+
+    #[test]
+    fn request_parser_resumes_after_the_parsed_elements() {
+        let request = b"*3\r\n$3\r\nSET\r\n$1\r\nk\r\n$5\r\nvalue\r\n";
+        let mut parser = RequestParser::default();
+        for end in 0..request.len() {
+            let error = parser.parse(&request[..end], 1024, 1024).unwrap_err();
+            assert_eq!(error.downcast_ref::<ParseError>(), Some(&ParseError::Incomplete));
+        }
+        assert_eq!(parser.partial.as_ref().map(|array| array.elements.len()), Some(2));
+
+        let (message, consumed) = parser.parse(request, 1024, 1024).unwrap();
+        assert_eq!(consumed, request.len());
+        assert_eq!(message.serialize(), request);
+        assert!(parser.partial.is_none());
+    }
 }
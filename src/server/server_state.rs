@@ -0,0 +1,154 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Instant;
+
+use rand::Rng;
+use tokio::sync::broadcast;
+
+use crate::server::common_variables::{MONITOR_CHANNEL_CAPACITY, NODE_ID_LEN};
+use crate::server::latency::LatencyMonitor;
+use crate::server::slowlog::SlowLog;
+use crate::server::snapshot::unix_time_secs;
+
+/// `SharedState` is an alias for an `Arc`-wrapped `ServerState`, shared by all client handlers and background tasks.
+pub type SharedState = Arc<ServerState>;
+
+/// `ServerState` holds the runtime state of the server that is not part of the keyspace.
+#[derive(Debug)]
+pub struct ServerState {
+    /// Whether the background sweeper actively removes expired keys (toggled by `DEBUG SET-ACTIVE-EXPIRE`).
+    pub active_expire: AtomicBool,
+    /// Number of keys changed since the last successful save (bumped through `mark_dirty`).
+    pub dirty: AtomicU64,
+    /// The id handed to the next client connection (reported by `CLIENT ID` and `HELLO`).
+    pub next_client_id: AtomicU64,
+    /// Number of currently connected clients (compared against `maxclients` when a connection is accepted).
+    pub connected_clients: AtomicU64,
+    /// Number of key lookups by read commands that found a live key (reported as `keyspace_hits`).
+    pub keyspace_hits: AtomicU64,
+    /// Number of key lookups by read commands that found no key or an expired one (reported as `keyspace_misses`).
+    pub keyspace_misses: AtomicU64,
+    /// Unix time (in seconds) of the last successful save.
+    pub last_save: AtomicU64,
+    /// Unix time (in seconds) of the last background save attempt.
+    pub last_bgsave_attempt: AtomicU64,
+    /// Whether a background save is currently running.
+    pub bgsave_in_progress: AtomicBool,
+    /// Whether the last background save succeeded.
+    pub last_bgsave_ok: AtomicBool,
+    /// Random 40-character hex id of this server run, distinct from the replication id (reported as `run_id`).
+    /// It changes on every restart, which lets clients detect one.
+    pub run_id: String,
+    /// When the server started (reported as `uptime_in_seconds` and `uptime_in_days`).
+    pub started: Instant,
+    /// Random 40-character hex id of this node, fixed for the lifetime of the process (reported by `CLUSTER MYID`).
+    pub node_id: String,
+    /// Random 40-character hex replication id, reported as `master_replid` and regenerated by `DEBUG CHANGE-REPL-ID`.
+    pub replid: RwLock<String>,
+    /// Channel on which every executed command is published, formatted for connections in MONITOR mode.
+    pub monitor: broadcast::Sender<String>,
+    /// The most recent commands that exceeded `slowlog-log-slower-than` (read by `SLOWLOG`).
+    pub slowlog: Mutex<SlowLog>,
+    /// Latency spikes per event category that exceeded `latency-monitor-threshold` (read by `LATENCY`).
+    pub latency: Mutex<LatencyMonitor>,
+}
+
+impl ServerState {
+    /// Creates a new `ServerState` with default settings, wrapped in an `Arc`.
+    ///
+    /// # Returns
+    ///
+    /// Returns a `SharedState` with active expiration enabled, the start and last save time set to now and a fresh run id, node id and replication id.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let state = ServerState::new_shared();
+    /// ```
+    pub fn new_shared() -> SharedState {
+        Arc::new(ServerState {
+            active_expire: AtomicBool::new(true),
+            dirty: AtomicU64::new(0),
+            next_client_id: AtomicU64::new(1),
+            connected_clients: AtomicU64::new(0),
+            keyspace_hits: AtomicU64::new(0),
+            keyspace_misses: AtomicU64::new(0),
+            last_save: AtomicU64::new(unix_time_secs()),
+            last_bgsave_attempt: AtomicU64::new(0),
+            bgsave_in_progress: AtomicBool::new(false),
+            last_bgsave_ok: AtomicBool::new(true),
+            run_id: random_hex_id(NODE_ID_LEN),
+            started: Instant::now(),
+            node_id: random_hex_id(NODE_ID_LEN),
+            replid: RwLock::new(random_hex_id(NODE_ID_LEN)),
+            monitor: broadcast::channel(MONITOR_CHANNEL_CAPACITY).0,
+            slowlog: Mutex::new(SlowLog::default()),
+            latency: Mutex::new(LatencyMonitor::default()),
+        })
+    }
+
+    /// Counts changes to the keyspace towards the next automatic save.
+    ///
+    /// Write commands call this with the number of keys they actually changed, so a no-op write
+    /// counts nothing and a multi-key write counts every key. The counter is read by the save points,
+    /// `INFO persistence` and `LASTSAVE` users, and only goes down when a save that included the
+    /// changes succeeds.
+    ///
+    /// # Arguments
+    ///
+    /// * `changes` - The number of keys changed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// state.mark_dirty(1);
+    /// ```
+    pub fn mark_dirty(&self, changes: u64) {
+        self.dirty.fetch_add(changes, Ordering::Relaxed);
+    }
+
+    /// Hands out a unique id for a new client connection, starting from 1.
+    ///
+    /// # Returns
+    ///
+    /// Returns an id that no other connection of this process got.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let id = state.new_client_id();
+    /// ```
+    pub fn new_client_id(&self) -> u64 {
+        self.next_client_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Counts a key lookup by a read command as a keyspace hit or miss.
+    ///
+    /// # Arguments
+    ///
+    /// * `hit` - Whether the lookup found a live key; a missing or expired key is a miss.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// state.record_keyspace_lookup(true);
+    /// ```
+    pub fn record_keyspace_lookup(&self, hit: bool) {
+        let counter = if hit { &self.keyspace_hits } else { &self.keyspace_misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Generates a random lowercase hexadecimal id.
+///
+/// # Arguments
+///
+/// * `len` - The number of hex characters.
+///
+/// # Returns
+///
+/// Returns the id as a `String`.
+pub fn random_hex_id(len: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..len).map(|_| format!("{:x}", rng.gen_range(0..16))).collect()
+}
@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::server::arg_handler::ArgsCli;
+use crate::server::common_variables::{SLOWLOG_ENTRY_MAX_ARGC, SLOWLOG_ENTRY_MAX_STRING};
+use crate::server::resp_response::RespResponse;
+use crate::server::snapshot::unix_time_secs;
+
+/// A command that took longer than `slowlog-log-slower-than` to execute.
+#[derive(Debug)]
+pub struct SlowLogEntry {
+    id: u64,               // Unique, increasing id of the entry.
+    timestamp: u64,        // Unix time (in seconds) at which the command was logged.
+    duration_micros: u64,  // Execution time in microseconds.
+    args: Vec<Vec<u8>>,    // The command and its arguments, possibly shortened.
+    client_addr: String,   // Address of the client that sent the command, or empty if unknown.
+}
+
+/// `SlowLog` keeps the most recent slow commands, newest first, up to `slowlog-max-len` entries.
+#[derive(Debug, Default)]
+pub struct SlowLog {
+    entries: VecDeque<SlowLogEntry>,
+    next_id: u64,
+}
+
+impl SlowLog {
+    /// Records a command if its execution time reaches the `slowlog-log-slower-than` threshold.
+    ///
+    /// A negative threshold disables the slow log and a threshold of 0 records every command. Like
+    /// Redis, at most 32 arguments and 128 bytes per argument are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The full command, including its name.
+    /// * `duration` - How long the command took to execute.
+    /// * `client_addr` - The address of the client that sent the command, if any.
+    /// * `args_cli` - The command-line arguments holding the threshold and maximum length.
+    pub fn record(&mut self, args: &[RespResponse], duration: Duration, client_addr: Option<&SocketAddr>, args_cli: &ArgsCli) {
+        let threshold = args_cli.slowlog_log_slower_than;
+        let duration_micros = duration.as_micros() as u64;
+        if threshold < 0 || duration_micros < threshold as u64 {
+            return;
+        }
+
+        let entry = SlowLogEntry {
+            id: self.next_id,
+            timestamp: unix_time_secs(),
+            duration_micros,
+            args: shorten_args(args),
+            client_addr: client_addr.map(SocketAddr::to_string).unwrap_or_default(),
+        };
+        self.next_id += 1;
+        self.entries.push_front(entry);
+        self.entries.truncate(args_cli.slowlog_max_len);
+    }
+
+    /// Returns the number of entries currently in the slow log.
+    ///
+    /// # Returns
+    ///
+    /// Returns the entry count.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Removes every entry from the slow log. Ids keep increasing afterwards.
+    pub fn reset(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Describes the most recent entries the way `SLOWLOG GET` reports them.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - The maximum number of entries, or `None` for all of them.
+    ///
+    /// # Returns
+    ///
+    /// Returns an array of entries, newest first. Each entry is an array of its id, unix timestamp,
+    /// duration in microseconds, arguments, client address and client name (always empty).
+    pub fn get(&self, count: Option<usize>) -> RespResponse {
+        let entries = self.entries.iter()
+            .take(count.unwrap_or(self.entries.len()))
+            .map(|entry| {
+                let args = entry.args.iter().map(|arg| RespResponse::BulkString(arg.clone())).collect();
+                RespResponse::RespArray(Arc::new(vec![
+                    RespResponse::Integer(entry.id as i64),
+                    RespResponse::Integer(entry.timestamp as i64),
+                    RespResponse::Integer(entry.duration_micros as i64),
+                    RespResponse::RespArray(Arc::new(args)),
+                    RespResponse::BulkString(entry.client_addr.clone().into_bytes()),
+                    RespResponse::BulkString(Vec::new()),
+                ]))
+            })
+            .collect();
+        RespResponse::RespArray(Arc::new(entries))
+    }
+}
+
+/// Shortens a command for the slow log, like Redis does.
+///
+/// Only the first 31 arguments are kept when there are more than 32, followed by
+/// `... (N more arguments)`. Arguments longer than 128 bytes are cut and end with `... (N more bytes)`.
+///
+/// # Arguments
+///
+/// * `args` - The full command, including its name.
+///
+/// # Returns
+///
+/// Returns the arguments to store.
+fn shorten_args(args: &[RespResponse]) -> Vec<Vec<u8>> {
+    let kept = if args.len() > SLOWLOG_ENTRY_MAX_ARGC { SLOWLOG_ENTRY_MAX_ARGC - 1 } else { args.len() };
+    let mut shortened: Vec<Vec<u8>> = args[..kept].iter()
+        .map(|arg| {
+            let mut bytes = arg.get_bytes();
+            if bytes.len() > SLOWLOG_ENTRY_MAX_STRING {
+                let more = bytes.len() - SLOWLOG_ENTRY_MAX_STRING;
+                bytes.truncate(SLOWLOG_ENTRY_MAX_STRING);
+                bytes.extend_from_slice(format!("... ({} more bytes)", more).as_bytes());
+            }
+            bytes
+        })
+        .collect();
+    if kept < args.len() {
+        shortened.push(format!("... ({} more arguments)", args.len() - kept).into_bytes());
+    }
+    shortened
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+    use crate::server::arg_handler::ArgHandler;
+    use crate::server::command_handler::execute_command;
+    use crate::server::command_handler::tests::setup;
+
+    /// Builds the arguments of a server started with `args`.
+    fn server_args(args: &[&str]) -> ArgsCli {
+        Arc::new(ArgHandler::parse_from(std::iter::once("redis-rust").chain(args.iter().copied())))
+    }
+
+    /// Builds the arguments of a command, starting with its name.
+    fn command(parts: &[&str]) -> Vec<RespResponse> {
+        parts.iter().map(|part| RespResponse::BulkString(part.as_bytes().to_vec())).collect()
+    }
+
+    #[test]
+    fn records_only_commands_reaching_the_threshold() {
+        let args_cli = server_args(&["--slowlog-log-slower-than", "1000"]);
+        let mut slowlog = SlowLog::default();
+        slowlog.record(&command(&["GET", "fast"]), Duration::from_micros(999), None, &args_cli);
+        slowlog.record(&command(&["GET", "slow"]), Duration::from_micros(1000), None, &args_cli);
+        assert_eq!(slowlog.len(), 1);
+
+        let disabled = server_args(&["--slowlog-log-slower-than", "-1"]);
+        slowlog.record(&command(&["GET", "slow"]), Duration::from_secs(1), None, &disabled);
+        assert_eq!(slowlog.len(), 1);
+    }
+
+    #[test]
+    fn keeps_the_newest_entries_up_to_the_maximum_length() {
+        let args_cli = server_args(&["--slowlog-log-slower-than", "0", "--slowlog-max-len", "2"]);
+        let mut slowlog = SlowLog::default();
+        for key in ["a", "b", "c"] {
+            slowlog.record(&command(&["GET", key]), Duration::ZERO, None, &args_cli);
+        }
+        assert_eq!(slowlog.len(), 2);
+        let ids: Vec<u64> = slowlog.entries.iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, [2, 1]);
+
+        slowlog.reset();
+        slowlog.record(&command(&["GET", "d"]), Duration::ZERO, None, &args_cli);
+        assert_eq!(slowlog.entries[0].id, 3);  // Ids keep increasing after a reset.
+        let RespResponse::RespArray(entries) = slowlog.get(Some(1)) else { panic!("expected an array") };
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn shortens_long_commands() {
+        let long_value = "x".repeat(SLOWLOG_ENTRY_MAX_STRING + 5);
+        let shortened = shorten_args(&command(&["SET", "k", &long_value]));
+        assert_eq!(shortened[2], format!("{}... (5 more bytes)", &long_value[..SLOWLOG_ENTRY_MAX_STRING]).into_bytes());
+
+        let many: Vec<String> = (0..SLOWLOG_ENTRY_MAX_ARGC + 3).map(|index| index.to_string()).collect();
+        let shortened = shorten_args(&command(&many.iter().map(String::as_str).collect::<Vec<_>>()));
+        assert_eq!(shortened.len(), SLOWLOG_ENTRY_MAX_ARGC);
+        assert_eq!(shortened.last().unwrap(), b"... (4 more arguments)");
+    }
+
+    #[test]
+    fn debug_sleep_is_recorded_as_a_slow_command() {
+        let (db, args_cli) = setup(&["--slowlog-log-slower-than", "10000"]);
+        let reply = String::from_utf8(execute_command(&db, &args_cli, b"PING\r\nDEBUG SLEEP 0.02\r\nSLOWLOG GET")).unwrap();
+        let lines: Vec<&str> = reply.split("\r\n").collect();
+
+        assert_eq!(lines[..5], ["+PONG", "+OK", "*1", "*6", ":0"]);  // Only DEBUG SLEEP was slow enough.
+        let micros: u64 = lines[6].trim_start_matches(':').parse().unwrap();
+        assert!(micros >= 20_000, "duration {}", micros);
+        assert!(reply.ends_with("*3\r\n$5\r\nDEBUG\r\n$5\r\nSLEEP\r\n$4\r\n0.02\r\n$0\r\n\r\n$0\r\n\r\n"));
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::server::arg_handler::ArgsCli;
+use crate::server::common_variables::{AUTO_SAVE_CYCLE_MS, BGSAVE_RETRY_DELAY_SECS, Db, LATENCY_EVENT_RDB_SAVE};
+use crate::server::rdb_writer::{encode_database, write_rdb_file};
+use crate::server::server_state::SharedState;
+
+/// Saves the database to the RDB file synchronously, blocking the caller until the file is written.
+///
+/// The time taken is reported to the latency monitor as the `rdb-save` event.
+///
+/// # Arguments
+///
+/// * `db` - The shared database instance.
+/// * `args_cli` - The command-line arguments, which determine the RDB path.
+/// * `state` - The shared server state.
+///
+/// # Returns
+///
+/// Returns `Ok(())` if the snapshot was written, or an error if it could not be.
+pub fn save(db: &Db, args_cli: &ArgsCli, state: &SharedState) -> Result<(), anyhow::Error> {
+    let started = Instant::now();
+    let (contents, dirty) = {
+        let db = db.lock().unwrap();
+        (encode_database(&db), state.dirty.load(Ordering::Relaxed))
+    };
+    write_rdb_file(&args_cli.rdb_file_path(), &contents)?;
+    finish_save(state, dirty);
+    state.latency.lock().unwrap().record(LATENCY_EVENT_RDB_SAVE, started.elapsed(), args_cli);
+    Ok(())
+}
+
+/// Starts saving the database to the RDB file in the background.
+///
+/// The keyspace is copied while holding the lock, then encoded and written on a blocking thread so
+/// clients are served in the meantime. Writes that happen during the save stay counted as dirty.
+///
+/// # Arguments
+///
+/// * `db` - The shared database instance.
+/// * `args_cli` - The command-line arguments, which determine the RDB path.
+/// * `state` - The shared server state.
+///
+/// # Returns
+///
+/// Returns `true` if a background save was started, or `false` if one is already in progress.
+pub fn bgsave(db: &Db, args_cli: &ArgsCli, state: &SharedState) -> bool {
+    if state.bgsave_in_progress.swap(true, Ordering::AcqRel) {
+        return false;
+    }
+    state.last_bgsave_attempt.store(unix_time_secs(), Ordering::Relaxed);
+
+    let (snapshot, dirty) = {
+        let db = db.lock().unwrap();
+        (db.clone(), state.dirty.load(Ordering::Relaxed))
+    };
+    let path = args_cli.rdb_file_path();
+    let state = state.clone();
+
+    run_in_background(move || {
+        match write_rdb_file(&path, &encode_database(&snapshot)) {
+            Ok(()) => {
+                finish_save(&state, dirty);
+                state.last_bgsave_ok.store(true, Ordering::Relaxed);
+                println!("Background saving terminated with success");
+            }
+            Err(e) => {
+                state.last_bgsave_ok.store(false, Ordering::Relaxed);
+                eprintln!("Background saving error: {:#}", e);
+            }
+        }
+        state.bgsave_in_progress.store(false, Ordering::Release);
+    });
+    true
+}
+
+/// Runs `task` on a thread of its own, so the caller does not wait for it.
+///
+/// Inside a tokio runtime the task goes to the runtime's blocking pool; without one (e.g. when commands
+/// are executed directly in tests) a plain thread is spawned instead, as `spawn_blocking` would panic.
+///
+/// # Arguments
+///
+/// * `task` - The work to run.
+///
+/// # Examples
+///
+/// ```
+/// run_in_background(move || drop(old_keyspace));
+/// ```
+pub fn run_in_background(task: impl FnOnce() + Send + 'static) {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => drop(handle.spawn_blocking(task)),
+        Err(_) => drop(std::thread::spawn(task)),
+    }
+}
+
+/// Periodically checks the configured save points and starts a background save when one is met.
+///
+/// A save point `(seconds, changes)` is met once at least `changes` writes happened and at least
+/// `seconds` passed since the last successful save. After a failed background save, the next attempt
+/// is delayed by `BGSAVE_RETRY_DELAY_SECS`.
+///
+/// # Arguments
+///
+/// * `db` - The shared database instance.
+/// * `args_cli` - The command-line arguments holding the save points.
+/// * `state` - The shared server state.
+///
+/// # Examples
+///
+/// ```
+/// tokio::spawn(auto_save_cycle(db.clone(), args_cli.clone(), state.clone()));
+/// ```
+pub async fn auto_save_cycle(db: Db, args_cli: ArgsCli, state: SharedState) {
+    let save_points = args_cli.save_points();
+    let mut interval = tokio::time::interval(Duration::from_millis(AUTO_SAVE_CYCLE_MS));
+
+    loop {
+        interval.tick().await;
+
+        let now = unix_time_secs();
+        let dirty = state.dirty.load(Ordering::Relaxed);
+        let since_last_save = now.saturating_sub(state.last_save.load(Ordering::Relaxed));
+        let can_retry = state.last_bgsave_ok.load(Ordering::Relaxed)
+            || now.saturating_sub(state.last_bgsave_attempt.load(Ordering::Relaxed)) >= BGSAVE_RETRY_DELAY_SECS;
+
+        let save_point_met = save_points.iter()
+            .any(|&(seconds, changes)| changes > 0 && dirty >= changes && since_last_save >= seconds);
+
+        if save_point_met && can_retry {
+            println!("{} changes in {} seconds. Saving...", dirty, since_last_save);
+            bgsave(&db, &args_cli, &state);
+        }
+    }
+}
+
+/// Returns the current Unix time in seconds.
+///
+/// # Returns
+///
+/// Returns the number of seconds since the Unix epoch, or 0 if the system clock is before it.
+pub fn unix_time_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Records a successful save: the writes included in the snapshot are no longer dirty.
+///
+/// A SAVE can overlap a background save, and then both snapshots count the same writes. The counter
+/// therefore stops at zero instead of wrapping around, which would make every save point look met.
+///
+/// # Arguments
+///
+/// * `state` - The shared server state.
+/// * `saved_dirty` - The dirty counter at the time the snapshot was taken.
+fn finish_save(state: &SharedState, saved_dirty: u64) {
+    let _ = state.dirty.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |dirty| Some(dirty.saturating_sub(saved_dirty)));
+    state.last_save.store(unix_time_secs(), Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::server_state::ServerState;
+
+    #[test]
+    fn overlapping_saves_do_not_wrap_the_dirty_counter() {
+        let state = ServerState::new_shared();
+        state.dirty.store(5, Ordering::Relaxed);
+
+        // A SAVE and a BGSAVE both snapshotted the same 5 writes, then 1 more write happened.
+        state.mark_dirty(1);
+        finish_save(&state, 5);
+        assert_eq!(state.dirty.load(Ordering::Relaxed), 1);
+        finish_save(&state, 5);
+        assert_eq!(state.dirty.load(Ordering::Relaxed), 0);
+    }
+}